@@ -0,0 +1,428 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::{Parser, Subcommand};
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+
+use crate::{
+    daemon::{self, client, protocol::Request},
+    download,
+    model::{
+        backend::BackendKind,
+        config::Config,
+        counts::STATUS_GROUPS,
+        downloader::{DownloadManager, DownloadStatus, DownloadTask, Downloader, export_tasks},
+        hooks::Hooks,
+    },
+    native_host,
+};
+
+/// A modern TUI download manager, run without a command to open the TUI
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+    /// URL to add, handed to the open TUI if there is one
+    pub url: Option<String>,
+    /// Add the URL right away with the default preset instead of opening the add task flow
+    #[arg(long, requires = "url")]
+    pub enqueue: bool,
+}
+
+/// Commands working on the tasks without opening the TUI, through the daemon when it runs.
+/// Tasks are identified by their id, or any prefix of it that is unique.
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// Queue a download, it starts right away in the daemon or else the next time cupcake runs
+    Add {
+        url: String,
+        /// Directory to download into, the downloads directory by default
+        #[arg(long)]
+        dest: Option<PathBuf>,
+        /// File name, asked from the backend by default
+        #[arg(long)]
+        name: Option<String>,
+        /// Preset from the config whose yt-dlp arguments are used
+        #[arg(long)]
+        preset: Option<String>,
+        /// Connections a direct file is downloaded over, `connections` from the config by default
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=32))]
+        connections: Option<u32>,
+        /// Backend to download with, `yt-dlp`, `http` or `aria2`, chosen from the URL by default
+        #[arg(long)]
+        backend: Option<BackendKind>,
+    },
+    /// List the tasks
+    List {
+        /// Only tasks with this status or in this group, e.g. `failed` or `unfinished`
+        #[arg(long)]
+        status: Option<String>,
+        /// Print the tasks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove tasks, downloaded files are left untouched
+    Remove {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Queue tasks to start over from scratch
+    Retry {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Mark unfinished tasks as paused so they aren't started
+    Pause {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Queue paused or failed tasks again
+    Resume {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Export tasks as JSON, all of them without ids
+    Export {
+        ids: Vec<String>,
+        /// Directory the export is written to, the current directory by default
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Download a URL right here without the TUI or storing a task, and wait until it's done.
+    /// Exits with 0 on success, 3 on network errors, 4 when the media is unavailable, 5 when
+    /// it needs a login, 6 when the file can't be written, 7 without yt-dlp and 1 otherwise.
+    Download {
+        url: String,
+        /// Directory to download into, the downloads directory by default
+        #[arg(long)]
+        dest: Option<PathBuf>,
+        /// File name, asked from the backend by default
+        #[arg(long)]
+        name: Option<String>,
+        /// Preset from the config whose yt-dlp arguments are used
+        #[arg(long)]
+        preset: Option<String>,
+        /// Connections a direct file is downloaded over, `connections` from the config by default
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=32))]
+        connections: Option<u32>,
+        /// Backend to download with, `yt-dlp`, `http` or `aria2`, chosen from the URL by default
+        #[arg(long)]
+        backend: Option<BackendKind>,
+        /// Print the progress as JSON lines on stdout instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run the downloads in the background, the TUI and these commands attach to it
+    Daemon {
+        /// Stop the running daemon instead, its downloads resume when it starts again
+        #[arg(long)]
+        stop: bool,
+    },
+    /// Take downloads from a browser extension over native messaging, started by the browser
+    NativeHost {
+        /// What the browser passes, such as the extension's origin
+        #[arg(hide = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        browser_args: Vec<String>,
+    },
+}
+
+pub async fn run(command: CliCommand) -> Result<()> {
+    let (config, problem) = Config::load_or_default();
+    if let Some(problem) = problem {
+        eprintln!("{problem}");
+    }
+
+    match command {
+        CliCommand::Daemon { stop: true } => {
+            return client::send(&Request::Shutdown).map_err(|_| eyre!("no daemon is running"));
+        }
+        CliCommand::Daemon { stop: false } => return daemon::run().await,
+        CliCommand::NativeHost { .. } => return native_host::run(&config),
+        CliCommand::Download {
+            url,
+            dest,
+            name,
+            preset,
+            connections,
+            backend,
+            json,
+        } => {
+            let args = download::DownloadArgs {
+                url,
+                dest,
+                name,
+                preset,
+                connections,
+                backend,
+                json,
+            };
+            let code = download::run(&config, args).await?;
+            std::process::exit(code);
+        }
+        _ => {}
+    }
+
+    // a running daemon owns the tasks, changes are sent to it instead of stored
+    let attached = client::snapshot();
+    let remote = attached.is_some();
+    // listing and exporting only read tasks.json, its writes are atomic
+    let writes = !matches!(command, CliCommand::List { .. } | CliCommand::Export { .. });
+    let _store = if remote || !writes {
+        None
+    } else {
+        Some(Downloader::lock_store()?)
+    };
+    // unlike the TUI, don't start over with an empty store when it can't be read
+    let mut downloader = match attached {
+        Some(downloader) => downloader,
+        None if Downloader::dir().join("tasks.json").exists() => Downloader::new()
+            .load()
+            .map_err(|e| eyre!("failed to read the tasks. {e}"))?,
+        None => Downloader::new(),
+    };
+
+    match command {
+        CliCommand::Add {
+            url,
+            dest,
+            name,
+            preset,
+            connections,
+            backend,
+        } => {
+            let preset = preset.or(config.default_preset.clone());
+            if let Some(preset) = &preset
+                && !config.presets.contains_key(preset)
+            {
+                bail!("unknown preset `{preset}`");
+            }
+
+            let dest = match dest {
+                Some(dest) => dest,
+                None => match dirs::download_dir() {
+                    Some(dir) => dir,
+                    None => std::env::current_dir()?,
+                },
+            };
+            let (name, mime_type) = match name {
+                Some(name) => (name, None),
+                None => DownloadManager::probe(&url, backend)
+                    .ok()
+                    .filter(|metadata| !metadata.name.is_empty())
+                    .map(|metadata| (metadata.name, metadata.mime_type))
+                    .ok_or_else(|| {
+                        let backend = backend.unwrap_or_else(|| BackendKind::detect(&url));
+                        eyre!("failed to get the file name from {backend}, pass --name")
+                    })?,
+            };
+
+            let mut task = DownloadTask::new(&url, dest, name);
+            task.preset = preset;
+            task.connections = connections;
+            task.backend = backend;
+            task.mime_type = mime_type;
+            let id = task.id();
+            if remote {
+                client::send(&Request::Add {
+                    task: Box::new(task),
+                })?;
+            } else {
+                downloader.single.enqueue(task);
+                downloader.save()?;
+                drop(_store);
+                if let Some(task) = downloader.single.task(id) {
+                    Hooks::stored(&config, task).await;
+                }
+            }
+            println!("{id}");
+            return Ok(());
+        }
+        CliCommand::List { status, json } => {
+            let statuses = ["queued", "running", "paused", "completed", "failed"];
+            if let Some(status) = &status
+                && !statuses.contains(&status.as_str())
+                && !STATUS_GROUPS.contains(&status.as_str())
+            {
+                bail!(
+                    "unknown status `{status}`, use {} or {}",
+                    statuses.join(", "),
+                    STATUS_GROUPS.join(", ")
+                );
+            }
+
+            let tasks = downloader
+                .tasks()
+                .filter(|task| match status.as_deref() {
+                    Some(status) if statuses.contains(&status) => task.status.as_str() == status,
+                    Some(group) => task.status.in_group(group),
+                    None => true,
+                })
+                .collect::<Vec<_>>();
+
+            let mut out = std::io::stdout().lock();
+            let result = if json {
+                serde_json::to_writer_pretty(&mut out, &tasks)
+                    .map_err(io::Error::from)
+                    .and_then(|_| writeln!(out))
+            } else {
+                tasks.iter().try_for_each(|task| {
+                    writeln!(
+                        out,
+                        "{:<20}  {:<9}  {:>4.0}%  {}",
+                        task.id(),
+                        task.status.as_str(),
+                        task.progress_percent,
+                        task.title
+                    )
+                })
+            };
+            // piping into `head` closes the output early, that's not an error
+            return match result {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        CliCommand::Remove { ids } => {
+            let ids = resolve(&downloader, &ids)?;
+            if remote {
+                return client::send(&Request::Remove { ids });
+            }
+            for id in ids {
+                if let Some(manager) = downloader.manager_of_mut(id) {
+                    manager.remove(&[id]);
+                }
+            }
+        }
+        CliCommand::Retry { ids } => {
+            let ids = resolve(&downloader, &ids)?;
+            if remote {
+                return client::send(&Request::Retry { ids });
+            }
+            // to find the aria2 that has the downloads
+            downloader.set_aria2(config.aria2.clone());
+            for id in ids {
+                if let Some(manager) = downloader.manager_of_mut(id) {
+                    manager.set_stored_status(id, DownloadStatus::Queued, true);
+                }
+            }
+        }
+        CliCommand::Pause { ids } => {
+            let ids = accepted(&downloader, &ids, |status| {
+                !matches!(status, DownloadStatus::Completed | DownloadStatus::Failed)
+            })?;
+            if remote {
+                return client::send(&Request::Pause { ids });
+            }
+            set_status(&mut downloader, &ids, DownloadStatus::Paused);
+        }
+        CliCommand::Resume { ids } => {
+            let ids = accepted(&downloader, &ids, |status| {
+                matches!(status, DownloadStatus::Paused | DownloadStatus::Failed)
+            })?;
+            if remote {
+                return client::send(&Request::Resume { ids });
+            }
+            set_status(&mut downloader, &ids, DownloadStatus::Queued);
+        }
+        CliCommand::Export { ids, dir } => {
+            let ids = if ids.is_empty() {
+                downloader.tasks().map(|t| t.id()).collect()
+            } else {
+                resolve(&downloader, &ids)?
+            };
+            let tasks = downloader
+                .tasks()
+                .filter(|t| ids.contains(&t.id()))
+                .collect::<Vec<_>>();
+            let dir = match dir {
+                Some(dir) => dir,
+                None => std::env::current_dir()?,
+            };
+            let path = export_tasks(&tasks, dir)?;
+            println!("{}", path.display());
+            return Ok(());
+        }
+        CliCommand::Daemon { .. } | CliCommand::NativeHost { .. } | CliCommand::Download { .. } => {
+            unreachable!("handled above")
+        }
+    }
+
+    downloader.save()
+}
+
+/// Full ids of the tasks whose status `from` accepts, the others are reported and skipped
+fn accepted(
+    downloader: &Downloader,
+    ids: &[String],
+    from: fn(&DownloadStatus) -> bool,
+) -> Result<Vec<u64>> {
+    let ids = resolve(downloader, ids)?;
+    Ok(ids
+        .into_iter()
+        .filter(|&id| {
+            let current = downloader
+                .tasks()
+                .find(|t| t.id() == id)
+                .map(|t| t.status.clone())
+                .unwrap_or_default();
+            if !from(&current) {
+                eprintln!("{id} is {}, skipping it", current.as_str());
+            }
+            from(&current)
+        })
+        .collect())
+}
+
+fn set_status(downloader: &mut Downloader, ids: &[u64], status: DownloadStatus) {
+    for &id in ids {
+        if let Some(manager) = downloader.manager_of_mut(id) {
+            manager.set_stored_status(id, status.clone(), false);
+        }
+    }
+}
+
+/// How many of the tasks an ambiguous id prefix matches are listed
+const AMBIGUOUS_SHOWN: usize = 5;
+
+/// Full ids of the tasks matching the given ids or id prefixes, a prefix matching several
+/// tasks is an error rather than a guess
+fn resolve(downloader: &Downloader, ids: &[String]) -> Result<Vec<u64>> {
+    ids.iter()
+        .map(|prefix| {
+            let matches = downloader
+                .tasks()
+                .map(|t| t.id())
+                .filter(|id| id.to_string().starts_with(prefix.as_str()))
+                .collect::<Vec<_>>();
+            // a full id can be the prefix of a longer one
+            if let Some(&id) = matches.iter().find(|id| id.to_string() == *prefix) {
+                return Ok(id);
+            }
+            match matches[..] {
+                [id] => Ok(id),
+                [] => Err(eyre!("no task with id `{prefix}`")),
+                _ => {
+                    let mut shown = matches
+                        .iter()
+                        .take(AMBIGUOUS_SHOWN)
+                        .map(u64::to_string)
+                        .collect::<Vec<_>>();
+                    if matches.len() > AMBIGUOUS_SHOWN {
+                        shown.push("…".to_string());
+                    }
+                    Err(eyre!(
+                        "`{prefix}` matches {} tasks ({}), use more of the id",
+                        matches.len(),
+                        shown.join(", ")
+                    ))
+                }
+            }
+        })
+        .collect()
+}
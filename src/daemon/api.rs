@@ -0,0 +1,368 @@
+use std::{collections::BTreeMap, convert::Infallible, net::Ipv4Addr, path::PathBuf, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request as HttpRequest, State},
+    http::{HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, UnboundedSender},
+};
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::{
+    daemon::{
+        Command,
+        protocol::{Event, Request},
+    },
+    model::{
+        backend::BackendKind,
+        config::Config,
+        counts::STATUS_GROUPS,
+        downloader::{DownloadManager, DownloadTask},
+    },
+};
+
+/// Settings of the HTTP API, which is only served when a token is set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Port on 127.0.0.1 the API listens on
+    pub port: u16,
+    /// Secret clients send as `Authorization: Bearer <token>` or `?token=<token>`
+    pub token: String,
+    /// Origins of the pages and extensions that may call the API from a browser, such as
+    /// `https://example.com`. `moz-extension://*` allows every origin of a scheme, `*` any.
+    pub origins: Vec<String>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            port: 7386,
+            token: String::new(),
+            origins: vec![
+                "chrome-extension://*".to_string(),
+                "moz-extension://*".to_string(),
+            ],
+        }
+    }
+}
+
+impl ApiConfig {
+    fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| {
+            if allowed == "*" {
+                return true;
+            }
+            match allowed.strip_suffix("://*") {
+                Some(scheme) => origin
+                    .strip_prefix(scheme)
+                    .is_some_and(|rest| rest.starts_with("://")),
+                None => allowed.eq_ignore_ascii_case(origin),
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    commands: UnboundedSender<Command>,
+    events: broadcast::Sender<Event>,
+    token: Arc<str>,
+    presets: Arc<BTreeMap<String, Vec<String>>>,
+    default_preset: Option<String>,
+}
+
+type ApiResult<T> = Result<T, (StatusCode, Json<serde_json::Value>)>;
+
+fn error(
+    status: StatusCode,
+    message: impl std::fmt::Display,
+) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(json!({ "error": message.to_string() })))
+}
+
+/// Serve the HTTP API on loopback next to the socket, a problem is logged and leaves it off
+pub(super) fn spawn(
+    config: &Config,
+    commands: UnboundedSender<Command>,
+    events: broadcast::Sender<Event>,
+) {
+    let api = config.api.clone();
+    if api.token.is_empty() {
+        return;
+    }
+
+    let state = ApiState {
+        commands,
+        events,
+        token: api.token.as_str().into(),
+        presets: Arc::new(config.presets.clone()),
+        default_preset: config.default_preset.clone(),
+    };
+    // preflight requests carry no token, so this answers them before `authorize`
+    let origins = api.clone();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(
+            move |origin: &HeaderValue, _: &Parts| {
+                origin.to_str().is_ok_and(|origin| origins.allows(origin))
+            },
+        ))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+    let router = Router::new()
+        .route("/tasks", get(list).post(add))
+        .route("/tasks/{id}", get(show).delete(remove))
+        .route("/tasks/{id}/pause", post(pause))
+        .route("/tasks/{id}/resume", post(resume))
+        .route("/tasks/{id}/retry", post(retry))
+        .route("/events", get(stream))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .layer(cors)
+        .with_state(state);
+
+    tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, api.port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Failed to serve the HTTP API on port {}. {e}", api.port);
+                return;
+            }
+        };
+        log::info!("HTTP API listening on http://127.0.0.1:{}", api.port);
+        if let Err(e) = axum::serve(listener, router).await {
+            log::error!("The HTTP API stopped. {e}");
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Let through the requests carrying the token, in the header or for `EventSource` and
+/// bookmarklets in the query
+async fn authorize(
+    State(state): State<ApiState>,
+    Query(query): Query<TokenQuery>,
+    request: HttpRequest,
+    next: Next,
+) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match bearer.or(query.token.as_deref()) {
+        Some(token) if bool::from(token.as_bytes().ct_eq(state.token.as_bytes())) => {
+            next.run(request).await
+        }
+        _ => error(StatusCode::UNAUTHORIZED, "missing or wrong token").into_response(),
+    }
+}
+
+/// Carry out a request in the daemon loop, like a socket client does
+async fn send(state: &ApiState, request: Request) -> ApiResult<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _ = state.commands.send(Command::Request(request, tx));
+    while let Some(event) = rx.recv().await {
+        match event {
+            Event::Done => return Ok(()),
+            Event::Error { message } => return Err(error(StatusCode::BAD_REQUEST, message)),
+            _ => {}
+        }
+    }
+    Err(error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "the daemon is stopping",
+    ))
+}
+
+/// The given tasks as the daemon has them, or every task without ids
+async fn tasks(state: &ApiState, ids: Option<Vec<u64>>) -> ApiResult<Vec<DownloadTask>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _ = state
+        .commands
+        .send(Command::Request(Request::Tasks { ids }, tx));
+    match rx.recv().await {
+        Some(Event::Tasks { tasks }) => Ok(tasks),
+        _ => Err(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the daemon is stopping",
+        )),
+    }
+}
+
+async fn find(state: &ApiState, id: u64) -> ApiResult<DownloadTask> {
+    tasks(state, Some(vec![id]))
+        .await?
+        .pop()
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, format!("no task with id {id}")))
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    /// A status or a status group, as `cupcake list --status`
+    status: Option<String>,
+}
+
+async fn list(
+    State(state): State<ApiState>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Json<Vec<DownloadTask>>> {
+    let statuses = ["queued", "running", "paused", "completed", "failed"];
+    if let Some(status) = &query.status
+        && !statuses.contains(&status.as_str())
+        && !STATUS_GROUPS.contains(&status.as_str())
+    {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            format!("unknown status `{status}`"),
+        ));
+    }
+
+    let tasks = tasks(&state, None)
+        .await?
+        .into_iter()
+        .filter(|task| match query.status.as_deref() {
+            Some(status) if statuses.contains(&status) => task.status.as_str() == status,
+            Some(group) => task.status.in_group(group),
+            None => true,
+        })
+        .collect();
+    Ok(Json(tasks))
+}
+
+async fn show(State(state): State<ApiState>, Path(id): Path<u64>) -> ApiResult<Json<DownloadTask>> {
+    find(&state, id).await.map(Json)
+}
+
+#[derive(Deserialize)]
+struct AddTask {
+    url: String,
+    /// The downloads directory by default
+    destination: Option<PathBuf>,
+    /// Asked from the backend by default
+    name: Option<String>,
+    /// The default preset by default
+    preset: Option<String>,
+    /// Chosen from the URL by default
+    backend: Option<BackendKind>,
+}
+
+async fn add(
+    State(state): State<ApiState>,
+    Json(body): Json<AddTask>,
+) -> ApiResult<(StatusCode, Json<DownloadTask>)> {
+    let preset = body.preset.or(state.default_preset.clone());
+    if let Some(preset) = &preset
+        && !state.presets.contains_key(preset)
+    {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            format!("unknown preset `{preset}`"),
+        ));
+    }
+
+    let destination = match body.destination {
+        Some(destination) => destination,
+        None => dirs::download_dir()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default(),
+    };
+    let name = match body.name {
+        Some(name) => name,
+        None => {
+            let (url, backend) = (body.url.clone(), body.backend);
+            let backend_name = backend.unwrap_or_else(|| BackendKind::detect(&url));
+            tokio::task::spawn_blocking(move || DownloadManager::probe(&url, backend))
+                .await
+                .ok()
+                .and_then(|metadata| metadata.ok())
+                .map(|metadata| metadata.name)
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| {
+                    error(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("failed to get the file name from {backend_name}, pass a name"),
+                    )
+                })?
+        }
+    };
+
+    let mut task = DownloadTask::new(&body.url, destination, name);
+    task.preset = preset;
+    task.backend = body.backend;
+    let id = task.id();
+    send(
+        &state,
+        Request::Add {
+            task: Box::new(task),
+        },
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(find(&state, id).await?)))
+}
+
+/// Apply a request to an existing task and answer with its new state, a request for an
+/// unknown task changes nothing and ends up not finding it
+async fn control(state: ApiState, id: u64, request: Request) -> ApiResult<Json<DownloadTask>> {
+    send(&state, request).await?;
+    find(&state, id).await.map(Json)
+}
+
+async fn pause(
+    State(state): State<ApiState>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<DownloadTask>> {
+    control(state, id, Request::Pause { ids: vec![id] }).await
+}
+
+async fn resume(
+    State(state): State<ApiState>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<DownloadTask>> {
+    control(state, id, Request::Resume { ids: vec![id] }).await
+}
+
+async fn retry(
+    State(state): State<ApiState>,
+    Path(id): Path<u64>,
+) -> ApiResult<Json<DownloadTask>> {
+    control(state, id, Request::Retry { ids: vec![id] }).await
+}
+
+async fn remove(State(state): State<ApiState>, Path(id): Path<u64>) -> ApiResult<StatusCode> {
+    find(&state, id).await?;
+    send(&state, Request::Remove { ids: vec![id] }).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every change of a task as a `task` event, the same updates the TUI gets
+async fn stream(
+    State(state): State<ApiState>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let events = BroadcastStream::new(state.events.subscribe()).filter_map(|event| match event {
+        Ok(Event::Task { task }) => SseEvent::default()
+            .event("task")
+            .json_data(task)
+            .ok()
+            .map(Ok),
+        // a client that fell behind misses the updates in between
+        _ => None,
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,136 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::{net, process::CommandExt},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt},
+    net::UnixStream,
+    sync::mpsc::{self, UnboundedSender},
+};
+
+use crate::{
+    daemon::{
+        protocol::{Event, Request},
+        socket_path,
+    },
+    model::downloader::Downloader,
+    update::message::Message,
+};
+
+/// How long to wait for a daemon started by the TUI to listen
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Connection of the TUI to the daemon, which owns the tasks while attached
+#[derive(Clone, Debug)]
+pub struct Remote {
+    requests: UnboundedSender<Request>,
+}
+
+impl Remote {
+    /// Attach to the running daemon, its events arrive as `Message::Remote`.
+    /// Returns `None` when no daemon is running.
+    pub fn attach(messages: UnboundedSender<Message>) -> Option<Self> {
+        let stream = net::UnixStream::connect(socket_path()).ok()?;
+        stream.set_nonblocking(true).ok()?;
+        let (reader, mut writer) = UnixStream::from_std(stream).ok()?.into_split();
+
+        let (requests, mut outgoing) = mpsc::unbounded_channel::<Request>();
+        tokio::spawn(async move {
+            while let Some(request) = outgoing.recv().await {
+                let Ok(mut line) = serde_json::to_string(&request) else {
+                    continue;
+                };
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut lines = tokio::io::BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str::<Event>(&line) {
+                    Ok(event) => {
+                        let _ = messages.send(Message::Remote(Box::new(event)));
+                    }
+                    Err(e) => log::warn!("Invalid event from the daemon. {e}"),
+                }
+            }
+            let _ = messages.send(Message::Detached);
+        });
+
+        Some(Self { requests })
+    }
+
+    pub fn send(&self, request: Request) {
+        let _ = self.requests.send(request);
+    }
+}
+
+/// Start the daemon in the background and wait until it listens
+pub fn spawn_daemon() -> Result<()> {
+    Command::new(std::env::current_exe()?)
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // out of the terminal's process group, so it outlives the TUI
+        .process_group(0)
+        .spawn()?;
+
+    let deadline = Instant::now() + SPAWN_TIMEOUT;
+    while Instant::now() < deadline {
+        if net::UnixStream::connect(socket_path()).is_ok() {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    bail!(
+        "the daemon didn't start listening on {}",
+        socket_path().display()
+    )
+}
+
+/// The daemon's tasks, `None` when no daemon is running. For the command line.
+pub fn snapshot() -> Option<Downloader> {
+    let stream = net::UnixStream::connect(socket_path()).ok()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    match serde_json::from_str(&line).ok()? {
+        Event::Snapshot { downloader } => Some(*downloader),
+        _ => None,
+    }
+}
+
+/// Send a single request to the daemon and wait until it is carried out. For the command line.
+pub fn send(request: &Request) -> Result<()> {
+    let mut stream = net::UnixStream::connect(socket_path())?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            // it doesn't get to answer a shutdown
+            return match request {
+                Request::Shutdown => Ok(()),
+                _ => Err(eyre!("the daemon closed the connection")),
+            };
+        }
+        match serde_json::from_str(&line)? {
+            Event::Done => return Ok(()),
+            Event::Error { message } => return Err(eyre!(message)),
+            _ => {}
+        }
+    }
+}
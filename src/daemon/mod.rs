@@ -0,0 +1,284 @@
+pub mod api;
+pub mod client;
+pub mod protocol;
+
+use std::path::PathBuf;
+
+use color_eyre::{Result, eyre::bail};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    signal::unix::{SignalKind, signal},
+    sync::{
+        broadcast,
+        mpsc::{self, UnboundedSender},
+    },
+};
+
+use crate::{
+    daemon::protocol::{Event, Request},
+    model::{
+        app_log,
+        config::Config,
+        downloader::{DownloadStatus, Downloader},
+        plugins::Plugins,
+    },
+    update::message::Message,
+};
+
+/// Events a slow client may fall behind by before it misses some
+const EVENT_BACKLOG: usize = 1024;
+
+/// Socket the daemon listens on
+pub fn socket_path() -> PathBuf {
+    Downloader::dir().join("cupcake.sock")
+}
+
+/// What the connections hand to the daemon loop
+enum Command {
+    /// A client attached, the snapshot goes to its own channel
+    Attach(UnboundedSender<Event>),
+    Request(Request, UnboundedSender<Event>),
+}
+
+/// Run the daemon until it is stopped, owning the tasks and their yt-dlp jobs.
+///
+/// Clients attach over a Unix socket, see `protocol`. Jobs keep running while no client is
+/// attached, and every change is saved to `tasks.json` right away.
+pub async fn run() -> Result<()> {
+    let path = socket_path();
+    if UnixStream::connect(&path).await.is_ok() {
+        bail!("a daemon is already running on {}", path.display());
+    }
+    // left behind by a daemon that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    std::fs::create_dir_all(Downloader::dir())?;
+    // held until the daemon exits, a TUI without it would overwrite its changes
+    let _store = Downloader::lock_store()?;
+    let listener = UnixListener::bind(&path)?;
+
+    let (config, config_problem) = Config::load_or_default();
+    let (tx, mut job_rx) = mpsc::unbounded_channel::<Message>();
+    if let Some(problem) = app_log::init(config.log_level.as_deref(), tx.clone()) {
+        eprintln!("{problem}");
+    }
+    if let Some(problem) = config_problem {
+        log::warn!("{problem}");
+        eprintln!("{problem}");
+    }
+
+    let mut downloader = if Downloader::dir().join("tasks.json").exists() {
+        Downloader::new().load()?
+    } else {
+        Downloader::new()
+    };
+    downloader.set_rate_limit(config.rate_limit.clone());
+    downloader.set_log_retention(config.task_logs.clone());
+    downloader.set_presets(config.presets.clone());
+    downloader.set_connections(config.connections);
+    downloader.set_aria2(config.aria2.clone());
+    downloader.set_hooks(config.hooks.clone(), config.webhooks.clone());
+    let (plugins, problems) = Plugins::load(&config.plugins);
+    for problem in problems {
+        log::warn!("{problem}");
+    }
+    downloader.set_plugins(plugins);
+    downloader.start_pending(tx.clone());
+    log::info!("Daemon listening on {}", path.display());
+
+    let (events, _) = broadcast::channel::<Event>(EVENT_BACKLOG);
+    let (command_tx, mut commands) = mpsc::unbounded_channel::<Command>();
+
+    api::spawn(&config, command_tx.clone(), events.clone());
+
+    let accept_events = events.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, command_tx.clone(), accept_events.subscribe()));
+        }
+    });
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    loop {
+        tokio::select! {
+            Some(message) = job_rx.recv() => match message {
+                Message::UpdateDownloadStatus(task, run) => {
+                    let id = task.id();
+                    let finished = matches!(task.status, DownloadStatus::Completed | DownloadStatus::Failed);
+                    downloader.update_download(*task, run);
+                    if let Some(task) = downloader.manager_of(id).and_then(|m| m.task(id)) {
+                        let _ = events.send(Event::Task { task: Box::new(task.clone()) });
+                    }
+                    if finished {
+                        save(&downloader);
+                    }
+                }
+                Message::TaskOutput(id, line) => {
+                    if let Some(manager) = downloader.manager_of_mut(id) {
+                        manager.push_output(id, line.clone());
+                    }
+                    let _ = events.send(Event::Output { id, line });
+                }
+                _ => {}
+            },
+            Some(command) = commands.recv() => match command {
+                Command::Attach(client) => {
+                    let _ = client.send(Event::Snapshot { downloader: Box::new(downloader.clone()) });
+                }
+                Command::Request(Request::Shutdown, _) => break,
+                Command::Request(Request::Tasks { ids }, client) => {
+                    let tasks = downloader
+                        .tasks()
+                        .filter(|t| ids.as_ref().is_none_or(|ids| ids.contains(&t.id())))
+                        .cloned()
+                        .collect();
+                    let _ = client.send(Event::Tasks { tasks });
+                }
+                Command::Request(request, client) => {
+                    let changes_tasks = request.changes_tasks();
+                    match handle(&mut downloader, request, tx.clone()) {
+                        Ok(_) => {
+                            let _ = client.send(Event::Done);
+                        }
+                        Err(e) => {
+                            log::warn!("{e}");
+                            let _ = client.send(Event::Error { message: e.to_string() });
+                        }
+                    }
+                    // a failed request may still have changed some of its tasks
+                    if changes_tasks {
+                        save(&downloader);
+                        let _ = events.send(Event::Snapshot { downloader: Box::new(downloader.clone()) });
+                    }
+                }
+            },
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+        }
+    }
+
+    // the jobs are killed with the runtime, their tasks stay running and resume on the next start
+    save(&downloader);
+    let _ = std::fs::remove_file(&path);
+    log::info!("Daemon stopped");
+    Ok(())
+}
+
+fn handle(
+    downloader: &mut Downloader,
+    request: Request,
+    tx: UnboundedSender<Message>,
+) -> Result<()> {
+    match request {
+        Request::Add { task } => downloader.single.start_download(*task, tx),
+        Request::Pause { ids } => {
+            for manager in downloader.managers_mut() {
+                manager.pause(&ids);
+            }
+        }
+        Request::Resume { ids } => {
+            for manager in downloader.managers_mut() {
+                manager.resume(&ids, tx.clone());
+            }
+        }
+        Request::Retry { ids } => {
+            for manager in downloader.managers_mut() {
+                manager.retry(&ids, tx.clone());
+            }
+        }
+        Request::Remove { ids } => {
+            for manager in downloader.managers_mut() {
+                manager.remove(&ids);
+            }
+        }
+        Request::ChangeDestination { ids, destination } => {
+            // a manager failing to move its tasks doesn't keep the others from moving theirs
+            let errors = downloader
+                .managers_mut()
+                .into_iter()
+                .filter_map(|manager| manager.change_destination(&ids, destination.clone()).err())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>();
+            if !errors.is_empty() {
+                bail!("{}", errors.join("\n"));
+            }
+        }
+        Request::Tag { ids, tags } => {
+            for manager in downloader.managers_mut() {
+                manager.tag(&ids, &tags);
+            }
+        }
+        Request::SetConnections { ids, connections } => {
+            for manager in downloader.managers_mut() {
+                manager.set_task_connections(&ids, connections);
+            }
+        }
+        Request::SetBackend { ids, backend } => {
+            for manager in downloader.managers_mut() {
+                manager.set_task_backend(&ids, backend);
+            }
+        }
+        Request::PauseAll => downloader.pause_all(),
+        Request::ResumeAll => downloader.resume_all(tx),
+        Request::SetRateLimit { rate } => downloader.set_rate_limit(rate),
+        Request::Tasks { .. } | Request::Shutdown => {}
+    }
+    Ok(())
+}
+
+fn save(downloader: &Downloader) {
+    if let Err(e) = downloader.save() {
+        log::error!("Failed to save the tasks. {e}");
+    }
+}
+
+/// Relay the requests of one client to the daemon loop and the events back to it
+async fn serve(
+    stream: UnixStream,
+    commands: UnboundedSender<Command>,
+    mut events: broadcast::Receiver<Event>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let (own_tx, mut own_events) = mpsc::unbounded_channel::<Event>();
+    if commands.send(Command::Attach(own_tx.clone())).is_err() {
+        return;
+    }
+
+    loop {
+        let event = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    match serde_json::from_str::<Request>(&line) {
+                        Ok(request) => {
+                            let _ = commands.send(Command::Request(request, own_tx.clone()));
+                        }
+                        Err(e) => {
+                            let _ = own_tx.send(Event::Error { message: format!("invalid request. {e}") });
+                        }
+                    }
+                    continue;
+                }
+                _ => return,
+            },
+            Some(event) = own_events.recv() => event,
+            event = events.recv() => match event {
+                Ok(event) => event,
+                // fell behind, a snapshot brings the client up to date again
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    let _ = commands.send(Command::Attach(own_tx.clone()));
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+
+        let Ok(mut line) = serde_json::to_string(&event) else {
+            continue;
+        };
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    backend::BackendKind,
+    downloader::{DownloadTask, Downloader, OutputLine},
+};
+
+/// What a client asks the daemon to do, one JSON object per line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Store the task and start downloading it
+    Add {
+        task: Box<DownloadTask>,
+    },
+    Pause {
+        ids: Vec<u64>,
+    },
+    Resume {
+        ids: Vec<u64>,
+    },
+    Retry {
+        ids: Vec<u64>,
+    },
+    Remove {
+        ids: Vec<u64>,
+    },
+    ChangeDestination {
+        ids: Vec<u64>,
+        destination: PathBuf,
+    },
+    Tag {
+        ids: Vec<u64>,
+        tags: Vec<String>,
+    },
+    /// Connections of the tasks' next native downloads, `None` for the config's
+    SetConnections {
+        ids: Vec<u64>,
+        connections: Option<u32>,
+    },
+    /// Backend of the tasks' next runs, `None` chooses it from the URL
+    SetBackend {
+        ids: Vec<u64>,
+        backend: Option<BackendKind>,
+    },
+    PauseAll,
+    ResumeAll,
+    SetRateLimit {
+        rate: Option<String>,
+    },
+    /// The given tasks, or every task without ids, answered with `Event::Tasks`
+    Tasks {
+        ids: Option<Vec<u64>>,
+    },
+    /// Save and exit, the running tasks resume when the daemon starts again
+    Shutdown,
+}
+
+impl Request {
+    /// Whether the request can change the tasks, so they are saved and sent to the clients
+    /// again after it
+    pub fn changes_tasks(&self) -> bool {
+        !matches!(
+            self,
+            Request::SetRateLimit { .. } | Request::Tasks { .. } | Request::Shutdown
+        )
+    }
+}
+
+/// What the daemon tells its clients, one JSON object per line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Every task, sent when a client attaches and after every request that changes them
+    Snapshot {
+        downloader: Box<Downloader>,
+    },
+    /// Progress or status of a single task changed
+    Task {
+        task: Box<DownloadTask>,
+    },
+    Output {
+        id: u64,
+        line: OutputLine,
+    },
+    /// The tasks this client asked for with `Request::Tasks`
+    Tasks {
+        tasks: Vec<DownloadTask>,
+    },
+    /// A request of this client was carried out
+    Done,
+    /// A request of this client failed
+    Error {
+        message: String,
+    },
+}
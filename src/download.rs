@@ -0,0 +1,416 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use color_eyre::{Result, eyre::bail};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    model::{
+        backend::BackendKind,
+        config::Config,
+        downloader::{DownloadManager, DownloadStatus, DownloadTask, Downloader, written_file},
+        filter::parse_size,
+    },
+    update::message::Message,
+};
+
+/// Exit code when the download was interrupted with Ctrl+C
+const INTERRUPTED: i32 = 130;
+
+/// How long an interrupted download gets to stop its job before cupcake exits anyway
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `cupcake download --json` prints, one JSON object per line
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum DownloadEvent<'a> {
+    Metadata {
+        id: String,
+        url: &'a str,
+        title: &'a str,
+        path: &'a PathBuf,
+        command: &'a [String],
+        backend: Option<BackendKind>,
+    },
+    Phase {
+        phase: Phase,
+    },
+    Progress {
+        percent: f32,
+        downloaded_bytes: Option<u64>,
+        total_bytes: Option<u64>,
+        /// Bytes per second
+        speed: Option<u64>,
+        eta_seconds: Option<u64>,
+    },
+    Completed {
+        path: PathBuf,
+    },
+    Failed {
+        kind: FailureKind,
+        message: String,
+        exit_code: i32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Phase {
+    Extracting,
+    Downloading,
+    PostProcessing,
+}
+
+/// Why a download failed, told apart by yt-dlp's error message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// yt-dlp isn't installed or can't be started
+    MissingYtDlp,
+    Network,
+    /// The URL isn't supported, or the media doesn't exist or was taken down
+    Unavailable,
+    /// The media needs a login, cookies or is geo-blocked
+    Forbidden,
+    /// The file couldn't be written
+    Filesystem,
+    Interrupted,
+    Unknown,
+}
+
+impl FailureKind {
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let any = |needles: &[&str]| needles.iter().any(|n| message.contains(n));
+        // any other missing file is a destination that went away
+        if any(&["failed to start yt-dlp", "yt-dlp: not found"]) {
+            FailureKind::MissingYtDlp
+        } else if any(&[
+            "no such file or directory",
+            "no space left",
+            "permission denied",
+            "read-only file system",
+        ]) {
+            FailureKind::Filesystem
+        } else if any(&[
+            "http error 401",
+            "http error 403",
+            "sign in",
+            "login",
+            "private video",
+            "members-only",
+            "cookies",
+            "geo",
+        ]) {
+            FailureKind::Forbidden
+        } else if any(&[
+            "unsupported url",
+            "http error 404",
+            "http error 410",
+            "not available",
+            "unavailable",
+            "has been removed",
+            "does not exist",
+        ]) {
+            FailureKind::Unavailable
+        } else if any(&[
+            "unable to download",
+            "connection",
+            "timed out",
+            "name resolution",
+            "getaddrinfo",
+            "network is unreachable",
+            "http error 5",
+        ]) {
+            FailureKind::Network
+        } else {
+            FailureKind::Unknown
+        }
+    }
+
+    /// Exit code of `cupcake download`, 1 when it isn't any more specific
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Unknown => 1,
+            FailureKind::Network => 3,
+            FailureKind::Unavailable => 4,
+            FailureKind::Forbidden => 5,
+            FailureKind::Filesystem => 6,
+            FailureKind::MissingYtDlp => 7,
+            FailureKind::Interrupted => INTERRUPTED,
+        }
+    }
+}
+
+/// Prints the events as JSON lines, or progress for people on stderr
+struct Reporter {
+    json: bool,
+    phase: Option<Phase>,
+}
+
+impl Reporter {
+    fn emit(&mut self, event: DownloadEvent) {
+        if let DownloadEvent::Phase { phase } = &event {
+            if self.phase == Some(*phase) {
+                return;
+            }
+            self.phase = Some(*phase);
+        }
+
+        if self.json {
+            let mut out = io::stdout().lock();
+            // nobody is reading anymore when the pipe is closed, the download goes on
+            let _ = serde_json::to_writer(&mut out, &event)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(out))
+                .and_then(|_| out.flush());
+            return;
+        }
+
+        match event {
+            DownloadEvent::Progress { percent, .. } => {
+                eprint!("\r{percent:>5.1}%");
+            }
+            DownloadEvent::Completed { path } => {
+                eprintln!();
+                println!("{}", path.display());
+            }
+            DownloadEvent::Failed { message, .. } => {
+                eprintln!();
+                eprintln!("cupcake: {message}");
+            }
+            _ => {}
+        }
+    }
+
+    fn fail(&mut self, kind: FailureKind, message: String) -> i32 {
+        let exit_code = kind.exit_code();
+        self.emit(DownloadEvent::Failed {
+            kind,
+            message,
+            exit_code,
+        });
+        exit_code
+    }
+}
+
+/// What `cupcake download` was asked to do, see `CliCommand::Download`
+pub struct DownloadArgs {
+    pub url: String,
+    pub dest: Option<PathBuf>,
+    pub name: Option<String>,
+    pub preset: Option<String>,
+    pub connections: Option<u32>,
+    pub backend: Option<BackendKind>,
+    pub json: bool,
+}
+
+/// Download the URL without storing a task, returns the exit code.
+/// The job's output goes to its task log as usual.
+pub async fn run(config: &Config, args: DownloadArgs) -> Result<i32> {
+    let DownloadArgs {
+        url,
+        dest,
+        name,
+        preset,
+        connections,
+        backend,
+        json,
+    } = args;
+    let preset = preset.or(config.default_preset.clone());
+    if let Some(preset) = &preset
+        && !config.presets.contains_key(preset)
+    {
+        bail!("unknown preset `{preset}`");
+    }
+
+    let mut reporter = Reporter { json, phase: None };
+    reporter.emit(DownloadEvent::Phase {
+        phase: Phase::Extracting,
+    });
+    let dest = match dest {
+        Some(dest) => dest,
+        None => match dirs::download_dir() {
+            Some(dir) => dir,
+            None => std::env::current_dir()?,
+        },
+    };
+    let (name, mime_type) = match name {
+        Some(name) => (name, None),
+        None => match DownloadManager::probe(&url, backend) {
+            Ok(metadata) if !metadata.name.is_empty() => (metadata.name, metadata.mime_type),
+            Ok(_) => {
+                return Ok(reporter.fail(
+                    FailureKind::Unknown,
+                    format!(
+                        "{} gave no file name, pass --name",
+                        backend.unwrap_or_else(|| BackendKind::detect(&url))
+                    ),
+                ));
+            }
+            Err(e) => {
+                let message = e.to_string();
+                return Ok(reporter.fail(FailureKind::classify(&message), message));
+            }
+        },
+    };
+
+    let mut downloader = Downloader::new();
+    downloader.set_rate_limit(config.rate_limit.clone());
+    downloader.set_log_retention(config.task_logs.clone());
+    downloader.set_presets(config.presets.clone());
+    downloader.set_connections(config.connections);
+    downloader.set_aria2(config.aria2.clone());
+    let manager = &mut downloader.single;
+
+    let mut task = DownloadTask::new(&url, dest, name);
+    task.preset = preset;
+    task.connections = connections;
+    task.backend = backend;
+    task.mime_type = mime_type;
+    let id = task.id();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    manager.start_download(task, tx);
+    if let Some(task) = manager.task(id) {
+        reporter.emit(DownloadEvent::Metadata {
+            id: id.to_string(),
+            url: &task.source_url,
+            title: &task.title,
+            path: &task.destination_path,
+            command: &task.command,
+            backend: task.backend,
+        });
+    }
+
+    // the file yt-dlp said it writes, post-processing may end up with another one
+    let mut path = None;
+    // yt-dlp's `ERROR:` line, or else the last line on stderr
+    let mut error = None;
+    let mut last_stderr = None;
+    loop {
+        let message = tokio::select! {
+            message = rx.recv() => message,
+            _ = tokio::signal::ctrl_c() => {
+                manager.pause(&[id]);
+                // the job owns the only sender, it is done once the channel closes
+                let drained = async { while rx.recv().await.is_some() {} };
+                let _ = tokio::time::timeout(STOP_TIMEOUT, drained).await;
+                return Ok(reporter.fail(FailureKind::Interrupted, "interrupted".to_string()));
+            }
+        };
+
+        match message {
+            Some(Message::TaskOutput(_, line)) => {
+                let text = line.text.trim();
+                if line.stderr {
+                    match text.strip_prefix("ERROR:") {
+                        Some(message) => error = Some(message.trim().to_string()),
+                        None => last_stderr = Some(text.to_string()),
+                    }
+                    continue;
+                }
+                if let Some(file) = written_file(text) {
+                    path = Some(PathBuf::from(file));
+                }
+                if text.starts_with("[download]") {
+                    reporter.emit(DownloadEvent::Phase {
+                        phase: Phase::Downloading,
+                    });
+                } else if POST_PROCESSORS.iter().any(|p| text.starts_with(p)) {
+                    reporter.emit(DownloadEvent::Phase {
+                        phase: Phase::PostProcessing,
+                    });
+                }
+            }
+            Some(Message::UpdateDownloadStatus(task, _)) => match task.status {
+                DownloadStatus::Completed => {
+                    reporter.emit(DownloadEvent::Completed {
+                        path: path.unwrap_or(task.destination_path),
+                    });
+                    return Ok(0);
+                }
+                DownloadStatus::Failed => {
+                    let message = error.or(last_stderr).unwrap_or_else(|| {
+                        let backend = task
+                            .backend
+                            .unwrap_or_else(|| BackendKind::detect(&task.source_url));
+                        format!("{backend} failed without an error")
+                    });
+                    return Ok(reporter.fail(FailureKind::classify(&message), message));
+                }
+                _ => {
+                    reporter.emit(DownloadEvent::Phase {
+                        phase: Phase::Downloading,
+                    });
+                    reporter.emit(progress(&task));
+                }
+            },
+            Some(_) => {}
+            None => bail!("the download stopped without a result"),
+        }
+    }
+}
+
+/// Prefixes of the lines yt-dlp's post-processors print
+const POST_PROCESSORS: [&str; 8] = [
+    "[Merger]",
+    "[ExtractAudio]",
+    "[VideoConvertor]",
+    "[VideoRemuxer]",
+    "[EmbedThumbnail]",
+    "[EmbedSubtitle]",
+    "[Metadata]",
+    "[Fixup",
+];
+
+fn progress(task: &DownloadTask) -> DownloadEvent<'static> {
+    let total_bytes = parse_size(&task.file_size);
+    DownloadEvent::Progress {
+        percent: task.progress_percent,
+        downloaded_bytes: total_bytes
+            .map(|total| (total as f64 * task.progress_percent as f64 / 100.0) as u64),
+        total_bytes,
+        speed: parse_size(task.download_speed.trim_end_matches("/s")),
+        eta_seconds: parse_eta(&task.estimated_time),
+    }
+}
+
+/// Seconds of an ETA such as `05:12` or `1:02:03`
+fn parse_eta(eta: &str) -> Option<u64> {
+    eta.split(':').try_fold(0, |seconds, part| {
+        Some(seconds * 60 + part.parse::<u64>().ok()?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_failures() {
+        let kind = FailureKind::classify;
+        assert_eq!(
+            kind("failed to start yt-dlp. No such file or directory (os error 2)"),
+            FailureKind::MissingYtDlp
+        );
+        assert_eq!(
+            kind("unable to open for writing: [Errno 2] No such file or directory: '/gone/a.mp4'"),
+            FailureKind::Filesystem
+        );
+        assert_eq!(
+            kind("No such file or directory (os error 2)"),
+            FailureKind::Filesystem
+        );
+        assert_eq!(kind("HTTP Error 403: Forbidden"), FailureKind::Forbidden);
+        assert_eq!(
+            kind("Unsupported URL: https://a.b"),
+            FailureKind::Unavailable
+        );
+        assert_eq!(kind("Connection refused"), FailureKind::Network);
+        assert_eq!(kind("something else"), FailureKind::Unknown);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{io::Write, os::unix::net, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixListener,
+    sync::mpsc::UnboundedSender,
+};
+
+use crate::{
+    model::downloader::{DownloadTask, Downloader},
+    update::message::Message,
+};
+
+/// Socket the open TUI takes URLs on
+pub fn socket_path() -> PathBuf {
+    Downloader::dir().join("ui.sock")
+}
+
+/// A URL passed to `cupcake <url>` for the open TUI, one JSON object per line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Handoff {
+    pub url: String,
+    /// Add the task right away instead of opening the add task flow
+    #[serde(default)]
+    pub enqueue: bool,
+    /// A task the sender already made, e.g. with the browser's cookies, added as it is
+    #[serde(default)]
+    pub task: Option<Box<DownloadTask>>,
+}
+
+impl Handoff {
+    pub fn message(self) -> Message {
+        match (self.task, self.enqueue) {
+            (Some(task), _) => Message::AddTask(task),
+            (None, true) => Message::EnqueueUrl(self.url),
+            (None, false) => Message::PrefillAddTask(self.url),
+        }
+    }
+}
+
+/// Hand the URL to the open TUI, `false` when none is open
+pub fn send(handoff: &Handoff) -> bool {
+    let Ok(mut stream) = net::UnixStream::connect(socket_path()) else {
+        return false;
+    };
+    let Ok(mut line) = serde_json::to_string(handoff) else {
+        return false;
+    };
+    line.push('\n');
+    stream.write_all(line.as_bytes()).is_ok()
+}
+
+/// Takes the URLs of `cupcake <url>` while the TUI is open, the socket is removed on drop
+#[derive(Debug)]
+pub struct Listener {
+    path: PathBuf,
+}
+
+impl Listener {
+    /// Listen for URLs, `None` when another open TUI already takes them
+    pub fn bind(tx: UnboundedSender<Message>) -> Option<Self> {
+        let path = socket_path();
+        if net::UnixStream::connect(&path).is_ok() {
+            return None;
+        }
+        // left behind by a TUI that didn't exit cleanly
+        let _ = std::fs::remove_file(&path);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!("Failed to listen on {}. {e}", path.display());
+                return None;
+            }
+        };
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut lines = BufReader::new(stream).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        match serde_json::from_str::<Handoff>(&line) {
+                            Ok(handoff) => {
+                                log::info!("Received {}", handoff.url);
+                                let _ = tx.send(handoff.message());
+                            }
+                            Err(e) => log::warn!("Invalid URL handoff. {e}"),
+                        }
+                    }
+                });
+            }
+        });
+
+        Some(Self { path })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,23 +1,63 @@
+mod cli;
+mod daemon;
+mod download;
+mod handoff;
 mod model;
+mod native_host;
 mod update;
 mod view;
 
 use std::sync::{Arc, atomic::Ordering};
 
+use clap::Parser;
 use color_eyre::Result;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event, MouseEventKind};
 use ratatui::DefaultTerminal;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-use crate::{model::state::Model, update::message::Message, view::draw};
+use crate::{
+    cli::Cli,
+    handoff::Handoff,
+    model::state::Model,
+    update::{message::Message, sequencer::KeySequencer},
+    view::draw,
+};
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
     color_eyre::install()?;
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        // plain messages instead of reports, these are meant for scripts and shells
+        if let Err(e) = cli::run(command).await {
+            eprintln!("cupcake: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // a URL handler started us, the open TUI takes the URL instead of a second one
+    let handoff = cli.url.map(|url| Handoff {
+        url,
+        enqueue: cli.enqueue,
+        task: None,
+    });
+    if let Some(handoff) = &handoff
+        && handoff::send(handoff)
+    {
+        return Ok(());
+    }
+
     let terminal = ratatui::init();
+    crossterm::execute!(std::io::stdout(), EnableMouseCapture)?;
 
     let (tx, rx) = mpsc::unbounded_channel::<Message>();
+    if let Some(handoff) = handoff {
+        tx.send(handoff.message())?;
+    }
 
     let app_result = App::new(tx).run(terminal, rx).await;
+    crossterm::execute!(std::io::stdout(), DisableMouseCapture)?;
     ratatui::restore();
     app_result
 }
@@ -40,7 +80,7 @@ impl App {
     ) -> Result<()> {
         self.handle_events();
 
-        self.state.menu_state.open(vec!["all"]);
+        self.state.menu_state.open(vec!["all".to_string()]);
 
         let panel = Arc::clone(&self.state.active_panel);
         let tab = Arc::clone(&self.state.active_tab);
@@ -69,30 +109,56 @@ impl App {
     }
 
     /// Handles terminal events in a separate thread.
+    ///
+    /// Reading terminal events blocks, so this runs on the blocking thread pool instead of
+    /// occupying one of the runtime workers the download jobs run on. Keys go through the
+    /// sequencer, which only needs to wake up on its own while a key sequence is pending.
     fn handle_events(&self) {
         let event_tx = self.state.message_tx.clone().unwrap();
         let panel = Arc::clone(&self.state.active_panel);
         let tab = Arc::clone(&self.state.active_tab);
         let running = Arc::clone(&self.state.running);
+        let mut sequencer = KeySequencer::new(self.state.keymap.clone());
 
-        tokio::spawn(async move {
+        tokio::task::spawn_blocking(move || {
             while running.load(Ordering::Relaxed) {
-                let event = crossterm::event::read().unwrap();
-
-                let active_panel = {
-                    let lock = panel.read().await;
-                    *lock
+                let event = match sequencer.remaining() {
+                    Some(timeout) if !crossterm::event::poll(timeout).unwrap() => None,
+                    _ => Some(crossterm::event::read().unwrap()),
                 };
 
-                let active_tab = {
-                    let lock = tab.read().await;
-                    *lock
+                let active_panel = *panel.blocking_read();
+                let active_tab = *tab.blocking_read();
+
+                // only clicks and the wheel, moves and drags would flood the queue
+                if let Some(Event::Mouse(mouse)) = event {
+                    if matches!(
+                        mouse.kind,
+                        MouseEventKind::Down(_)
+                            | MouseEventKind::ScrollDown
+                            | MouseEventKind::ScrollUp
+                    ) {
+                        event_tx.send(Message::Mouse(mouse)).unwrap();
+                    }
+                    continue;
+                }
+
+                let pending = sequencer.pending();
+                let messages = match event {
+                    Some(event) => sequencer.handle(event, &active_panel, &active_tab),
+                    None => sequencer.expire(&active_panel, &active_tab),
                 };
+                if sequencer.pending() != pending {
+                    event_tx
+                        .send(Message::UpdatePendingKeys(sequencer.pending()))
+                        .unwrap();
+                }
 
-                if let Some(message) = Message::from_event(event, &active_panel, &active_tab) {
-                    event_tx.send(message.clone()).unwrap();
-                    if let Message::Quit = message {
-                        break;
+                for message in messages {
+                    let quit = matches!(message, Message::Quit);
+                    event_tx.send(message).unwrap();
+                    if quit {
+                        return;
                     }
                 }
             }
@@ -0,0 +1,158 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    sync::{Mutex, OnceLock},
+};
+
+use color_eyre::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    model::downloader::{Downloader, format_timestamp, unix_now},
+    update::message::Message,
+};
+
+/// Environment variable overriding the configured log level, e.g. `CUPCAKE_LOG=debug`
+pub const LEVEL_ENV: &str = "CUPCAKE_LOG";
+
+/// Number of warnings and errors kept for the log panel
+const RECENT_ENTRIES: usize = 200;
+
+/// Size the log file grows to before it is moved to `cupcake.log.1` on startup
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// A warning or error as shown in the log panel
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub time: u64,
+    pub level: Level,
+    pub message: String,
+}
+
+/// Writes every record at or above the level to `cupcake.log` in the data directory and
+/// keeps the recent warnings and errors for the log panel.
+struct AppLogger {
+    level: LevelFilter,
+    file: Mutex<Option<File>>,
+    recent: Mutex<VecDeque<Entry>>,
+    /// Tells the UI to redraw the log panel
+    tx: UnboundedSender<Message>,
+}
+
+static LOGGER: OnceLock<AppLogger> = OnceLock::new();
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = unix_now();
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = writeln!(
+                file,
+                "{} {:<5} {} {}",
+                format_timestamp(time),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+
+        if record.level() <= Level::Warn {
+            if let Ok(mut recent) = self.recent.lock() {
+                if recent.len() == RECENT_ENTRIES {
+                    recent.pop_front();
+                }
+                recent.push_back(Entry {
+                    time,
+                    level: record.level(),
+                    message: record.args().to_string(),
+                });
+            }
+            let _ = self.tx.send(Message::Logged);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Install the app log, the level comes from `CUPCAKE_LOG`, then the config, then `info`.
+/// Returns a problem with the level or the log file, logging keeps working without the file.
+pub fn init(config_level: Option<&str>, tx: UnboundedSender<Message>) -> Option<String> {
+    let mut problems = Vec::new();
+
+    let env_level = std::env::var(LEVEL_ENV).ok();
+    let level = match env_level.as_deref().or(config_level) {
+        Some(name) => name.parse::<LevelFilter>().unwrap_or_else(|_| {
+            problems.push(format!(
+                "Unknown log level `{name}`, use off, error, warn, info, debug or trace."
+            ));
+            LevelFilter::Info
+        }),
+        None => LevelFilter::Info,
+    };
+
+    let file = match open_file() {
+        Ok(file) => Some(file),
+        Err(e) => {
+            problems.push(format!("Failed to open the log file. {e}"));
+            None
+        }
+    };
+
+    let logger = LOGGER.get_or_init(|| AppLogger {
+        level,
+        file: Mutex::new(file),
+        recent: Mutex::new(VecDeque::new()),
+        tx,
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+
+    (!problems.is_empty()).then(|| problems.join("\n"))
+}
+
+fn open_file() -> Result<File> {
+    let dir = Downloader::dir();
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("cupcake.log");
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_FILE_SIZE) {
+        fs::rename(&path, dir.join("cupcake.log.1"))?;
+    }
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// The recent warnings and errors, oldest first
+pub fn recent() -> Vec<Entry> {
+    LOGGER
+        .get()
+        .and_then(|logger| {
+            logger
+                .recent
+                .lock()
+                .ok()
+                .map(|r| r.iter().cloned().collect())
+        })
+        .unwrap_or_default()
+}
+
+/// The level records are logged at, `off` before the log is installed
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
@@ -0,0 +1,497 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    net::{Ipv4Addr, TcpListener},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::{sync::OnceCell, task};
+
+use crate::model::{
+    backend::{Backend, Job, JobOptions, Metadata, Stop},
+    downloader::{DownloadStatus, DownloadTask, OutputLine},
+    http::{self, format_bytes, format_eta},
+};
+
+/// aria2, for magnet links, torrents, metalinks, FTP and the tasks set to `aria2`.
+///
+/// Downloads go to the aria2c at `endpoint` when it's set, otherwise cupcake starts its own
+/// aria2c that lives as long as cupcake does.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Aria2Config {
+    /// JSON-RPC endpoint of a running aria2c, e.g. `http://localhost:6800/jsonrpc`
+    pub endpoint: Option<String>,
+    /// Its `--rpc-secret`
+    pub secret: Option<String>,
+    /// The aria2c started when there's no endpoint
+    pub path: String,
+}
+
+impl Default for Aria2Config {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            secret: None,
+            path: "aria2c".to_string(),
+        }
+    }
+}
+
+/// Extensions of the files aria2 reads downloads from instead of saving them
+const DESCRIPTOR_EXTENSIONS: &[&str] = &["torrent", "metalink", "meta4"];
+
+/// How often a job asks aria2 about its download
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a started aria2c may take to answer
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a removed download may take to stop
+const REMOVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// aria2 takes at most this many connections to a server
+const MAX_CONNECTIONS_PER_SERVER: u32 = 16;
+
+/// The aria2c cupcake started, shared by all jobs
+static LOCAL: OnceCell<Rpc> = OnceCell::const_new();
+
+/// Downloads through aria2's JSON-RPC interface
+pub(crate) struct Aria2;
+
+impl Backend for Aria2 {
+    fn handles(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+        matches!(url.scheme(), "magnet" | "ftp" | "sftp") || is_descriptor(&url)
+    }
+
+    /// The name of a magnet link's `dn`, or the last segment of the URL's path. What a
+    /// torrent or metalink holds is only known once aria2 reads it.
+    fn probe(&self, url: &str) -> Result<Metadata> {
+        let parsed = Url::parse(url)?;
+        // the name becomes the destination, a `dn` of `../x` would leave the directory
+        let name = match parsed.scheme() {
+            "magnet" => parsed
+                .query_pairs()
+                .find(|(key, _)| key == "dn")
+                .and_then(|(_, name)| http::sanitize(&name))
+                .or_else(|| {
+                    parsed
+                        .query_pairs()
+                        .find(|(key, _)| key == "xt")
+                        .and_then(|(_, xt)| http::sanitize(xt.rsplit(':').next()?))
+                }),
+            _ => http::url_name(&parsed),
+        };
+        Ok(Metadata {
+            name: name.ok_or_else(|| eyre!("no file name in {url}, pass one"))?,
+            mime_type: None,
+        })
+    }
+
+    fn command(&self, task: &DownloadTask, _options: &JobOptions) -> Vec<String> {
+        vec!["aria2.addUri".to_string(), task.source_url.clone()]
+    }
+
+    /// Hand the task to aria2, or pick up the download it already has, and follow it until
+    /// it's done. Pausing pauses it in aria2, cancelling removes it there.
+    async fn start(
+        &self,
+        task: &mut DownloadTask,
+        job: &mut Job,
+    ) -> Result<Option<DownloadStatus>> {
+        let rpc = Rpc::connect(&job.options.aria2).await?;
+        let known = match task.backend_id.clone() {
+            Some(gid) => match rpc.status(&gid).await.map(|s| s.status) {
+                Ok(status) if status == "paused" => {
+                    rpc.call::<String>("aria2.unpause", vec![json!(gid)])
+                        .await?;
+                    job.info(task, format!("[aria2] Resuming {gid}"));
+                    Some(gid)
+                }
+                Ok(status) if matches!(status.as_str(), "active" | "waiting" | "complete") => {
+                    job.info(task, format!("[aria2] Following {gid}"));
+                    Some(gid)
+                }
+                // gone with the aria2c that had it, or failed
+                _ => None,
+            },
+            None => None,
+        };
+        let mut gid = match known {
+            Some(gid) => gid,
+            None => {
+                let gid = rpc
+                    .call::<String>(
+                        "aria2.addUri",
+                        vec![json!([task.source_url]), add_options(task, &job.options)],
+                    )
+                    .await?;
+                job.info(task, format!("[aria2] Added as {gid}"));
+                gid
+            }
+        };
+        task.backend_id = Some(gid.clone());
+        job.progress(task);
+
+        let control = job.control.clone();
+        let mut destination = None;
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                stop = control.stopped() => {
+                    let method = match stop {
+                        Stop::Pause => "aria2.pause",
+                        Stop::Cancel => "aria2.remove",
+                    };
+                    match rpc.call::<String>(method, vec![json!(gid)]).await {
+                        Err(e) if stop == Stop::Pause => {
+                            log::warn!("Failed to pause `{}` in aria2. {e}", task.title);
+                        }
+                        // a retry removed it already
+                        Err(e) => log::debug!("Failed to remove `{}` from aria2. {e}", task.title),
+                        Ok(_) => {}
+                    }
+                    return Ok(None);
+                }
+                _ = ticker.tick() => {}
+            }
+
+            let status = rpc.status(&gid).await?;
+            if let Some(path) = status.files.first().map(|f| &f.path)
+                && !path.is_empty()
+                && destination.as_ref() != Some(path)
+            {
+                job.info(task, format!("[aria2] Destination: {path}"));
+                destination = Some(path.clone());
+            }
+            match status.status.as_str() {
+                "complete" => match status.followed_by.first() {
+                    // the torrent, metalink or magnet is read, what it describes comes next
+                    Some(next) => {
+                        job.info(task, format!("[aria2] {gid} continues as {next}"));
+                        gid = next.clone();
+                        task.backend_id = Some(gid.clone());
+                        destination = None;
+                    }
+                    None => {
+                        status.report(task);
+                        return Ok(Some(DownloadStatus::Completed));
+                    }
+                },
+                "error" => {
+                    let message = status
+                        .error_message
+                        .filter(|message| !message.is_empty())
+                        .unwrap_or_else(|| format!("aria2 failed with code {}", status.error_code));
+                    job.output(
+                        task,
+                        OutputLine {
+                            stderr: true,
+                            text: format!("ERROR: {message}"),
+                        },
+                    );
+                    return Ok(Some(DownloadStatus::Failed));
+                }
+                "removed" => bail!("the download was removed from aria2"),
+                _ => {
+                    status.report(task);
+                    job.progress(task);
+                }
+            }
+        }
+    }
+
+    /// Take the download out of aria2 and delete its control file. The file itself may be
+    /// one the user already had, so it stays.
+    fn discard(&self, task: &DownloadTask, options: &JobOptions) {
+        if let Some(gid) = &task.backend_id {
+            let removed = task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(Rpc::remove(&options.aria2, gid))
+            });
+            if let Err(e) = removed {
+                log::debug!("Failed to remove `{}` from aria2. {e}", task.title);
+            }
+        }
+        let mut control = task.destination_path.as_os_str().to_owned();
+        control.push(".aria2");
+        let _ = fs::remove_file(PathBuf::from(control));
+    }
+}
+
+/// Whether the URL points at a torrent or metalink
+fn is_descriptor(url: &Url) -> bool {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(|name| name.rsplit_once('.'))
+        .is_some_and(|(_, ext)| DESCRIPTOR_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// A config file with the RPC secret only the user can read. Other users see the command
+/// line of the aria2c cupcake starts, so the secret can't go there.
+fn write_conf(secret: &str) -> Result<PathBuf> {
+    let name = format!(
+        "cupcake-aria2-{}.conf",
+        hex::encode(rand::random::<[u8; 8]>())
+    );
+    let path = std::env::temp_dir().join(name);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    writeln!(file, "rpc-secret={secret}")?;
+    Ok(path)
+}
+
+/// The options of `aria2.addUri` for the task
+fn add_options(task: &DownloadTask, options: &JobOptions) -> Value {
+    let mut add = json!({
+        "dir": task.destination_dir(),
+        "continue": "true",
+        "split": options.connections.to_string(),
+        "max-connection-per-server": options.connections.min(MAX_CONNECTIONS_PER_SERVER).to_string(),
+        // a finished torrent counts as done instead of being seeded
+        "seed-time": "0",
+    });
+    // torrents and metalinks name their files themselves
+    let descriptor = Url::parse(&task.source_url)
+        .is_ok_and(|url| url.scheme() == "magnet" || is_descriptor(&url));
+    if !descriptor {
+        add["out"] = json!(task.title);
+    }
+    if let Some(rate) = &options.rate_limit {
+        add["max-download-limit"] = json!(rate);
+    }
+    let headers = Url::parse(&task.source_url)
+        .map(|url| http::task_headers(task, &url))
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|(name, value)| Some(format!("{name}: {}", value.to_str().ok()?)))
+        .collect::<Vec<_>>();
+    if !headers.is_empty() {
+        add["header"] = json!(headers);
+    }
+    add
+}
+
+/// What `aria2.tellStatus` says about a download, aria2 sends its numbers as strings
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Status {
+    status: String,
+    total_length: String,
+    completed_length: String,
+    download_speed: String,
+    error_code: String,
+    error_message: Option<String>,
+    /// The downloads a finished torrent, metalink or magnet started
+    followed_by: Vec<String>,
+    files: Vec<StatusFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StatusFile {
+    path: String,
+}
+
+impl Status {
+    /// Write the progress into the task the way yt-dlp's progress lines fill it
+    fn report(&self, task: &mut DownloadTask) {
+        let total = self.total_length.parse::<u64>().unwrap_or(0);
+        let completed = self.completed_length.parse::<u64>().unwrap_or(0);
+        let speed = self.download_speed.parse::<u64>().unwrap_or(0);
+
+        task.status = match self.status.as_str() {
+            "waiting" => DownloadStatus::Queued,
+            _ => DownloadStatus::Running,
+        };
+        task.download_speed = format!("{}/s", format_bytes(speed));
+        if total > 0 {
+            task.file_size = format_bytes(total);
+            task.progress_percent = (completed as f64 / total as f64 * 100.0) as f32;
+        }
+        task.estimated_time = match speed {
+            0 => "Unknown".to_string(),
+            _ => format_eta(total.saturating_sub(completed) / speed),
+        };
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+/// Client of an aria2c's JSON-RPC interface
+#[derive(Clone, Debug)]
+struct Rpc {
+    client: Client,
+    endpoint: String,
+    secret: Option<String>,
+}
+
+impl Rpc {
+    fn new(endpoint: String, secret: Option<String>) -> Result<Self> {
+        Ok(Self {
+            client: http::client()?,
+            endpoint,
+            secret,
+        })
+    }
+
+    /// The configured aria2c, or the one cupcake started, starting it first if needed
+    async fn connect(config: &Aria2Config) -> Result<Self> {
+        match &config.endpoint {
+            Some(endpoint) => Self::new(endpoint.clone(), config.secret.clone()),
+            None => LOCAL
+                .get_or_try_init(|| Self::spawn(&config.path))
+                .await
+                .cloned(),
+        }
+    }
+
+    /// Start an aria2c listening on a free loopback port, with a secret only cupcake knows.
+    /// It exits together with cupcake.
+    async fn spawn(path: &str) -> Result<Self> {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+            .local_addr()?
+            .port();
+        let secret = hex::encode(rand::random::<[u8; 16]>());
+        let conf = write_conf(&secret)?;
+        let rpc = Self::launch(path, port, &conf, secret).await;
+        // aria2c reads it before it answers, and it's no use once it failed to
+        let _ = fs::remove_file(&conf);
+        rpc
+    }
+
+    async fn launch(path: &str, port: u16, conf: &Path, secret: String) -> Result<Self> {
+        tokio::process::Command::new(path)
+            .args([
+                format!("--conf-path={}", conf.display()),
+                "--enable-rpc".to_string(),
+                "--rpc-listen-all=false".to_string(),
+                format!("--rpc-listen-port={port}"),
+                format!("--stop-with-process={}", std::process::id()),
+                "--max-concurrent-downloads=100".to_string(),
+                "--quiet".to_string(),
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| eyre!("failed to start {path}. {e}"))?;
+        log::info!("Started {path} on port {port}");
+
+        let rpc = Self::new(format!("http://127.0.0.1:{port}/jsonrpc"), Some(secret))?;
+        let started = Instant::now();
+        loop {
+            match rpc.call::<Value>("aria2.getVersion", Vec::new()).await {
+                Ok(_) => return Ok(rpc),
+                Err(e) if started.elapsed() > STARTUP_TIMEOUT => {
+                    bail!("{path} didn't answer on port {port}. {e}")
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Vec<Value>) -> Result<T> {
+        let params = self
+            .secret
+            .iter()
+            .map(|secret| json!(format!("token:{secret}")))
+            .chain(params)
+            .collect::<Vec<_>>();
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": "cupcake",
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .map_err(http::describe)?
+            .json::<RpcResponse<T>>()
+            .await
+            .map_err(http::describe)?;
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => bail!("aria2: {}", error.message),
+            (None, None) => bail!("aria2 sent no result for {method}"),
+        }
+    }
+
+    /// Stop the download in the aria2c that has it and wait until it let go of its file
+    async fn remove(config: &Aria2Config, gid: &str) -> Result<()> {
+        let rpc = match &config.endpoint {
+            Some(endpoint) => Self::new(endpoint.clone(), config.secret.clone())?,
+            None => match LOCAL.get() {
+                Some(rpc) => rpc.clone(),
+                // the aria2c of an earlier session took it along
+                None => return Ok(()),
+            },
+        };
+        rpc.call::<String>("aria2.forceRemove", vec![json!(gid)])
+            .await?;
+        let started = Instant::now();
+        while started.elapsed() < REMOVE_TIMEOUT {
+            match rpc.status(gid).await {
+                Ok(status) if status.status != "removed" => {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    async fn status(&self, gid: &str) -> Result<Status> {
+        self.call("aria2.tellStatus", vec![json!(gid)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_magnets_inside_the_destination() {
+        let name = |url: &str| Aria2.probe(url).map(|metadata| metadata.name).ok();
+        assert_eq!(
+            name("magnet:?xt=urn:btih:abc&dn=Some%20Album").as_deref(),
+            Some("Some Album")
+        );
+        assert_eq!(
+            name("magnet:?xt=urn:btih:abc&dn=../../x").as_deref(),
+            Some("x")
+        );
+        assert_eq!(
+            name("magnet:?xt=urn:btih:abc&dn=..").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(name("magnet:?dn=.."), None);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, mpsc::UnboundedSender};
+
+use crate::{
+    model::{
+        aria2::{Aria2, Aria2Config},
+        downloader::{DownloadStatus, DownloadTask, OutputLine},
+        http::Http,
+        task_log::TaskLog,
+        ytdlp::YtDlp,
+    },
+    update::message::Message,
+};
+
+/// A way of downloading tasks, e.g. with yt-dlp or natively over HTTP.
+///
+/// Every task records the backend it's downloaded with, so resuming and retrying it go through
+/// the same one.
+pub(crate) trait Backend {
+    /// Whether the backend is the one to download the URL with when the user doesn't pick one
+    fn handles(&self, url: &str) -> bool;
+
+    /// Look up what the URL is without downloading it, blocking until it's known
+    fn probe(&self, url: &str) -> Result<Metadata>;
+
+    /// The command line of the task's job, or the request standing in for it, for the log and
+    /// the detail pane
+    fn command(&self, task: &DownloadTask, options: &JobOptions) -> Vec<String>;
+
+    /// Download the task, reporting its progress and output through `job`, until it's done or
+    /// `job` asks it to stop. A pause keeps what was downloaded for the next start to resume,
+    /// a cancel doesn't have to. Returns the final status, or `None` once it stopped.
+    async fn start(&self, task: &mut DownloadTask, job: &mut Job)
+    -> Result<Option<DownloadStatus>>;
+
+    /// Remove what earlier runs left behind, so the next start begins from scratch
+    fn discard(&self, task: &DownloadTask, options: &JobOptions);
+}
+
+/// What a backend knows about a URL before downloading it
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    /// File name the download is saved as
+    pub name: String,
+    pub mime_type: Option<String>,
+}
+
+/// The backends a task can be downloaded with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackendKind {
+    #[serde(rename = "yt-dlp")]
+    YtDlp,
+    #[serde(rename = "http")]
+    Http,
+    #[serde(rename = "aria2")]
+    Aria2,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 3] = [BackendKind::Http, BackendKind::Aria2, BackendKind::YtDlp];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackendKind::YtDlp => "yt-dlp",
+            BackendKind::Http => "http",
+            BackendKind::Aria2 => "aria2",
+        }
+    }
+
+    /// The backend a URL is downloaded with when the task doesn't name one, yt-dlp unless a
+    /// more specific backend handles it
+    pub fn detect(url: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.handles(url))
+            .unwrap_or(BackendKind::YtDlp)
+    }
+
+    pub(crate) fn handles(self, url: &str) -> bool {
+        match self {
+            BackendKind::YtDlp => YtDlp.handles(url),
+            BackendKind::Http => Http.handles(url),
+            BackendKind::Aria2 => Aria2.handles(url),
+        }
+    }
+
+    pub(crate) fn probe(self, url: &str) -> Result<Metadata> {
+        match self {
+            BackendKind::YtDlp => YtDlp.probe(url),
+            BackendKind::Http => Http.probe(url),
+            BackendKind::Aria2 => Aria2.probe(url),
+        }
+    }
+
+    pub(crate) fn command(self, task: &DownloadTask, options: &JobOptions) -> Vec<String> {
+        match self {
+            BackendKind::YtDlp => YtDlp.command(task, options),
+            BackendKind::Http => Http.command(task, options),
+            BackendKind::Aria2 => Aria2.command(task, options),
+        }
+    }
+
+    pub(crate) async fn start(
+        self,
+        task: &mut DownloadTask,
+        job: &mut Job,
+    ) -> Result<Option<DownloadStatus>> {
+        match self {
+            BackendKind::YtDlp => YtDlp.start(task, job).await,
+            BackendKind::Http => Http.start(task, job).await,
+            BackendKind::Aria2 => Aria2.start(task, job).await,
+        }
+    }
+
+    pub(crate) fn discard(self, task: &DownloadTask, options: &JobOptions) {
+        match self {
+            BackendKind::YtDlp => YtDlp.discard(task, options),
+            BackendKind::Http => Http.discard(task, options),
+            BackendKind::Aria2 => Aria2.discard(task, options),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names = Self::ALL.map(|kind| kind.as_str()).join(", ");
+                format!("unknown backend `{s}`, use one of {names}")
+            })
+    }
+}
+
+/// Settings of the manager a job is started with
+#[derive(Clone, Debug, Default)]
+pub(crate) struct JobOptions {
+    /// Download rate limit such as `2M`
+    pub(crate) rate_limit: Option<String>,
+    /// Extra yt-dlp arguments of the task's preset
+    pub(crate) preset: Option<Vec<String>>,
+    /// Connections of a native or aria2 download, at least 1
+    pub(crate) connections: u32,
+    pub(crate) aria2: Aria2Config,
+}
+
+/// Why a running job is asked to stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// The task is resumed later
+    Pause,
+    /// The task is removed or started over
+    Cancel,
+}
+
+/// Number of the next job, see `Control::run`
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Handle to stop a running job, the manager keeps one per job
+#[derive(Clone, Debug)]
+pub struct Control {
+    run: u64,
+    stop: Arc<Mutex<Option<Stop>>>,
+    notify: Arc<Notify>,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            run: NEXT_RUN.fetch_add(1, Ordering::Relaxed),
+            stop: Arc::default(),
+            notify: Arc::default(),
+        }
+    }
+}
+
+impl Control {
+    /// Tells the job apart from earlier ones of the same task, which may still report after a
+    /// retry replaced them
+    pub fn run(&self) -> u64 {
+        self.run
+    }
+
+    pub fn stop(&self, stop: Stop) {
+        *self.stop.lock().expect("Failed to lock the job control") = Some(stop);
+        self.notify.notify_waiters();
+    }
+
+    /// Wait until the job is asked to stop
+    pub async fn stopped(&self) -> Stop {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            // registered before the check, so a stop in between isn't missed
+            notified.as_mut().enable();
+            if let Some(stop) = *self.stop.lock().expect("Failed to lock the job control") {
+                return stop;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// What a backend's job reports through: its progress and output go to the manager, the
+/// output to the task's log as well
+pub(crate) struct Job {
+    pub(crate) control: Control,
+    pub(crate) options: JobOptions,
+    pub(crate) log: Option<TaskLog>,
+    tx: UnboundedSender<Message>,
+}
+
+impl Job {
+    pub(crate) fn new(
+        control: Control,
+        options: JobOptions,
+        log: Option<TaskLog>,
+        tx: UnboundedSender<Message>,
+    ) -> Self {
+        Self {
+            control,
+            options,
+            log,
+            tx,
+        }
+    }
+
+    /// Send the task's progress to the manager
+    pub(crate) fn progress(&self, task: &DownloadTask) {
+        let _ = self.tx.send(Message::UpdateDownloadStatus(
+            Box::new(task.clone()),
+            self.control.run(),
+        ));
+        let _ = self.tx.send(Message::UpdateProgressSingle);
+    }
+
+    /// Write a line of output to the task's log and send it to the detail pane
+    pub(crate) fn output(&mut self, task: &DownloadTask, line: OutputLine) {
+        if let Some(file) = self.log.as_mut()
+            && let Err(e) = file.push(&line)
+        {
+            // keep the download going, the output is still shown in the detail pane
+            self.log = None;
+            log::warn!("Failed to write the log of `{}`. {e}", task.title);
+        }
+        let _ = self.tx.send(Message::TaskOutput(task.id(), line));
+    }
+
+    /// Shorthand for a line of informational output
+    pub(crate) fn info(&mut self, task: &DownloadTask, text: String) {
+        self.output(
+            task,
+            OutputLine {
+                stderr: false,
+                text,
+            },
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    path::PathBuf,
+};
+
+use color_eyre::{Result, eyre::bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    daemon::api::ApiConfig,
+    model::{
+        aria2::Aria2Config, downloader::DownloadTask, filter::SavedFilter, hooks::HooksConfig,
+        plugins::PluginConfig, task_log::LogRetention, webhooks::WebhookConfig,
+    },
+};
+
+/// Tree identifier of the catch-all category, reserved for tasks that match no other category.
+pub const OTHERS_CATEGORY: &str = "Others";
+
+/// User configuration, stored as `config.json` in the cupcake config directory.
+///
+/// Every field falls back to its default when missing, so a config file only needs to
+/// contain the settings the user actually wants to change.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub categories: Vec<Category>,
+    pub filters: Vec<SavedFilter>,
+    /// Key bindings on top of the defaults, by context then keys, e.g.
+    /// `{"content": {"n": "next_row", "j": "none"}}`
+    pub keys: BTreeMap<String, BTreeMap<String, String>>,
+    /// One of the built-in themes, e.g. `dracula`
+    pub theme: Option<String>,
+    /// Download rate limit such as `2M`, passed to yt-dlp as `--limit-rate`
+    pub rate_limit: Option<String>,
+    /// How many task logs to keep under `logs/` in the data directory
+    pub task_logs: LogRetention,
+    /// Level of the app log such as `warn` or `debug`, overridden by `CUPCAKE_LOG`
+    pub log_level: Option<String>,
+    /// Named sets of extra yt-dlp arguments, e.g. `{"audio": ["-x", "--audio-format", "mp3"]}`
+    pub presets: BTreeMap<String, Vec<String>>,
+    /// Preset of the tasks added without one
+    pub default_preset: Option<String>,
+    /// Start the daemon when the TUI opens, so downloads keep running after it closes
+    pub daemon: bool,
+    /// HTTP API of the daemon, served on loopback once a token is set
+    pub api: ApiConfig,
+    /// Shell commands run when tasks are added, start, complete or fail
+    pub hooks: HooksConfig,
+    /// URLs the task events are POSTed to
+    pub webhooks: Vec<WebhookConfig>,
+    /// Lua plugins loaded from the `plugins` directory next to this file
+    pub plugins: PluginConfig,
+    /// Connections a native download of a large file is split into, 1 downloads in one piece
+    pub connections: u32,
+    /// The aria2c that magnet links, torrents, metalinks and FTP are downloaded with
+    pub aria2: Aria2Config,
+    /// Set on the defaults standing in for a config.json that failed to load, which are never
+    /// saved over it
+    #[serde(skip)]
+    fallback: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            categories: vec![
+                Category::new(
+                    "Music",
+                    "󰎆",
+                    &[
+                        "mp3", "wav", "aac", "ogg", "m4a", "flac", "wma", "aiff", "opus", "dsd",
+                    ],
+                    &["audio/*"],
+                ),
+                Category::new(
+                    "Videos",
+                    "",
+                    &[
+                        "mp4", "avi", "mkv", "mov", "wmv", "flv", "webm", "mpeg", "mpg", "3gp",
+                    ],
+                    &["video/*"],
+                ),
+                Category::new(
+                    "Documents",
+                    "󰈙",
+                    &[
+                        "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "rtf", "odt",
+                    ],
+                    &["application/pdf", "text/*"],
+                ),
+                Category::new(
+                    "Compressed",
+                    "",
+                    &[
+                        "zip", "rar", "tar", "gz", "7z", "bz2", "xz", "iso", "tgz", "z",
+                    ],
+                    &["application/zip", "application/x-7z-compressed"],
+                ),
+                Category::new(
+                    "Programs",
+                    "",
+                    &[
+                        "exe", "dll", "msi", "app", "dmg", "deb", "rpm", "sh", "bin", "jar", "apk",
+                        "xapk",
+                    ],
+                    &["application/vnd.android.package-archive"],
+                ),
+            ],
+            filters: Vec::new(),
+            keys: BTreeMap::new(),
+            theme: None,
+            rate_limit: None,
+            task_logs: LogRetention::default(),
+            log_level: None,
+            presets: BTreeMap::new(),
+            default_preset: None,
+            daemon: false,
+            api: ApiConfig::default(),
+            hooks: HooksConfig::default(),
+            webhooks: Vec::new(),
+            plugins: PluginConfig::default(),
+            connections: 4,
+            aria2: Aria2Config::default(),
+            fallback: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or(std::env::current_dir().unwrap_or_default())
+            .join("cupcake")
+    }
+
+    /// Load the config from disk, writing the defaults out on first run so they can be edited.
+    pub fn load() -> Result<Self> {
+        let path = Self::dir().join("config.json");
+        if !path.exists() {
+            let config = Self::default();
+            config.save()?;
+            return Ok(config);
+        }
+
+        let file = File::open(path)?;
+        let mut config: Config = serde_json::from_reader(file)?;
+
+        // menu identifiers must be unique, drop duplicates instead of panicking while rendering
+        config
+            .categories
+            .retain(|c| !c.name.eq_ignore_ascii_case(OTHERS_CATEGORY));
+        dedup_by_name(&mut config.categories, |c| &c.name);
+        dedup_by_name(&mut config.filters, |f| &f.name);
+
+        Ok(config)
+    }
+
+    /// Load the config, or fall back to the defaults with the reason it couldn't be loaded
+    pub fn load_or_default() -> (Self, Option<String>) {
+        match Self::load() {
+            Ok(config) => (config, None),
+            Err(e) => (
+                Self {
+                    fallback: true,
+                    ..Self::default()
+                },
+                Some(format!(
+                    "Failed to load {}, using the defaults. {e}",
+                    Self::dir().join("config.json").display()
+                )),
+            ),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.fallback {
+            bail!("config.json failed to load, fix it and restart so it isn't overwritten");
+        }
+        let path = Self::dir();
+        fs::create_dir_all(path.clone())?;
+        let file = File::create(path.join("config.json"))?;
+        serde_json::to_writer_pretty(file, &self)?;
+        Ok(())
+    }
+}
+
+/// Keep only the first of the items sharing a name
+fn dedup_by_name<T>(items: &mut Vec<T>, name: impl Fn(&T) -> &str) {
+    let mut seen = HashSet::new();
+    items.retain(|item| seen.insert(name(item).to_string()));
+}
+
+/// A named group of tasks, shown as a node under every status group in the sidebar.
+///
+/// A task belongs to the category if its file extension or its MIME type matches.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Category {
+    pub name: String,
+    pub icon: String,
+    /// File extensions without the leading dot, compared case-insensitively
+    pub extensions: Vec<String>,
+    /// MIME patterns such as `video/mp4` or `video/*`
+    pub mime_types: Vec<String>,
+}
+
+impl Category {
+    fn new(name: &str, icon: &str, extensions: &[&str], mime_types: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            icon: icon.to_string(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            mime_types: mime_types.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        if self.icon.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} {}", self.icon, self.name)
+        }
+    }
+
+    pub fn matches(&self, task: &DownloadTask) -> bool {
+        let title = task.title.to_lowercase();
+        let by_extension = self.extensions.iter().any(|ext| {
+            let ext = ext.trim_start_matches('.').to_lowercase();
+            title
+                .strip_suffix(ext.as_str())
+                .is_some_and(|rest| rest.ends_with('.'))
+        });
+
+        let by_mime = task.mime_type.as_deref().is_some_and(|mime| {
+            let mime = mime.split(';').next().unwrap_or_default().trim();
+            self.mime_types
+                .iter()
+                .any(|pattern| match pattern.strip_suffix("/*") {
+                    Some(kind) => mime
+                        .split_once('/')
+                        .is_some_and(|(k, _)| k.eq_ignore_ascii_case(kind)),
+                    None => mime.eq_ignore_ascii_case(pattern),
+                })
+        });
+
+        by_extension || by_mime
+    }
+}
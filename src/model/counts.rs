@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use crate::model::{
+    config::{Category, Config, OTHERS_CATEGORY},
+    downloader::DownloadTask,
+    filter::{FilterQuery, SAVED_FILTERS_GROUP},
+    plugins::{PLUGIN_FILTERS_GROUP, Plugins},
+};
+
+/// Status groups shown at the top level of the sidebar, in display order.
+pub const STATUS_GROUPS: [&str; 4] = ["all", "unfinished", "finished", "failed"];
+
+/// Number of tasks matching each sidebar node, keyed by the node's tree identifier path.
+///
+/// The counts are adjusted per task as status updates arrive, and only rebuilt from scratch
+/// when the categories or saved filters change. Filters on the time a task was added are
+/// brought up to date by [`TaskCounts::refresh`].
+#[derive(Clone, Debug, Default)]
+pub struct TaskCounts {
+    categories: Vec<Category>,
+    filters: Vec<(String, FilterQuery)>,
+    plugins: Plugins,
+    counts: HashMap<Vec<String>, usize>,
+    /// The nodes each task is counted under by id, taken back out when it's removed
+    counted: HashMap<u64, Vec<Vec<String>>>,
+}
+
+impl TaskCounts {
+    pub fn new<'a>(
+        config: &Config,
+        plugins: &Plugins,
+        tasks: impl Iterator<Item = &'a DownloadTask>,
+    ) -> Self {
+        let mut counts = Self {
+            categories: config.categories.clone(),
+            filters: config
+                .filters
+                .iter()
+                .filter_map(|f| f.parse().ok().map(|q| (f.name.clone(), q)))
+                .collect(),
+            plugins: plugins.clone(),
+            counts: HashMap::new(),
+            counted: HashMap::new(),
+        };
+        tasks.for_each(|t| counts.add(t));
+        counts
+    }
+
+    pub fn get(&self, path: &[String]) -> usize {
+        self.counts.get(path).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, task: &DownloadTask) {
+        self.remove(task);
+        let paths = self.paths(task);
+        for path in &paths {
+            *self.counts.entry(path.clone()).or_default() += 1;
+        }
+        self.counted.insert(task.id(), paths);
+    }
+
+    pub fn remove(&mut self, task: &DownloadTask) {
+        for path in self.counted.remove(&task.id()).unwrap_or_default() {
+            if let Some(count) = self.counts.get_mut(&path) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Count the tasks again under the saved filters that depend on the time they were added,
+    /// which match other tasks as time passes. Returns whether a count changed.
+    pub fn refresh<'a>(&mut self, tasks: impl Iterator<Item = &'a DownloadTask>) -> bool {
+        if !self
+            .filters
+            .iter()
+            .any(|(_, query)| query.depends_on_time())
+        {
+            return false;
+        }
+        let mut changed = false;
+        for task in tasks {
+            let Some(counted) = self.counted.get(&task.id()) else {
+                continue;
+            };
+            let stale = self
+                .filters
+                .iter()
+                .filter(|(_, query)| query.depends_on_time())
+                .any(|(name, query)| {
+                    let path = vec![SAVED_FILTERS_GROUP.to_string(), name.clone()];
+                    query.matches(task, &self.categories) != counted.contains(&path)
+                });
+            if stale {
+                self.add(task);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Every sidebar node the task is listed under
+    fn paths(&self, task: &DownloadTask) -> Vec<Vec<String>> {
+        let mut categories = self
+            .categories
+            .iter()
+            .filter(|c| c.matches(task))
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+        if categories.is_empty() {
+            categories.push(OTHERS_CATEGORY.to_string());
+        }
+
+        let mut paths = Vec::new();
+        for group in STATUS_GROUPS
+            .iter()
+            .filter(|group| task.status.in_group(group))
+        {
+            paths.push(vec![group.to_string()]);
+            for category in &categories {
+                paths.push(vec![group.to_string(), category.clone()]);
+            }
+        }
+
+        paths.push(vec![SAVED_FILTERS_GROUP.to_string()]);
+        for (name, query) in &self.filters {
+            if query.matches(task, &self.categories) {
+                paths.push(vec![SAVED_FILTERS_GROUP.to_string(), name.clone()]);
+            }
+        }
+
+        paths.push(vec![PLUGIN_FILTERS_GROUP.to_string()]);
+        for name in self.plugins.filters() {
+            if self.plugins.matches(name, task) {
+                paths.push(vec![PLUGIN_FILTERS_GROUP.to_string(), name.clone()]);
+            }
+        }
+
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{downloader::unix_now, filter::SavedFilter};
+
+    fn filter_path(name: &str) -> Vec<String> {
+        vec![SAVED_FILTERS_GROUP.to_string(), name.to_string()]
+    }
+
+    #[test]
+    fn refreshes_filters_on_the_time_tasks_were_added() {
+        let mut config = Config::default();
+        config.filters.push(SavedFilter {
+            name: "recent".into(),
+            query: "added<1d".into(),
+        });
+        let mut task = DownloadTask::new("https://example.com/a", "/tmp".into(), "a.mp4".into());
+        task.added_at = unix_now();
+        let mut counts = TaskCounts::new(&config, &Plugins::default(), [&task].into_iter());
+        assert_eq!(counts.get(&filter_path("recent")), 1);
+        assert!(!counts.refresh([&task].into_iter()));
+
+        // a day later
+        task.added_at -= 2 * 24 * 60 * 60;
+        assert!(counts.refresh([&task].into_iter()));
+        assert_eq!(counts.get(&filter_path("recent")), 0);
+        assert_eq!(counts.get(&["all".to_string()]), 1);
+
+        counts.remove(&task);
+        assert_eq!(counts.get(&["all".to_string()]), 0);
+        assert_eq!(counts.get(&filter_path("recent")), 0);
+    }
+}
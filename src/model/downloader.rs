@@ -1,21 +1,37 @@
 use std::{
-    collections::VecDeque,
-    fs::{self, File},
-    io::{BufRead, BufReader},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs::{self, File, TryLockError},
+    io::{BufWriter, Write},
     path::PathBuf,
-    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use color_eyre::{Result, eyre::Ok};
+use color_eyre::{Result, eyre::bail};
 use ratatui::widgets::{ScrollbarState, TableState};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::update::message::Message;
+use crate::{
+    model::{
+        aria2::Aria2Config,
+        backend::{BackendKind, Control, Job, JobOptions, Metadata, Stop},
+        config::{Config, OTHERS_CATEGORY},
+        counts::TaskCounts,
+        filter::{SAVED_FILTERS_GROUP, parse_size},
+        hooks::{HookEvent, HookTask, Hooks, HooksConfig},
+        http::Segment,
+        plugins::{PLUGIN_FILTERS_GROUP, Plugins},
+        state::ActiveTab,
+        task_log::{self, LogRetention, TaskLog},
+        webhooks::WebhookConfig,
+    },
+    update::message::Message,
+};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct DownloadTask {
-    #[serde(skip)]
+    #[serde(default = "rand::random")]
     id: u64,
     pub title: String,
     pub source_url: String,
@@ -25,22 +41,198 @@ pub struct DownloadTask {
     pub progress_percent: f32,
     pub estimated_time: String,
     pub status: DownloadStatus,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Unix timestamp in seconds, 0 for tasks created before it was recorded
+    #[serde(default)]
+    pub added_at: u64,
+    /// Unix timestamps in seconds of the last run, 0 if it hasn't started or finished
+    #[serde(default)]
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: u64,
+    /// The command line of the last run
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Log with everything the task's jobs printed, `None` if it never ran
+    #[serde(default)]
+    pub log_path: Option<PathBuf>,
+    /// Name of the config preset whose yt-dlp arguments are added to the command
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Page the download was started from, sent as the `Referer`
+    #[serde(default)]
+    pub referer: Option<String>,
+    /// Cookies of the browser that handed the download over, passed to yt-dlp as `--cookies`
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
+    /// Connections a native download of a large file is split into, the config's if unset
+    #[serde(default)]
+    pub connections: Option<u32>,
+    /// Byte ranges of the last segmented native download, for the detail pane
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    /// Backend the task is downloaded with, chosen from the URL when it first starts unless
+    /// the user picked one
+    #[serde(default)]
+    pub backend: Option<BackendKind>,
+    /// The task's download in its backend, e.g. aria2's GID, for later runs to pick up
+    #[serde(default)]
+    pub backend_id: Option<String>,
 }
 
 impl DownloadTask {
-    fn update(&mut self, speed: String, progress: f32, eta: String, status: DownloadStatus) {
-        self.progress_percent = progress;
-        self.estimated_time = eta;
-        self.download_speed = speed;
-        self.status = status;
+    pub fn new(source: &str, destination: PathBuf, title: String) -> Self {
+        Self {
+            id: rand::random(),
+            destination_path: destination.join(&title),
+            title,
+            source_url: source.to_string(),
+            added_at: unix_now(),
+            ..Default::default()
+        }
+    }
+
+    fn update(&mut self, task: DownloadTask) {
+        self.progress_percent = task.progress_percent;
+        self.estimated_time = task.estimated_time;
+        self.download_speed = task.download_speed;
+        self.file_size = task.file_size;
+        self.status = task.status;
+        self.finished_at = task.finished_at;
+        self.segments = task.segments;
+        if task.backend_id.is_some() {
+            self.backend_id = task.backend_id;
+        }
+        if task.mime_type.is_some() {
+            self.mime_type = task.mime_type;
+        }
+    }
+
+    /// Clear the progress of a previous run so the task starts over
+    fn reset(&mut self) {
+        self.progress_percent = 0.0;
+        self.download_speed = String::new();
+        self.estimated_time = String::new();
+        self.status = DownloadStatus::Queued;
+        self.finished_at = 0;
+        self.segments.clear();
+        self.backend_id = None;
     }
 
     pub fn id(&self) -> u64 {
         self.id
     }
+
+    /// Directory the file is downloaded into
+    pub fn destination_dir(&self) -> PathBuf {
+        self.destination_path
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default()
+    }
+
+    /// Host of the source URL, e.g. `www.youtube.com`
+    pub fn site(&self) -> String {
+        let url = self
+            .source_url
+            .split_once("://")
+            .map_or(self.source_url.as_str(), |(_, rest)| rest);
+        url.split(['/', '?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    }
+
+    pub fn size_bytes(&self) -> Option<u64> {
+        parse_size(&self.file_size)
+    }
+
+    /// The command line of the last run, quoted so it can be pasted into a shell
+    pub fn command_line(&self) -> String {
+        self.command
+            .iter()
+            .map(|arg| {
+                if !arg.is_empty()
+                    && arg
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c))
+                {
+                    arg.clone()
+                } else {
+                    format!("'{}'", arg.replace('\'', "'\\''"))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+/// Write the tasks to `cupcake-export-<timestamp>.json` in the directory
+pub(crate) fn export_tasks(tasks: &[&DownloadTask], directory: PathBuf) -> Result<PathBuf> {
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("cupcake-export-{}.json", unix_now()));
+    let file = File::create(&path)?;
+    serde_json::to_writer_pretty(file, &tasks)?;
+    Ok(path)
+}
+
+/// A line the job printed to stdout or stderr
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputLine {
+    pub stderr: bool,
+    pub text: String,
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The file a line of yt-dlp's output says it writes to, if it is such a line
+pub(crate) fn written_file(line: &str) -> Option<&str> {
+    if let Some((_, path)) = line.split_once("Destination: ") {
+        return Some(path);
+    }
+    if let Some((_, path)) = line.split_once("Merging formats into ") {
+        return Some(path.trim_matches('"'));
+    }
+    line.strip_prefix("[download] ")
+        .and_then(|rest| rest.strip_suffix(" has already been downloaded"))
+}
+
+/// Format a unix timestamp as `2025-07-01 14:05:09 UTC`, or `-` when it isn't set
+pub(crate) fn format_timestamp(secs: u64) -> String {
+    if secs == 0 {
+        return "-".to_string();
+    }
+
+    // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum DownloadStatus {
     #[default]
@@ -51,6 +243,29 @@ pub enum DownloadStatus {
     Failed,
 }
 
+impl DownloadStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadStatus::Queued => "queued",
+            DownloadStatus::Running => "running",
+            DownloadStatus::Paused => "paused",
+            DownloadStatus::Completed => "completed",
+            DownloadStatus::Failed => "failed",
+        }
+    }
+
+    /// Whether the status belongs to one of the sidebar status groups
+    /// (`all`, `unfinished`, `finished` or `failed`)
+    pub fn in_group(&self, group: &str) -> bool {
+        match group {
+            "unfinished" => !matches!(self, DownloadStatus::Completed | DownloadStatus::Failed),
+            "finished" => matches!(self, DownloadStatus::Completed),
+            "failed" => matches!(self, DownloadStatus::Failed),
+            _ => true,
+        }
+    }
+}
+
 impl std::fmt::Display for DownloadStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let status = match self {
@@ -64,11 +279,37 @@ impl std::fmt::Display for DownloadStatus {
     }
 }
 
+/// Number of output lines kept per task
+const OUTPUT_LINES: usize = 1000;
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct DownloadManager {
     downloads: VecDeque<DownloadTask>,
     #[serde(skip)]
     pub(crate) state: DownloadManagerUIState,
+    /// Handles to stop the running jobs, keyed by task id
+    #[serde(skip)]
+    jobs: HashMap<u64, Control>,
+    /// Download rate limit passed to yt-dlp as `--limit-rate`, for jobs started from now on
+    #[serde(skip)]
+    pub(crate) rate_limit: Option<String>,
+    /// The last lines the jobs printed, keyed by task id
+    #[serde(skip)]
+    output: HashMap<u64, VecDeque<OutputLine>>,
+    #[serde(skip)]
+    pub(crate) log_retention: LogRetention,
+    /// Extra yt-dlp arguments by preset name, from the config
+    #[serde(skip)]
+    pub(crate) presets: BTreeMap<String, Vec<String>>,
+    #[serde(skip)]
+    pub(crate) hooks: Hooks,
+    #[serde(skip)]
+    pub(crate) plugins: Plugins,
+    /// Connections of native downloads of tasks that don't set their own
+    #[serde(skip)]
+    pub(crate) connections: u32,
+    #[serde(skip)]
+    pub(crate) aria2: Aria2Config,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -76,42 +317,191 @@ pub(crate) struct DownloadManagerUIState {
     pub(crate) filtered_downloads: VecDeque<DownloadTask>,
     pub(crate) table_state: TableState,
     pub(crate) scroll_state: ScrollbarState,
+    pub(crate) counts: TaskCounts,
+    /// Ids of the selected tasks, kept by id so the selection survives filtering
+    pub(crate) selection: HashSet<u64>,
+    /// Task visual mode was started on, `None` outside of visual mode
+    pub(crate) visual_anchor: Option<u64>,
 }
 
 impl DownloadManager {
-    // this optimisation is called "bitch, suck my dick"
-    pub fn update_download(&mut self, task: DownloadTask) {
+    /// A manager of stored tasks, its settings come from the config afterwards
+    fn from_downloads(downloads: VecDeque<DownloadTask>) -> Self {
+        let mut manager = Self {
+            state: DownloadManagerUIState {
+                filtered_downloads: downloads.clone(),
+                ..Default::default()
+            },
+            downloads,
+            connections: 1,
+            ..Default::default()
+        };
+        manager.reset_scroll_state();
+        manager
+    }
+
+    /// Apply a progress update from the job of the given run, returns whether the sidebar
+    /// counts changed.
+    ///
+    /// Updates for tasks without a job are dropped, they are leftovers from a job that was
+    /// paused or removed in the meantime. So are those of an earlier run a retry replaced.
+    pub fn update_download(&mut self, task: DownloadTask, run: u64) -> bool {
         let task_id = task.id;
-        if let Some(existing_task) = self.downloads.iter_mut().find(|t| t.id == task_id) {
-            existing_task.update(
-                task.download_speed.clone(),
-                task.progress_percent,
-                task.estimated_time.clone(),
-                task.status.clone(),
-            );
+        if self
+            .jobs
+            .get(&task_id)
+            .is_none_or(|control| control.run() != run)
+        {
+            return false;
+        }
+
+        if matches!(
+            task.status,
+            DownloadStatus::Completed | DownloadStatus::Failed
+        ) {
+            self.jobs.remove(&task_id);
+        }
+
+        let Some(existing_task) = self.downloads.iter_mut().find(|t| t.id == task_id) else {
+            return false;
+        };
+
+        let counts_changed = existing_task.status != task.status;
+        if counts_changed {
+            self.state.counts.remove(existing_task);
+        }
+        existing_task.update(task);
+        if counts_changed {
+            self.state.counts.add(existing_task);
+        }
+
+        let updated = existing_task.clone();
+        if counts_changed {
+            let event = match updated.status {
+                DownloadStatus::Completed => Some(HookEvent::Completed),
+                DownloadStatus::Failed => Some(HookEvent::Failed),
+                _ => None,
+            };
+            if let Some(event) = event {
+                self.notify(event, &updated);
+            }
+        }
+        if let Some(existing_task) = self
+            .state
+            .filtered_downloads
+            .iter_mut()
+            .find(|t| t.id == task_id)
+        {
+            *existing_task = updated;
+        }
+
+        counts_changed
+    }
+
+    /// Tell the hooks, webhooks and plugins about the task
+    fn notify(&self, event: HookEvent, task: &DownloadTask) {
+        if !self.hooks.wants(event) && !self.plugins.wants(event) {
+            return;
+        }
+        let hook = self.hook_task(task);
+        self.plugins.emit(event, Some((task, &hook)));
+        self.hooks.run(event, Some(hook));
+    }
+
+    /// The task as hooks get it, with the file and error its job printed last
+    fn hook_task(&self, task: &DownloadTask) -> HookTask {
+        let output = self.output.get(&task.id);
+        let lines = || output.into_iter().flatten().rev();
+        let path = lines()
+            .filter(|line| !line.stderr)
+            .find_map(|line| written_file(&line.text))
+            .map(PathBuf::from)
+            .unwrap_or_else(|| task.destination_path.clone());
+        let error = (task.status == DownloadStatus::Failed)
+            .then(|| {
+                lines()
+                    .filter(|line| line.stderr)
+                    .find_map(|line| line.text.strip_prefix("ERROR:"))
+                    .or_else(|| {
+                        lines()
+                            .find(|line| line.stderr)
+                            .map(|line| line.text.as_str())
+                    })
+                    .map(|error| error.trim().to_string())
+            })
+            .flatten();
+        HookTask::new(task, path, error)
+    }
+
+    /// Insert a new task or replace the stored copy of an existing one
+    fn upsert(&mut self, task: DownloadTask) {
+        if let Some(existing_task) = self.downloads.iter_mut().find(|t| t.id == task.id) {
+            self.state.counts.remove(existing_task);
+            *existing_task = task.clone();
         } else {
             self.downloads.push_back(task.clone());
         }
+        self.state.counts.add(&task);
 
         if let Some(existing_task) = self
             .state
             .filtered_downloads
             .iter_mut()
-            .find(|t| t.id == task_id)
+            .find(|t| t.id == task.id)
         {
-            existing_task.update(
-                task.download_speed,
-                task.progress_percent,
-                task.estimated_time,
-                task.status,
-            );
+            *existing_task = task;
         } else {
-            self.state.filtered_downloads.push_back(task.clone());
+            self.state.filtered_downloads.push_back(task);
+        }
+        self.reset_scroll_state();
+    }
+
+    /// Take over the state of a task from the daemon, which runs the jobs when attached
+    pub fn apply_remote(&mut self, task: DownloadTask) -> bool {
+        let counts_changed = self.task(task.id).is_none_or(|t| t.status != task.status);
+        self.upsert(task);
+        counts_changed
+    }
+
+    /// Replace every task with the daemon's, keeping the filter and what is still selected
+    pub fn replace_remote(
+        &mut self,
+        downloads: VecDeque<DownloadTask>,
+        selected_menu_item: &[String],
+        config: &Config,
+    ) {
+        self.downloads = downloads;
+        self.recount(config);
+        self.state.filtered_downloads = self.downloads.clone();
+        self.filter_downloads_by_category(selected_menu_item, config);
+
+        let ids = self.downloads.iter().map(|t| t.id).collect::<HashSet<_>>();
+        self.state.selection.retain(|id| ids.contains(id));
+        // filtering starts the scrollbar over, put it back on the highlighted row
+        match self.state.table_state.selected() {
+            Some(_) if self.state.filtered_downloads.is_empty() => {
+                self.state.table_state.select(None)
+            }
+            Some(i) => self.select_row(i),
+            None => {}
         }
     }
 
-    pub fn filtered_downloads(&self) -> &VecDeque<DownloadTask> {
-        &self.state.filtered_downloads
+    pub fn contains(&self, id: u64) -> bool {
+        self.downloads.iter().any(|t| t.id == id)
+    }
+
+    pub fn task(&self, id: u64) -> Option<&DownloadTask> {
+        self.downloads.iter().find(|t| t.id == id)
+    }
+
+    pub fn is_running(&self, id: u64) -> bool {
+        self.jobs.contains_key(&id)
+    }
+
+    /// Rebuild the sidebar counts from scratch, needed when categories or filters change
+    pub fn recount(&mut self, config: &Config) {
+        self.state.counts = TaskCounts::new(config, &self.plugins, self.downloads.iter());
     }
 
     fn slugify(input: &str, separator: char) -> String {
@@ -132,90 +522,435 @@ impl DownloadManager {
             .join(separator.to_string().as_str())
     }
 
+    /// The file name the source is saved as, or the error the backend gave when it can't
     pub fn extract_filename(source: &str) -> Result<String> {
-        let name = Command::new("yt-dlp")
-            .arg("--no-warnings")
-            .arg("--print")
-            .arg("filename")
-            .arg(source)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?
-            .wait_with_output()?
-            .stdout;
-
-        Ok(Self::slugify(String::from_utf8(name)?.as_str(), '_'))
-    }
-
-    pub fn start_download(
-        &self,
-        source: &str,
-        destination: PathBuf,
-        title: String,
-        tx: UnboundedSender<Message>,
-    ) {
-        let source = source.to_string();
+        Ok(Self::probe(source, None)?.name)
+    }
+
+    /// Look the source up with the given backend, or the one chosen for its URL, with the
+    /// file name made safe to save as
+    pub fn probe(source: &str, backend: Option<BackendKind>) -> Result<Metadata> {
+        let backend = backend.unwrap_or_else(|| BackendKind::detect(source));
+        let metadata = backend.probe(source)?;
+        Ok(Metadata {
+            name: Self::slugify(&metadata.name, '_'),
+            ..metadata
+        })
+    }
+
+    /// Store the task and spawn a job for it with the task's backend, the job reports back
+    /// through `Message::UpdateDownloadStatus`. Tasks without a backend get the one that fits
+    /// their URL, and keep it for later runs, as they keep the URL the plugins rewrote.
+    pub fn start_download(&mut self, mut task: DownloadTask, tx: UnboundedSender<Message>) {
+        task.status = DownloadStatus::Queued;
+        // only before its first run, rewrites such as adding a query would pile up on the
+        // resumes and retries
+        if task.started_at == 0 {
+            task.source_url = self.plugins.rewrite_url(&task.source_url);
+        }
+        let backend = *task
+            .backend
+            .get_or_insert_with(|| BackendKind::detect(&task.source_url));
+        let options = self.job_options(&task);
+        task.command = backend.command(&task, &options);
+        if backend == BackendKind::YtDlp {
+            task.command = self.plugins.rewrite_args(&task, task.command.clone());
+        }
+        task.started_at = unix_now();
+        task.finished_at = 0;
+        task.log_path = Some(task_log::path(task.id));
+        let added = !self.contains(task.id);
+        self.upsert(task.clone());
+        if added {
+            self.notify(HookEvent::Added, &task);
+        }
+        self.notify(HookEvent::Started, &task);
+
+        let control = Control::default();
+        self.jobs.insert(task.id, control.clone());
+        let retention = self.log_retention.clone();
+
         tokio::spawn(async move {
-            if let Err(e) = async {
-
-                let id = rand::random::<u64>();
-
-                let mut cmd = Command::new("yt-dlp")
-                    .arg("--no-warnings")
-                    .arg("--newline")
-                    .arg("--progress-template")
-                    .arg("[CUPCAKE] %(progress._percent_str)s %(progress._total_bytes_str)s %(progress._speed_str)s ETA %(progress._eta_str)s")
-                    .arg(source.clone())
-                    .arg("-o")
-                    .arg(format!("{}/{}", destination.to_string_lossy(), title))
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()?;
-
-                let stdout = cmd.stdout.take().expect("Failed to capture stdout");
-                let reader = BufReader::new(stdout);
-
-                for line in reader.lines() {
-                    let line = line?;
-
-                    if line.starts_with("[CUPCAKE]") {
-                        let parts: Vec<&str> = line[10..].split_whitespace().collect();
-
-                        if parts.len() >= 5 {
-                            let progress = parts[0].trim_end_matches('%').parse::<f32>().unwrap_or(0.0);
-                            let file_size = parts[1].to_string();
-                            let status =  match progress {
-                                100.0 => DownloadStatus::Completed,
-                                _ if progress > 0.0 => DownloadStatus::Running,
-                                _ => DownloadStatus::Queued,
-                            };
-
-                            let _ = tx.send(Message::UpdateDownloadStatus(DownloadTask {
-                                title: title.clone(),
-                                file_size,
-                                source_url: source.to_string(),
-                                destination_path: destination.join(title.clone()),
-                                estimated_time: parts[4].to_string(),
-                                download_speed: parts[2].to_string(),
-                                status,
-                                progress_percent: progress,
-                                id,
-                            }));
-
-                            let _ = tx.send(Message::UpdateProgressSingle);
-                        }
+            let mut final_task = task;
+
+            // a missing log shouldn't keep the download from running
+            let log = match TaskLog::open(final_task.id, &final_task.command_line(), &retention) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    log::warn!("Failed to open the log of `{}`. {e}", final_task.title);
+                    None
+                }
+            };
+            let _ = task_log::prune(&retention);
+
+            let run = control.run();
+            let mut job = Job::new(control, options, log, tx.clone());
+            let result = backend.start(&mut final_task, &mut job).await;
+            let mut log = job.log;
+
+            match result {
+                Ok(Some(status)) => {
+                    final_task.status = status;
+                }
+                // stopped, whoever stopped the job owns the task now
+                Ok(None) => {
+                    if let Some(log) = log.as_mut() {
+                        let _ = log.note("stopped");
+                    }
+                    return;
+                }
+                Err(e) => {
+                    log::error!("Failed to run the job of `{}`. {e}", final_task.title);
+                    let line = OutputLine {
+                        stderr: true,
+                        text: e.to_string(),
+                    };
+                    if let Some(log) = log.as_mut() {
+                        let _ = log.push(&line);
                     }
+                    let _ = tx.send(Message::TaskOutput(final_task.id, line));
+                    final_task.status = DownloadStatus::Failed;
                 }
+            }
+            if let Some(log) = log.as_mut() {
+                let _ = log.note(final_task.status.as_str());
+            }
+            match final_task.status {
+                DownloadStatus::Failed => log::warn!(
+                    "`{}` failed, its output is in {}",
+                    final_task.title,
+                    task_log::path(final_task.id).display()
+                ),
+                _ => log::info!("`{}` {}", final_task.title, final_task.status.as_str()),
+            }
+
+            if final_task.status == DownloadStatus::Completed {
+                final_task.progress_percent = 100.0;
+            }
+            final_task.download_speed = String::new();
+            final_task.estimated_time = String::new();
+            final_task.finished_at = unix_now();
+            let _ = tx.send(Message::UpdateDownloadStatus(Box::new(final_task), run));
+            let _ = tx.send(Message::UpdateProgressSingle);
+        });
+    }
 
-                Ok(())
-            }.await {
-                eprintln!("{e}");
+    /// The settings a job of the task runs with
+    fn job_options(&self, task: &DownloadTask) -> JobOptions {
+        let preset = task.preset.as_ref().and_then(|name| {
+            let args = self.presets.get(name);
+            if args.is_none() {
+                log::warn!("Unknown preset `{name}` for `{}`, ignoring it", task.title);
             }
+            args.cloned()
         });
+        JobOptions {
+            rate_limit: self.rate_limit.clone(),
+            preset,
+            connections: task.connections.unwrap_or(self.connections).max(1),
+            aria2: self.aria2.clone(),
+        }
+    }
+
+    /// Keep a line the task's job printed, only the last `OUTPUT_LINES` are kept
+    pub fn push_output(&mut self, id: u64, line: OutputLine) {
+        if !self.contains(id) {
+            return;
+        }
+        let output = self.output.entry(id).or_default();
+        if output.len() == OUTPUT_LINES {
+            output.pop_front();
+        }
+        output.push_back(line);
+    }
+
+    pub fn output(&self, id: u64) -> Option<&VecDeque<OutputLine>> {
+        self.output.get(&id)
+    }
+
+    /// Fill the output of a task that hasn't run since startup from its log
+    pub fn load_output(&mut self, id: u64) {
+        if self.output.contains_key(&id) {
+            return;
+        }
+        let Some(path) = self.task(id).and_then(|t| t.log_path.clone()) else {
+            return;
+        };
+        if let Ok(lines) = task_log::tail(&path, OUTPUT_LINES) {
+            self.output.insert(id, lines);
+        }
+    }
+
+    /// Stop the running jobs of the given tasks and mark them as paused
+    pub fn pause(&mut self, ids: &[u64]) {
+        for id in ids {
+            if let Some(control) = self.jobs.remove(id) {
+                control.stop(Stop::Pause);
+                self.set_status(*id, DownloadStatus::Paused);
+            }
+        }
+    }
+
+    /// Start a job again for the given tasks that are neither running nor completed, their
+    /// backend picks up the partially downloaded file where it left off
+    pub fn resume(&mut self, ids: &[u64], tx: UnboundedSender<Message>) {
+        for id in ids {
+            if let Some(task) = self.task(*id).cloned()
+                && !self.is_running(*id)
+                && task.status != DownloadStatus::Completed
+            {
+                self.start_download(task, tx.clone());
+            }
+        }
+    }
+
+    /// Start the given tasks over from scratch, stopping them first if they are running
+    pub fn retry(&mut self, ids: &[u64], tx: UnboundedSender<Message>) {
+        for id in ids {
+            if let Some(control) = self.jobs.remove(id) {
+                control.stop(Stop::Cancel);
+            }
+            if let Some(mut task) = self.task(*id).cloned() {
+                self.discard(&task);
+                task.reset();
+                self.start_download(task, tx.clone());
+            }
+        }
+    }
+
+    /// Remove what the task's earlier runs left behind, its backend would resume the partial
+    /// file otherwise
+    fn discard(&self, task: &DownloadTask) {
+        if let Some(backend) = task.backend {
+            backend.discard(task, &self.job_options(task));
+        }
+    }
+
+    /// Store the task as queued without starting a job, for the command line.
+    /// Queued tasks are started the next time the app runs.
+    pub fn enqueue(&mut self, mut task: DownloadTask) {
+        task.status = DownloadStatus::Queued;
+        self.upsert(task);
+    }
+
+    /// Change the status of tasks without a job in this process, for the command line.
+    /// `reset` clears the progress and the partial file of the last run, as a retry does.
+    pub fn set_stored_status(&mut self, id: u64, status: DownloadStatus, reset: bool) {
+        if let Some(mut task) = self.task(id).cloned() {
+            if reset {
+                self.discard(&task);
+                task.reset();
+            }
+            task.status = status;
+            self.upsert(task);
+        }
+    }
+
+    /// Stop and forget the given tasks, downloaded files are left untouched
+    pub fn remove(&mut self, ids: &[u64]) {
+        for id in ids {
+            if let Some(control) = self.jobs.remove(id) {
+                control.stop(Stop::Cancel);
+            }
+            if let Some(task) = self.downloads.iter().find(|t| t.id == *id) {
+                self.state.counts.remove(task);
+                if let Some(path) = &task.cookie_file {
+                    let _ = fs::remove_file(path);
+                }
+            }
+            self.state.selection.remove(id);
+            self.output.remove(id);
+        }
+        self.downloads.retain(|t| !ids.contains(&t.id));
+        self.state
+            .filtered_downloads
+            .retain(|t| !ids.contains(&t.id));
+
+        let len = self.state.filtered_downloads.len();
+        if let Some(i) = self.state.table_state.selected()
+            && i >= len
+        {
+            self.state
+                .table_state
+                .select(if len == 0 { None } else { Some(len - 1) });
+        }
+        self.reset_scroll_state();
+    }
+
+    /// Move the given tasks to another directory, moving files that were already downloaded.
+    /// Running tasks are skipped, returns the number of tasks that were moved. A task that
+    /// can't be moved doesn't stop the others, the failures are returned together.
+    pub fn change_destination(&mut self, ids: &[u64], destination: PathBuf) -> Result<usize> {
+        let mut moved = 0;
+        let mut errors = Vec::new();
+        for id in ids {
+            if self.is_running(*id) {
+                continue;
+            }
+            let Some(mut task) = self.task(*id).cloned() else {
+                continue;
+            };
+
+            let new_path = destination.join(&task.title);
+            if task.destination_path.exists()
+                && let Err(e) = fs::create_dir_all(&destination)
+                    .and_then(|_| fs::rename(&task.destination_path, &new_path))
+            {
+                errors.push(format!("`{}`: {e}", task.title));
+                continue;
+            }
+            task.destination_path = new_path;
+            self.upsert(task);
+            moved += 1;
+        }
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+        Ok(moved)
+    }
+
+    /// Set the connections of the given tasks' native downloads from their next run on,
+    /// `None` goes back to the config's
+    pub fn set_task_connections(&mut self, ids: &[u64], connections: Option<u32>) {
+        for id in ids {
+            if let Some(mut task) = self.task(*id).cloned() {
+                task.connections = connections;
+                self.upsert(task);
+            }
+        }
+    }
+
+    /// Set the backend of the given tasks from their next run on, `None` chooses it from the
+    /// URL again
+    pub fn set_task_backend(&mut self, ids: &[u64], backend: Option<BackendKind>) {
+        for id in ids {
+            if let Some(mut task) = self.task(*id).cloned() {
+                task.backend = backend;
+                self.upsert(task);
+            }
+        }
+    }
+
+    /// Add tags to the given tasks, a tag prefixed with `-` is removed instead
+    pub fn tag(&mut self, ids: &[u64], tags: &[String]) {
+        for id in ids {
+            let Some(mut task) = self.task(*id).cloned() else {
+                continue;
+            };
+            for tag in tags {
+                match tag.strip_prefix('-') {
+                    Some(tag) => task.tags.retain(|t| t != tag),
+                    None if !task.tags.contains(tag) => task.tags.push(tag.clone()),
+                    None => {}
+                }
+            }
+            self.upsert(task);
+        }
+    }
+
+    /// Write the given tasks as JSON into `directory`, returns the path of the export
+    pub fn export(&self, ids: &[u64], directory: PathBuf) -> Result<PathBuf> {
+        let tasks = self
+            .downloads
+            .iter()
+            .filter(|t| ids.contains(&t.id))
+            .collect::<Vec<_>>();
+        export_tasks(&tasks, directory)
+    }
+
+    fn set_status(&mut self, id: u64, status: DownloadStatus) {
+        if let Some(mut task) = self.task(id).cloned() {
+            task.status = status;
+            task.download_speed = String::new();
+            task.estimated_time = String::new();
+            self.upsert(task);
+        }
+    }
+
+    /// The tasks a bulk action applies to: the selection, or the highlighted row if
+    /// nothing is selected
+    pub fn targets(&self) -> Vec<u64> {
+        if !self.state.selection.is_empty() {
+            return self.state.selection.iter().copied().collect();
+        }
+        self.highlighted().map(|t| t.id).into_iter().collect()
+    }
+
+    pub fn highlighted(&self) -> Option<&DownloadTask> {
+        self.state
+            .table_state
+            .selected()
+            .and_then(|i| self.state.filtered_downloads.get(i))
+    }
+
+    /// Add the highlighted row to the selection, or remove it if it is already selected
+    pub fn toggle_selection(&mut self) {
+        if let Some(id) = self.highlighted().map(|t| t.id)
+            && !self.state.selection.remove(&id)
+        {
+            self.state.selection.insert(id);
+        }
+    }
+
+    /// Start or end visual mode, in visual mode moving the cursor selects every row
+    /// between the row visual mode was started on and the cursor
+    pub fn toggle_visual_mode(&mut self) {
+        match self.state.visual_anchor {
+            Some(_) => self.state.visual_anchor = None,
+            None => {
+                self.state.visual_anchor = self.highlighted().map(|t| t.id);
+                self.extend_visual_selection();
+            }
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.state.visual_anchor = None;
+        self.state.selection.clear();
+    }
+
+    fn extend_visual_selection(&mut self) {
+        let Some(anchor) = self.state.visual_anchor else {
+            return;
+        };
+        let Some(cursor) = self.state.table_state.selected() else {
+            return;
+        };
+        let Some(anchor) = self
+            .state
+            .filtered_downloads
+            .iter()
+            .position(|t| t.id == anchor)
+        else {
+            return;
+        };
+
+        let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+        self.state.selection.extend(
+            self.state
+                .filtered_downloads
+                .range(start..=end)
+                .map(|t| t.id),
+        );
+    }
+
+    fn reset_scroll_state(&mut self) {
+        let position = self.state.table_state.selected().unwrap_or_default() * 3;
+        self.state.scroll_state = ScrollbarState::new(
+            (if !self.state.filtered_downloads.is_empty() {
+                self.state.filtered_downloads.len() - 1
+            } else {
+                0
+            }) * 3,
+        )
+        .position(position);
     }
 
     pub(crate) fn next_row(&mut self) {
         let len = self.state.filtered_downloads.len();
+        if len == 0 {
+            return;
+        }
 
         let i = match self.state.table_state.selected() {
             Some(i) => {
@@ -230,10 +965,14 @@ impl DownloadManager {
 
         self.state.table_state.select(Some(i));
         self.state.scroll_state = self.state.scroll_state.position(i * 3);
+        self.extend_visual_selection();
     }
 
     pub(crate) fn previous_row(&mut self) {
         let len = self.state.filtered_downloads.len();
+        if len == 0 {
+            return;
+        }
 
         let i = match self.state.table_state.selected() {
             Some(i) => {
@@ -248,6 +987,27 @@ impl DownloadManager {
 
         self.state.table_state.select(Some(i));
         self.state.scroll_state = self.state.scroll_state.position(i * 3);
+        self.extend_visual_selection();
+    }
+
+    pub(crate) fn select_row(&mut self, i: usize) {
+        let len = self.state.filtered_downloads.len();
+        if len == 0 {
+            return;
+        }
+
+        let i = i.min(len - 1);
+        self.state.table_state.select(Some(i));
+        self.state.scroll_state = self.state.scroll_state.position(i * 3);
+        self.extend_visual_selection();
+    }
+
+    pub(crate) fn first_row(&mut self) {
+        self.select_row(0);
+    }
+
+    pub(crate) fn last_row(&mut self) {
+        self.select_row(usize::MAX);
     }
 
     /// Get the number of running downloads
@@ -277,224 +1037,59 @@ impl DownloadManager {
     /// Filter tasks based on the selected menu item
     /// # Arguments
     /// * `selected_menu_item` - A slice of strings representing the selected menu item
+    /// * `config` - The user config holding the categories and saved filters the menu was built from
     ///
     /// coming from `tui_tree_widget::TreeState.selected()`
-    /// All the &str are Tree identifiers
-    // TODO: refactor this and make it idiomatic
-    // also, fuck this garbage, right now i just want it to work
-    pub fn filter_downloads_by_category(&mut self, selected_menu_item: Vec<&str>) {
-        match selected_menu_item.len() {
-            1 => {
-                let identifier = selected_menu_item[0];
-                self.state.filtered_downloads = self
-                    .downloads
-                    .iter()
-                    .filter(|&t| match identifier {
-                        "unfinished" => {
-                            !matches!(t.status, DownloadStatus::Completed | DownloadStatus::Failed)
-                        }
-                        "finished" => matches!(t.status, DownloadStatus::Completed),
-                        "failed" => matches!(t.status, DownloadStatus::Failed),
-                        _ => true,
-                    })
-                    .cloned()
-                    .collect();
-                self.state.scroll_state = ScrollbarState::new(
-                    (if !self.state.filtered_downloads.is_empty() {
-                        self.state.filtered_downloads.len() - 1
-                    } else {
-                        0
-                    }) * 3,
-                );
-            }
+    /// All the strings are Tree identifiers, a status group optionally followed by a category name,
+    /// or `filters` followed by the name of a saved filter
+    pub fn filter_downloads_by_category(&mut self, selected_menu_item: &[String], config: &Config) {
+        let Some(group) = selected_menu_item.first() else {
+            return;
+        };
+        let child = selected_menu_item.get(1);
+        let categories = &config.categories;
 
-            2 => match selected_menu_item[1] {
-                "all-music" | "finished-music" | "unfinished-music" => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match selected_menu_item[1] {
-                                "finished-music" => matches!(t.status, DownloadStatus::Completed),
-                                "unfinished-music" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".mp3", ".wav", ".aac", ".ogg", ".m4a", ".flac", ".wma", ".aiff",
-                                ".opus", ".dsd",
-                            ]
-                            .iter()
-                            .any(|&ext| t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
-                }
-                "all-vids" | "finished-vids" | "unfinished-vids" => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match selected_menu_item[1] {
-                                "finished-vids" => matches!(t.status, DownloadStatus::Completed),
-                                "unfinished-vids" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".mp4", ".avi", ".mkv", ".mov", ".wmv", ".flv", ".webm", ".mpeg",
-                                ".mpg", ".3gp",
-                            ]
-                            .iter()
-                            .any(|&ext| t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
-                }
-                "all-docs" | "finished-docs" | "unfinished-docs" => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match selected_menu_item[1] {
-                                "finished-docs" => matches!(t.status, DownloadStatus::Completed),
-                                "unfinished-docs" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".pdf", ".doc", ".docx", ".xls", ".xlsx", ".ppt", ".pptx", ".txt",
-                                ".rtf", ".odt",
-                            ]
-                            .iter()
-                            .any(|&ext| t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
-                }
-                "all-compressed" | "finished-compressed" | "unfinished-compressed" => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match selected_menu_item[1] {
-                                "finished-compressed" => {
-                                    matches!(t.status, DownloadStatus::Completed)
-                                }
-                                "unfinished-compressed" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".zip", ".rar", ".tar", ".gz", ".7z", ".bz2", ".xz", ".iso",
-                                ".tgz", ".z",
-                            ]
-                            .iter()
-                            .any(|&ext| t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
+        let query = match (group.as_str(), child) {
+            (SAVED_FILTERS_GROUP, Some(name)) => config
+                .filters
+                .iter()
+                .find(|f| &f.name == name)
+                .and_then(|f| f.parse().ok()),
+            _ => None,
+        };
+
+        self.state.filtered_downloads = self
+            .downloads
+            .iter()
+            .filter(|&t| match (group.as_str(), child) {
+                (SAVED_FILTERS_GROUP, Some(_)) => {
+                    query.as_ref().is_some_and(|q| q.matches(t, categories))
                 }
-                "all-programs" | "finished-programs" | "unfinished-programs" => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match selected_menu_item[1] {
-                                "finished-programs" => {
-                                    matches!(t.status, DownloadStatus::Completed)
-                                }
-                                "unfinished-programs" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".exe", ".dll", ".msi", ".app", ".dmg", ".deb", ".rpm", ".sh",
-                                ".bin", ".jar", ".apk", ".xapk",
-                            ]
-                            .iter()
-                            .any(|&ext| t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
+                (SAVED_FILTERS_GROUP, None) => true,
+                (PLUGIN_FILTERS_GROUP, Some(name)) => self.plugins.matches(name, t),
+                (PLUGIN_FILTERS_GROUP, None) => true,
+                (_, None) => t.status.in_group(group),
+                (_, Some(name)) if name == OTHERS_CATEGORY => {
+                    t.status.in_group(group) && categories.iter().all(|c| !c.matches(t))
                 }
-                others => {
-                    self.state.filtered_downloads = self
-                        .downloads
-                        .iter()
-                        .filter(|&t| {
-                            (match others {
-                                "finished-others" => matches!(t.status, DownloadStatus::Completed),
-                                "unfinished-others" => !matches!(
-                                    t.status,
-                                    DownloadStatus::Completed | DownloadStatus::Failed
-                                ),
-                                _ => true,
-                            }) && ([
-                                ".mp3", ".wav", ".aac", ".ogg", ".m4a", ".flac", ".wma", ".aiff",
-                                ".opus", ".dsd", ".mp4", ".avi", ".mkv", ".mov", ".wmv", ".flv",
-                                ".webm", ".mpeg", ".mpg", ".3gp", ".pdf", ".doc", ".docx", ".xls",
-                                ".xlsx", ".ppt", ".pptx", ".txt", ".rtf", ".odt", ".zip", ".rar",
-                                ".tar", ".gz", ".7z", ".bz2", ".xz", ".iso", ".tgz", ".z", ".exe",
-                                ".dll", ".msi", ".app", ".dmg", ".deb", ".rpm", ".sh", ".bin",
-                                ".jar", ".apk", ".xapk",
-                            ]
+                (_, Some(name)) => {
+                    t.status.in_group(group)
+                        && categories
                             .iter()
-                            .all(|&ext| !t.title.ends_with(ext)))
-                        })
-                        .cloned()
-                        .collect();
-                    self.state.scroll_state = ScrollbarState::new(
-                        (if !self.state.filtered_downloads.is_empty() {
-                            self.state.filtered_downloads.len() - 1
-                        } else {
-                            0
-                        }) * 3,
-                    );
+                            .find(|c| &c.name == name)
+                            .is_some_and(|c| c.matches(t))
                 }
-            },
-            _ => {}
-        }
+            })
+            .cloned()
+            .collect();
+
+        self.state.scroll_state = ScrollbarState::new(
+            (if !self.state.filtered_downloads.is_empty() {
+                self.state.filtered_downloads.len() - 1
+            } else {
+                0
+            }) * 3,
+        );
     }
 }
 
@@ -516,61 +1111,265 @@ impl Downloader {
         let file = File::open(path.join("tasks.json"))?;
         let store: Downloader = serde_json::from_reader(file)?;
 
-        self.single = DownloadManager {
-            downloads: store.single.downloads.clone(),
-            state: DownloadManagerUIState {
-                filtered_downloads: store.single.downloads.clone(),
-                table_state: TableState::default(),
-                scroll_state: ScrollbarState::new(
-                    (if !store.single.downloads.is_empty() {
-                        store.single.downloads.len() - 1
-                    } else {
-                        0
-                    }) * 3,
-                ),
-            },
-        };
+        self.single = DownloadManager::from_downloads(store.single.downloads);
+        self.batch = DownloadManager::from_downloads(store.batch.downloads);
+        self.playlist = DownloadManager::from_downloads(store.playlist.downloads);
 
-        self.batch = DownloadManager {
-            downloads: store.batch.downloads.clone(),
-            state: DownloadManagerUIState {
-                filtered_downloads: store.batch.downloads.clone(),
-                table_state: TableState::default(),
-                scroll_state: ScrollbarState::new(
-                    (if !store.batch.downloads.is_empty() {
-                        store.batch.downloads.len() - 1
-                    } else {
-                        0
-                    }) * 3,
-                ),
-            },
-        };
+        Ok(self.clone())
+    }
 
-        self.playlist = DownloadManager {
-            downloads: store.playlist.downloads.clone(),
-            state: DownloadManagerUIState {
-                filtered_downloads: store.playlist.downloads.clone(),
-                table_state: TableState::default(),
-                scroll_state: ScrollbarState::new(
-                    (if !store.playlist.downloads.is_empty() {
-                        store.playlist.downloads.len() - 1
-                    } else {
-                        0
-                    }) * 3,
-                ),
-            },
+    /// The manager backing the given tab, tabs without tasks fall back to `single`
+    pub fn manager(&self, tab: &ActiveTab) -> &DownloadManager {
+        match tab {
+            ActiveTab::Batch => &self.batch,
+            ActiveTab::Playlist => &self.playlist,
+            _ => &self.single,
+        }
+    }
+
+    pub fn manager_mut(&mut self, tab: &ActiveTab) -> &mut DownloadManager {
+        match tab {
+            ActiveTab::Batch => &mut self.batch,
+            ActiveTab::Playlist => &mut self.playlist,
+            _ => &mut self.single,
+        }
+    }
+
+    /// The manager owning the task with the given id
+    pub fn manager_of(&self, id: u64) -> Option<&DownloadManager> {
+        [&self.single, &self.batch, &self.playlist]
+            .into_iter()
+            .find(|m| m.contains(id))
+    }
+
+    /// The manager owning the task with the given id
+    pub fn manager_of_mut(&mut self, id: u64) -> Option<&mut DownloadManager> {
+        [&mut self.single, &mut self.batch, &mut self.playlist]
+            .into_iter()
+            .find(|m| m.contains(id))
+    }
+
+    pub fn recount(&mut self, config: &Config) {
+        self.single.recount(config);
+        self.batch.recount(config);
+        self.playlist.recount(config);
+    }
+
+    /// Directory the tasks and other app data are stored in
+    pub fn dir() -> PathBuf {
+        dirs::data_local_dir()
+            .unwrap_or(std::env::current_dir().unwrap_or_default())
+            .join("cupcake")
+    }
+
+    /// Limit the download rate of the jobs started from now on, `None` removes the limit
+    pub fn set_rate_limit(&mut self, rate: Option<String>) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.rate_limit = rate.clone();
+        }
+    }
+
+    pub fn set_log_retention(&mut self, retention: LogRetention) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.log_retention = retention.clone();
+        }
+    }
+
+    pub fn set_connections(&mut self, connections: u32) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.connections = connections;
+        }
+    }
+
+    /// Where the jobs started from now on find aria2
+    pub fn set_aria2(&mut self, config: Aria2Config) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.aria2 = config.clone();
+        }
+    }
+
+    pub fn set_presets(&mut self, presets: BTreeMap<String, Vec<String>>) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.presets = presets.clone();
+        }
+    }
+
+    pub fn set_hooks(&mut self, config: HooksConfig, webhooks: Vec<WebhookConfig>) {
+        let hooks = Hooks::new(config, webhooks);
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.hooks = hooks.clone();
+        }
+    }
+
+    pub fn set_plugins(&mut self, plugins: Plugins) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            manager.plugins = plugins.clone();
+        }
+    }
+
+    /// Apply a progress update from a running job to the task's manager, see
+    /// `DownloadManager::update_download`. Runs the `on_queue_empty` hook once the last
+    /// running job has finished.
+    pub fn update_download(&mut self, task: DownloadTask, run: u64) -> bool {
+        let finished = matches!(
+            task.status,
+            DownloadStatus::Completed | DownloadStatus::Failed
+        );
+        let Some(manager) = self.manager_of_mut(task.id) else {
+            return false;
         };
+        let counts_changed = manager.update_download(task, run);
+        if finished
+            && counts_changed
+            && [&self.single, &self.batch, &self.playlist]
+                .iter()
+                .all(|m| m.jobs.is_empty())
+        {
+            self.single.plugins.emit(HookEvent::QueueEmpty, None);
+            self.single.hooks.run(HookEvent::QueueEmpty, None);
+        }
+        counts_changed
+    }
 
-        Ok(self.clone())
+    /// Every task of every tab
+    pub fn tasks(&self) -> impl Iterator<Item = &DownloadTask> {
+        [&self.single, &self.batch, &self.playlist]
+            .into_iter()
+            .flat_map(|m| m.downloads.iter())
+    }
+
+    /// Recount the saved filters on the time tasks were added, returns whether a count changed
+    pub fn refresh_counts(&mut self) -> bool {
+        self.managers_mut()
+            .map(|manager| manager.state.counts.refresh(manager.downloads.iter()))
+            .contains(&true)
+    }
+
+    pub fn managers_mut(&mut self) -> [&mut DownloadManager; 3] {
+        [&mut self.single, &mut self.batch, &mut self.playlist]
+    }
+
+    /// Start the tasks left queued by the command line, or interrupted by the end of a
+    /// previous session
+    pub fn start_pending(&mut self, tx: UnboundedSender<Message>) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            let ids = manager
+                .downloads
+                .iter()
+                .filter(|t| matches!(t.status, DownloadStatus::Queued | DownloadStatus::Running))
+                .map(|t| t.id)
+                .collect::<Vec<_>>();
+            manager.resume(&ids, tx.clone());
+        }
     }
 
+    /// Take over every task from the daemon, see `DownloadManager::replace_remote`
+    pub fn replace_remote(
+        &mut self,
+        other: Downloader,
+        selected_menu_item: &[String],
+        config: &Config,
+    ) {
+        self.single
+            .replace_remote(other.single.downloads, selected_menu_item, config);
+        self.batch
+            .replace_remote(other.batch.downloads, selected_menu_item, config);
+        self.playlist
+            .replace_remote(other.playlist.downloads, selected_menu_item, config);
+    }
+
+    /// Pause every running task of every tab
+    pub fn pause_all(&mut self) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            let ids = manager.jobs.keys().copied().collect::<Vec<_>>();
+            manager.pause(&ids);
+        }
+    }
+
+    /// Resume every paused task of every tab
+    pub fn resume_all(&mut self, tx: UnboundedSender<Message>) {
+        for manager in [&mut self.single, &mut self.batch, &mut self.playlist] {
+            let ids = manager
+                .downloads
+                .iter()
+                .filter(|t| t.status == DownloadStatus::Paused)
+                .map(|t| t.id)
+                .collect::<Vec<_>>();
+            manager.resume(&ids, tx.clone());
+        }
+    }
+
+    /// Write `tasks.json` through a temporary file, so a crash midway leaves the old one
     pub fn save(&self) -> Result<()> {
-        let path = dirs::data_local_dir()
-            .unwrap_or(std::env::current_dir().unwrap())
-            .join("cupcake");
+        let path = Self::dir();
         fs::create_dir_all(path.clone())?;
-        let file = File::create(path.join("tasks.json"))?;
-        serde_json::to_writer_pretty(file, &self)?;
+        let temp = path.join("tasks.json.tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer_pretty(&mut writer, &self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(temp, path.join("tasks.json"))?;
         Ok(())
     }
+
+    /// Take the lock on `tasks.json` that whoever changes it holds: the daemon and a TUI
+    /// without one for as long as they run, the command line while it writes. Waits a moment
+    /// for another command line to finish.
+    pub fn lock_store() -> Result<StoreLock> {
+        let path = Self::dir();
+        fs::create_dir_all(path.clone())?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.join("tasks.lock"))?;
+
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(StoreLock { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < STORE_LOCK_WAIT => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryLockError::WouldBlock) => bail!(
+                    "the tasks are in use by another cupcake, close it or run `cupcake daemon` \
+                     so both can share them"
+                ),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// How long to wait for the lock on `tasks.json` before giving up
+const STORE_LOCK_WAIT: Duration = Duration::from_secs(1);
+
+/// Hold on `tasks.json`, released when dropped
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: File,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_updates_of_replaced_runs() {
+        let task = DownloadTask::new("https://a.b/f.zip", "/tmp".into(), "f.zip".to_string());
+        let id = task.id();
+        let mut manager = DownloadManager::from_downloads(VecDeque::from([task.clone()]));
+        let (old, new) = (Control::default(), Control::default());
+        manager.jobs.insert(id, new.clone());
+
+        let mut finished = task.clone();
+        finished.status = DownloadStatus::Completed;
+        assert!(!manager.update_download(finished.clone(), old.run()));
+        assert!(manager.is_running(id));
+        assert_eq!(manager.task(id).unwrap().status, task.status);
+
+        assert!(manager.update_download(finished, new.run()));
+        assert!(!manager.is_running(id));
+        assert_eq!(manager.task(id).unwrap().status, DownloadStatus::Completed);
+    }
 }
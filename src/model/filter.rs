@@ -0,0 +1,346 @@
+use std::str::FromStr;
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    config::{Category, OTHERS_CATEGORY},
+    downloader::{DownloadTask, unix_now},
+};
+
+/// Tree identifier of the sidebar node holding the saved filters.
+pub const SAVED_FILTERS_GROUP: &str = "filters";
+
+/// A filter expression saved by the user and shown as a node in the sidebar.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SavedFilter {
+    pub name: String,
+    pub query: String,
+}
+
+impl SavedFilter {
+    pub fn parse(&self) -> Result<FilterQuery> {
+        self.query.parse()
+    }
+}
+
+/// A parsed filter expression.
+///
+/// The expression is a whitespace separated list of terms which must all match, spaces around
+/// the `:`, `<` and `>` of a term and before its unit are allowed (`size > 1 GiB`):
+/// * `status:failed` - a status (`queued`, `running`, `paused`, `completed`, `failed`)
+///   or a status group (`unfinished`, `finished`)
+/// * `category:videos` - one of the configured categories, or `others`
+/// * `site:youtube` - part of the host of the source URL
+/// * `tag:work` - a tag attached to the task
+/// * `size>1GiB`, `size<500M` - the file size
+/// * `added<7d`, `added>2w` - added within, or longer than, a duration ago (`h`, `d`, `w`)
+/// * any other word must appear in the title
+///
+/// Values can be combined with `|`, e.g. `status:failed|paused`.
+#[derive(Clone, Debug, Default)]
+pub struct FilterQuery {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Status(Vec<String>),
+    Category(Vec<String>),
+    Site(Vec<String>),
+    Tag(Vec<String>),
+    SizeAbove(u64),
+    SizeBelow(u64),
+    AddedWithin(u64),
+    AddedBefore(u64),
+    Text(String),
+}
+
+impl FromStr for FilterQuery {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words: Vec<String> = Vec::new();
+        for word in s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+        {
+            match words.last_mut() {
+                Some(last) if joins(last, word) => last.push_str(word),
+                _ => words.push(word.to_string()),
+            }
+        }
+        let terms = words
+            .iter()
+            .map(|word| Term::parse(word))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { terms })
+    }
+}
+
+/// Whether `word` continues the term in `last` that a space split, as in `size >`, `size >1`,
+/// `size>1 GiB` or `status: failed`
+fn joins(last: &str, word: &str) -> bool {
+    if last.ends_with([':', '<', '>']) {
+        return true;
+    }
+    if word.starts_with(['<', '>']) {
+        return last.chars().all(|c| c.is_ascii_alphabetic());
+    }
+    let unit = word.chars().all(|c| c.is_ascii_alphabetic())
+        && (parse_size(&format!("1{word}")).is_some() || matches!(word, "h" | "d" | "w"));
+    unit && last.rsplit_once(['<', '>']).is_some_and(|(_, value)| {
+        !value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == '.')
+    })
+}
+
+impl Term {
+    fn parse(word: &str) -> Result<Self> {
+        let word = word.to_lowercase();
+
+        if let Some((key, value)) = word.split_once(':') {
+            let values = value
+                .split('|')
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            if values.is_empty() {
+                bail!("missing value for `{key}:`");
+            }
+
+            return match key {
+                "status" => Ok(Term::Status(values)),
+                "category" | "cat" => Ok(Term::Category(values)),
+                "site" | "extractor" => Ok(Term::Site(values)),
+                "tag" => Ok(Term::Tag(values)),
+                _ => Err(eyre!("unknown filter key `{key}`")),
+            };
+        }
+
+        if let Some((key, op, value)) = word
+            .split_once('>')
+            .map(|(k, v)| (k, '>', v))
+            .or_else(|| word.split_once('<').map(|(k, v)| (k, '<', v)))
+        {
+            return match (key, op) {
+                ("size", '>') => Ok(Term::SizeAbove(Self::size(value)?)),
+                ("size", _) => Ok(Term::SizeBelow(Self::size(value)?)),
+                ("added", '<') => Ok(Term::AddedWithin(Self::duration(value)?)),
+                ("added", _) => Ok(Term::AddedBefore(Self::duration(value)?)),
+                _ => Err(eyre!("unknown filter key `{key}`")),
+            };
+        }
+
+        Ok(Term::Text(word))
+    }
+
+    fn size(value: &str) -> Result<u64> {
+        parse_size(value).ok_or_else(|| eyre!("invalid size `{value}`"))
+    }
+
+    fn duration(value: &str) -> Result<u64> {
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (amount, unit) = value.split_at(split);
+        let amount = amount
+            .parse::<u64>()
+            .map_err(|_| eyre!("invalid duration `{value}`"))?;
+
+        let unit_secs: u64 = match unit {
+            "h" => 60 * 60,
+            "d" | "" => 60 * 60 * 24,
+            "w" => 60 * 60 * 24 * 7,
+            _ => bail!("invalid duration unit `{unit}`, use h, d or w"),
+        };
+        amount
+            .checked_mul(unit_secs)
+            .ok_or_else(|| eyre!("duration `{value}` is too long"))
+    }
+
+    fn matches(&self, task: &DownloadTask, categories: &[Category]) -> bool {
+        match self {
+            Term::Status(values) => values.iter().any(|v| match v.as_str() {
+                "unfinished" | "finished" => task.status.in_group(v),
+                other => task.status.as_str() == other,
+            }),
+            Term::Category(values) => values.iter().any(|v| {
+                if v.eq_ignore_ascii_case(OTHERS_CATEGORY) {
+                    categories.iter().all(|c| !c.matches(task))
+                } else {
+                    categories
+                        .iter()
+                        .find(|c| c.name.eq_ignore_ascii_case(v))
+                        .is_some_and(|c| c.matches(task))
+                }
+            }),
+            Term::Site(values) => {
+                let site = task.site();
+                values.iter().any(|v| site.contains(v.as_str()))
+            }
+            Term::Tag(values) => values
+                .iter()
+                .any(|v| task.tags.iter().any(|t| t.eq_ignore_ascii_case(v))),
+            Term::SizeAbove(bytes) => task.size_bytes().is_some_and(|size| size > *bytes),
+            Term::SizeBelow(bytes) => task.size_bytes().is_some_and(|size| size < *bytes),
+            Term::AddedWithin(secs) => {
+                task.added_at > 0 && unix_now().saturating_sub(task.added_at) <= *secs
+            }
+            Term::AddedBefore(secs) => {
+                task.added_at > 0 && unix_now().saturating_sub(task.added_at) > *secs
+            }
+            Term::Text(text) => task.title.to_lowercase().contains(text.as_str()),
+        }
+    }
+}
+
+impl FilterQuery {
+    /// Whether tasks come and go as time passes, with `added<…` or `added>…`
+    pub fn depends_on_time(&self) -> bool {
+        self.terms
+            .iter()
+            .any(|term| matches!(term, Term::AddedWithin(_) | Term::AddedBefore(_)))
+    }
+
+    pub fn matches(&self, task: &DownloadTask, categories: &[Category]) -> bool {
+        self.terms.iter().all(|term| term.matches(task, categories))
+    }
+}
+
+/// Parse a human readable size such as `31.7MiB`, `1G` or `500kB` into bytes.
+///
+/// Units without `i` are treated as binary except the explicit SI forms (`kB`, `MB`, `GB`, `TB`),
+/// which matches how yt-dlp prints sizes.
+pub fn parse_size(input: &str) -> Option<u64> {
+    let input = input.trim().trim_start_matches('~');
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount = amount.parse::<f64>().ok()?;
+
+    let multiplier: f64 = match unit.trim() {
+        "" | "B" | "b" => 1.0,
+        "k" | "K" | "KiB" | "kib" => 1024.0,
+        "M" | "m" | "MiB" | "mib" => 1024.0_f64.powi(2),
+        "G" | "g" | "GiB" | "gib" => 1024.0_f64.powi(3),
+        "T" | "t" | "TiB" | "tib" => 1024.0_f64.powi(4),
+        "kB" | "KB" | "kb" => 1e3,
+        "MB" | "mb" => 1e6,
+        "GB" | "gb" => 1e9,
+        "TB" | "tb" => 1e12,
+        _ => return None,
+    };
+
+    Some((amount * multiplier) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn terms(query: &str) -> Vec<Term> {
+        query.parse::<FilterQuery>().unwrap().terms
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("1K"), Some(1024));
+        assert_eq!(parse_size("31.5MiB"), Some(33_030_144));
+        assert_eq!(parse_size("~2GiB"), Some(2 * GIB));
+        assert_eq!(parse_size("1 GiB"), Some(GIB));
+        assert_eq!(parse_size("500kB"), Some(500_000));
+        assert_eq!(parse_size("3GB"), Some(3_000_000_000));
+        assert_eq!(parse_size("1.5tb"), Some(1_500_000_000_000));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("GiB"), None);
+        assert_eq!(parse_size("12 parsecs"), None);
+        assert_eq!(parse_size("N/A"), None);
+    }
+
+    #[test]
+    fn parses_terms() {
+        assert_eq!(
+            terms("status:failed|paused cat:Videos site:youtube tag:work Talk"),
+            [
+                Term::Status(vec!["failed".into(), "paused".into()]),
+                Term::Category(vec!["videos".into()]),
+                Term::Site(vec!["youtube".into()]),
+                Term::Tag(vec!["work".into()]),
+                Term::Text("talk".into()),
+            ]
+        );
+        assert_eq!(
+            terms("size>1GiB, size<500M added<7d added>2w added<12h"),
+            [
+                Term::SizeAbove(GIB),
+                Term::SizeBelow(500 * 1024 * 1024),
+                Term::AddedWithin(7 * 24 * 3600),
+                Term::AddedBefore(14 * 24 * 3600),
+                Term::AddedWithin(12 * 3600),
+            ]
+        );
+    }
+
+    #[test]
+    fn allows_spaces_inside_terms() {
+        for query in ["size > 1 GiB", "size >1GiB", "size> 1GiB", "size>1 GiB"] {
+            assert_eq!(terms(query), [Term::SizeAbove(GIB)], "{query}");
+        }
+        assert_eq!(terms("added < 2 w"), [Term::AddedWithin(14 * 24 * 3600)]);
+        assert_eq!(
+            terms("status: failed"),
+            [Term::Status(vec!["failed".into()])]
+        );
+        assert_eq!(
+            terms("size>1 gigantic"),
+            [Term::SizeAbove(1), Term::Text("gigantic".into())]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_terms() {
+        for query in [
+            "status:",
+            "color:red",
+            "size>big",
+            "added<3y",
+            "added<99999999999999w",
+            "weight>2",
+        ] {
+            assert!(query.parse::<FilterQuery>().is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn matches_tasks() {
+        let mut task = DownloadTask::new(
+            "https://example.com/talk",
+            "/tmp".into(),
+            "Conference Talk.mp4".into(),
+        );
+        task.file_size = "1.5GiB".into();
+        task.added_at = unix_now() - 3 * 24 * 3600;
+        let query = |q: &str| q.parse::<FilterQuery>().unwrap().matches(&task, &[]);
+
+        assert!(query("size > 1 GiB talk"));
+        assert!(!query("size<1GiB"));
+        assert!(query("added<7d added>2d"));
+        assert!(!query("added<1d"));
+        assert!(query("category:others"));
+        assert!(!query("tag:work"));
+        assert!(query(""));
+    }
+}
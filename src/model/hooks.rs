@@ -0,0 +1,251 @@
+use std::{path::PathBuf, process::Stdio, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    task::JoinSet,
+};
+
+use crate::model::{config::Config, downloader::DownloadTask, webhooks::WebhookConfig};
+
+/// Shell commands run on task events, e.g. `{"on_completed": "mv \"$CUPCAKE_PATH\" ~/Videos"}`.
+///
+/// Each hook runs with `sh -c` and gets the task as `CUPCAKE_*` environment variables and
+/// as JSON on stdin. Whatever it prints goes to the app log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// A task was added
+    pub on_added: Option<String>,
+    /// A task's job started, also when it is resumed or retried
+    pub on_started: Option<String>,
+    pub on_completed: Option<String>,
+    pub on_failed: Option<String>,
+    /// The last running task finished
+    pub on_queue_empty: Option<String>,
+    /// Seconds a hook may run before it is killed
+    pub timeout: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            on_added: None,
+            on_started: None,
+            on_completed: None,
+            on_failed: None,
+            on_queue_empty: None,
+            timeout: 60,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    Added,
+    Started,
+    Completed,
+    Failed,
+    QueueEmpty,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::Added => "added",
+            HookEvent::Started => "started",
+            HookEvent::Completed => "completed",
+            HookEvent::Failed => "failed",
+            HookEvent::QueueEmpty => "queue_empty",
+        }
+    }
+}
+
+/// What a hook gets to know about the task
+#[derive(Clone, Debug, Serialize)]
+pub struct HookTask {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// The file yt-dlp last said it writes, the task's destination otherwise
+    pub path: PathBuf,
+    pub status: &'static str,
+    /// yt-dlp's error when the task failed
+    pub error: Option<String>,
+}
+
+impl HookTask {
+    pub fn new(task: &DownloadTask, path: PathBuf, error: Option<String>) -> Self {
+        Self {
+            id: task.id().to_string(),
+            title: task.title.clone(),
+            url: task.source_url.clone(),
+            path,
+            status: task.status.as_str(),
+            error,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: HookEvent,
+    task: Option<&'a HookTask>,
+}
+
+/// Runs the configured hooks and webhooks in the background, the downloads never wait for
+/// them
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    config: Arc<HooksConfig>,
+    webhooks: Arc<Vec<WebhookConfig>>,
+    /// Only made when there are webhooks
+    client: Option<reqwest::Client>,
+}
+
+impl Hooks {
+    pub fn new(config: HooksConfig, webhooks: Vec<WebhookConfig>) -> Self {
+        let client = (!webhooks.is_empty()).then(reqwest::Client::new);
+        Self {
+            config: Arc::new(config),
+            webhooks: Arc::new(webhooks),
+            client,
+        }
+    }
+
+    fn command(&self, event: HookEvent) -> Option<&String> {
+        match event {
+            HookEvent::Added => self.config.on_added.as_ref(),
+            HookEvent::Started => self.config.on_started.as_ref(),
+            HookEvent::Completed => self.config.on_completed.as_ref(),
+            HookEvent::Failed => self.config.on_failed.as_ref(),
+            HookEvent::QueueEmpty => self.config.on_queue_empty.as_ref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+
+    /// Whether a hook or webhook is set for the event, to skip gathering the task's details
+    /// otherwise
+    pub fn wants(&self, event: HookEvent) -> bool {
+        self.command(event).is_some() || self.webhooks.iter().any(|w| w.wants(event))
+    }
+
+    /// Run `on_added` and its webhooks for a task stored without the TUI or the daemon, they
+    /// run them when they add the task themselves
+    pub async fn stored(config: &Config, task: &DownloadTask) {
+        let hooks = Self::new(config.hooks.clone(), config.webhooks.clone());
+        if hooks.wants(HookEvent::Added) {
+            let hook = HookTask::new(task, task.destination_path.clone(), None);
+            hooks.fire(HookEvent::Added, Some(hook)).await;
+        }
+    }
+
+    /// Run the event's hook and call its webhooks, if any, without waiting for them
+    pub fn run(&self, event: HookEvent, task: Option<HookTask>) {
+        let hooks = self.clone();
+        tokio::spawn(async move { hooks.fire(event, task).await });
+    }
+
+    /// Run the event's hook and call its webhooks, if any, until they are done
+    pub async fn fire(&self, event: HookEvent, task: Option<HookTask>) {
+        let mut deliveries = JoinSet::new();
+        if let Some(client) = &self.client
+            && self.webhooks.iter().any(|w| w.wants(event))
+        {
+            match serde_json::to_vec(&Payload {
+                event,
+                task: task.as_ref(),
+            }) {
+                Ok(body) => {
+                    for webhook in self.webhooks.iter().filter(|w| w.wants(event)) {
+                        let (webhook, client, body) =
+                            (webhook.clone(), client.clone(), body.clone());
+                        deliveries
+                            .spawn(async move { webhook.deliver(&client, event, body).await });
+                    }
+                }
+                Err(e) => log::warn!("Failed to write the webhook payload. {e}"),
+            }
+        }
+
+        if let Some(command) = self.command(event) {
+            let timeout = Duration::from_secs(self.config.timeout);
+            if let Err(e) = Self::execute(command, event, task.as_ref(), timeout).await {
+                log::warn!("The on_{} hook failed. {e}", event.as_str());
+            }
+        }
+        deliveries.join_all().await;
+    }
+
+    async fn execute(
+        command: &str,
+        event: HookEvent,
+        task: Option<&HookTask>,
+        timeout: Duration,
+    ) -> color_eyre::Result<()> {
+        let name = format!("on_{}", event.as_str());
+        let mut process = tokio::process::Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .env("CUPCAKE_EVENT", event.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // a hook that outlives its timeout is killed with it
+            .kill_on_drop(true);
+        if let Some(task) = task {
+            process
+                .env("CUPCAKE_ID", &task.id)
+                .env("CUPCAKE_TITLE", &task.title)
+                .env("CUPCAKE_URL", &task.url)
+                .env("CUPCAKE_PATH", &task.path)
+                .env("CUPCAKE_STATUS", task.status)
+                .env("CUPCAKE_ERROR", task.error.as_deref().unwrap_or_default());
+        }
+        let mut child = process.spawn()?;
+
+        let payload = serde_json::to_vec(&Payload { event, task })?;
+        let mut stdin = child.stdin.take().expect("Failed to capture stdin");
+        // hooks that don't read stdin close it early, that's fine
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&payload).await;
+        });
+
+        let stdout = child.stdout.take().expect("Failed to capture stdout");
+        let stderr = child.stderr.take().expect("Failed to capture stderr");
+        let output = async {
+            let mut lines = BufReader::new(stdout).lines();
+            let mut errors = BufReader::new(stderr).lines();
+            let (mut stdout_open, mut stderr_open) = (true, true);
+            while stdout_open || stderr_open {
+                tokio::select! {
+                    line = lines.next_line(), if stdout_open => match line? {
+                        Some(line) => log::info!("{name}: {line}"),
+                        None => stdout_open = false,
+                    },
+                    line = errors.next_line(), if stderr_open => match line? {
+                        Some(line) => log::warn!("{name}: {line}"),
+                        None => stderr_open = false,
+                    },
+                }
+            }
+            child.wait().await
+        };
+
+        match tokio::time::timeout(timeout, output).await {
+            Ok(status) => {
+                let status = status?;
+                if !status.success() {
+                    log::warn!("The {name} hook exited with {status}");
+                }
+            }
+            Err(_) => log::warn!(
+                "The {name} hook was killed after {} seconds",
+                timeout.as_secs()
+            ),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1058 @@
+use std::{
+    fmt, fs,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::{Result, eyre::bail};
+use reqwest::{
+    Client, Response, StatusCode, Url,
+    header::{self, HeaderMap},
+    redirect::Policy,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt},
+    task::{self, JoinSet},
+};
+
+use crate::model::{
+    backend::{Backend, Job, JobOptions, Metadata},
+    downloader::{DownloadStatus, DownloadTask},
+    filter::parse_size,
+};
+
+/// Extensions of the files downloaded without yt-dlp, plain files it has nothing to extract
+/// from
+const DIRECT_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "dmg", "pkg", "deb", "rpm", "apk", "xapk", "appimage", "jar", "bin", "iso",
+    "img", "zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst", "pdf",
+];
+
+/// How many redirects are followed before giving up
+const MAX_REDIRECTS: usize = 10;
+
+/// How long connecting, or waiting for the next bytes, may take
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the progress of a job is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Smallest part of a file worth its own connection
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+/// How many more times a segment is fetched after its connection fails
+const SEGMENT_RETRIES: u32 = 3;
+
+/// Whether the URL points at a plain file the native downloader fetches, judging by the
+/// extension of its path
+pub fn is_direct(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    matches!(url.scheme(), "http" | "https")
+        && url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|name| name.rsplit_once('.'))
+            .is_some_and(|(_, ext)| DIRECT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// What the server says about a file before it is downloaded
+#[derive(Clone, Debug)]
+pub struct Probe {
+    /// The URL after following the redirects
+    pub url: Url,
+    /// From `Content-Disposition`, or the last segment of the URL's path
+    pub name: String,
+    pub size: Option<u64>,
+    pub mime_type: Option<String>,
+    /// Whether the server answers `Range` requests
+    pub ranges: bool,
+}
+
+impl Probe {
+    fn new(url: &str, response: &Response) -> Self {
+        let headers = response.headers();
+        let size = match response.status() {
+            StatusCode::PARTIAL_CONTENT => content_range_total(headers),
+            // not `response.content_length()`, a HEAD response has no body to measure
+            _ => headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok()?.parse().ok()),
+        };
+        Self {
+            url: response.url().clone(),
+            name: headers
+                .get(header::CONTENT_DISPOSITION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(disposition_name)
+                .or_else(|| url_name(response.url()))
+                .or_else(|| Url::parse(url).ok().as_ref().and_then(url_name))
+                .unwrap_or_else(|| "download".to_string()),
+            size,
+            mime_type: mime_type(headers),
+            ranges: response.status() == StatusCode::PARTIAL_CONTENT
+                || headers
+                    .get(header::ACCEPT_RANGES)
+                    .is_some_and(|value| value.as_bytes() == b"bytes"),
+        }
+    }
+}
+
+/// The client the native downloads use
+pub fn client() -> Result<Client> {
+    Ok(Client::builder()
+        .user_agent(concat!("cupcake/", env!("CARGO_PKG_VERSION")))
+        .redirect(Policy::limited(MAX_REDIRECTS))
+        .connect_timeout(TIMEOUT)
+        .read_timeout(TIMEOUT)
+        .build()?)
+}
+
+/// Ask the server about the file with a HEAD request, or with a GET of its first byte
+/// when HEAD isn't allowed
+pub async fn probe(client: &Client, url: &str, headers: HeaderMap) -> Result<Probe> {
+    if let Ok(response) = client.head(url).headers(headers.clone()).send().await
+        && response.status().is_success()
+    {
+        return Ok(Probe::new(url, &response));
+    }
+
+    let response = client
+        .get(url)
+        .headers(headers)
+        .header(header::RANGE, "bytes=0-0")
+        .send()
+        .await
+        .map_err(describe)?;
+    if !response.status().is_success() {
+        bail!("{}", status_error(response.status()));
+    }
+    Ok(Probe::new(url, &response))
+}
+
+/// Probe the URL from synchronous code running on the runtime, e.g. while a task is added
+pub fn probe_blocking(url: &str) -> Result<Probe> {
+    let client = client()?;
+    task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(probe(&client, url, HeaderMap::new()))
+    })
+}
+
+/// File the download is written to until it is complete
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Delete what a native download of `path` left behind, so it starts over
+pub fn remove_partial(path: &Path) {
+    let _ = fs::remove_file(part_path(path));
+    let _ = fs::remove_file(state_path(path));
+}
+
+/// Where a segmented download keeps the progress of its segments, next to the `.part` file
+fn state_path(path: &Path) -> PathBuf {
+    let mut state = part_path(path).into_os_string();
+    state.push(".segments");
+    PathBuf::from(state)
+}
+
+/// A byte range of a segmented download, fetched over its own connection
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: u64,
+    /// Inclusive, like the `Range` header
+    pub end: u64,
+    pub downloaded: u64,
+}
+
+impl Segment {
+    pub fn len(&self) -> u64 {
+        self.end + 1 - self.start
+    }
+}
+
+/// The segments of a download, stored while it runs so it resumes segment by segment
+#[derive(Serialize, Deserialize)]
+struct SegmentState {
+    size: u64,
+    segments: Vec<Segment>,
+}
+
+impl SegmentState {
+    /// The stored segments, if they belong to a partial file of `size` bytes
+    fn load(path: &Path, size: u64) -> Option<Vec<Segment>> {
+        let state: Self = serde_json::from_slice(&fs::read(state_path(path)).ok()?).ok()?;
+        let part_len = fs::metadata(part_path(path)).ok()?.len();
+        (state.size == size && part_len == size).then_some(state.segments)
+    }
+
+    fn save(path: &Path, size: u64, segments: &[Segment]) {
+        let state = Self {
+            size,
+            segments: segments.to_vec(),
+        };
+        let written = serde_json::to_vec(&state)
+            .map_err(color_eyre::Report::from)
+            .and_then(|json| Ok(fs::write(state_path(path), json)?));
+        if let Err(e) = written {
+            log::warn!("Failed to store the segments of {}. {e}", path.display());
+        }
+    }
+}
+
+/// Split `size` bytes into `count` even segments, counting the first `prefix` bytes as
+/// downloaded, e.g. by an earlier run in one piece
+fn plan(size: u64, count: u64, prefix: u64) -> Vec<Segment> {
+    let len = size.div_ceil(count);
+    (0..count)
+        .map(|i| i * len)
+        .take_while(|start| *start < size)
+        .map(|start| {
+            let end = (start + len).min(size) - 1;
+            Segment {
+                start,
+                end,
+                downloaded: prefix.saturating_sub(start).min(end + 1 - start),
+            }
+        })
+        .collect()
+}
+
+/// How a transfer ended, unless it failed
+enum Outcome {
+    /// With the size of the file
+    Completed(u64),
+    Cancelled,
+    /// The server sent the whole file for a segment's range
+    RangesIgnored,
+}
+
+/// Downloads plain files natively, through a `.part` file that a later run picks up with
+/// a `Range` request. Large files are split into segments fetched in parallel when the
+/// server supports ranges.
+pub(crate) struct Http;
+
+impl Backend for Http {
+    fn handles(&self, url: &str) -> bool {
+        is_direct(url)
+    }
+
+    fn probe(&self, url: &str) -> Result<Metadata> {
+        let probe = probe_blocking(url)?;
+        Ok(Metadata {
+            name: probe.name,
+            mime_type: probe.mime_type,
+        })
+    }
+
+    fn command(&self, task: &DownloadTask, _options: &JobOptions) -> Vec<String> {
+        vec!["GET".to_string(), task.source_url.clone()]
+    }
+
+    /// Download the task's URL into its destination in `connections` segments, or in one
+    /// piece. Pausing and cancelling both keep the partial file.
+    async fn start(
+        &self,
+        task: &mut DownloadTask,
+        job: &mut Job,
+    ) -> Result<Option<DownloadStatus>> {
+        let client = client()?;
+        let part = part_path(&task.destination_path);
+        if let Some(dir) = part.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let url = Url::parse(&task.source_url)?;
+        job.info(task, format!("[http] Probing {url}"));
+        let probe = probe(&client, url.as_str(), task_headers(task, &url)).await?;
+        if probe.url.as_str() != task.source_url {
+            job.info(task, format!("[http] Redirected to {}", probe.url));
+        }
+        job.info(
+            task,
+            format!(
+                "[http] {}, {}",
+                probe.size.map_or("Unknown size".to_string(), format_bytes),
+                probe.mime_type.as_deref().unwrap_or("unknown type")
+            ),
+        );
+        task.mime_type = probe.mime_type.clone().or(task.mime_type.take());
+        job.info(
+            task,
+            format!("[http] Destination: {}", task.destination_path.display()),
+        );
+
+        let transfer = Transfer {
+            client,
+            url: probe.url.clone(),
+            headers: task_headers(task, &probe.url),
+            path: task.destination_path.clone(),
+            rate: job
+                .options
+                .rate_limit
+                .as_deref()
+                .and_then(parse_size)
+                .filter(|rate| *rate > 0),
+        };
+        let started = Instant::now();
+        let segmented = probe
+            .size
+            .filter(|_| probe.ranges)
+            .map(|size| {
+                let count = (job.options.connections as u64).min(size / MIN_SEGMENT_SIZE);
+                (size, count)
+            })
+            .filter(|(_, count)| *count > 1);
+        let outcome = match segmented {
+            Some((size, count)) => match transfer.segmented(task, size, count, job).await? {
+                Outcome::RangesIgnored => {
+                    job.info(
+                        task,
+                        "[http] The server ignored the ranges, downloading in one piece"
+                            .to_string(),
+                    );
+                    transfer.single(task, &probe, job).await?
+                }
+                outcome => outcome,
+            },
+            None => transfer.single(task, &probe, job).await?,
+        };
+        let Outcome::Completed(size) = outcome else {
+            return Ok(None);
+        };
+
+        fs::rename(&part, &task.destination_path)?;
+        let _ = fs::remove_file(state_path(&task.destination_path));
+        task.file_size = format_bytes(size);
+        job.info(
+            task,
+            format!(
+                "[http] Downloaded {} in {}s",
+                task.file_size,
+                started.elapsed().as_secs()
+            ),
+        );
+        Ok(Some(DownloadStatus::Completed))
+    }
+
+    fn discard(&self, task: &DownloadTask, _options: &JobOptions) {
+        remove_partial(&task.destination_path);
+    }
+}
+
+/// What the connections of a job share
+#[derive(Clone)]
+struct Transfer {
+    client: Client,
+    /// The URL after the redirects
+    url: Url,
+    headers: HeaderMap,
+    /// The task's destination
+    path: PathBuf,
+    /// Bytes per second, for all connections together
+    rate: Option<u64>,
+}
+
+impl Transfer {
+    /// Download the file over one connection, appending to the partial file if the server
+    /// supports ranges
+    async fn single(
+        &self,
+        task: &mut DownloadTask,
+        probe: &Probe,
+        job: &mut Job,
+    ) -> Result<Outcome> {
+        let part = part_path(&self.path);
+        let control = job.control.clone();
+        task.segments.clear();
+        // a segmented download fills the partial file out of order
+        let mut offset = match fs::remove_file(state_path(&self.path)) {
+            Ok(()) => 0,
+            Err(_) => fs::metadata(&part).map(|m| m.len()).unwrap_or(0),
+        };
+        if offset > 0 && !probe.ranges {
+            job.info(
+                task,
+                "[http] The server doesn't support resuming, starting over".to_string(),
+            );
+            offset = 0;
+        }
+        if probe.size.is_some_and(|size| offset > size) {
+            job.info(
+                task,
+                "[http] The partial file is larger than the file, starting over".to_string(),
+            );
+            offset = 0;
+        }
+        if offset > 0 && probe.size == Some(offset) {
+            job.info(task, "[http] The partial file is complete".to_string());
+            return Ok(Outcome::Completed(offset));
+        }
+
+        let mut request = self
+            .client
+            .get(self.url.clone())
+            .headers(self.headers.clone());
+        if offset > 0 {
+            request = request.header(header::RANGE, format!("bytes={offset}-"));
+        }
+        let mut response = request.send().await.map_err(describe)?;
+        if !response.status().is_success() {
+            bail!("{}", status_error(response.status()));
+        }
+        if offset > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+            // the server ignored the range and sends the whole file
+            offset = 0;
+        }
+        if offset > 0 && content_range(response.headers()).is_none_or(|(first, ..)| first != offset)
+        {
+            bail!("the server answered bytes={offset}- with another range");
+        }
+        let append = offset > 0;
+        if append {
+            job.info(task, format!("[http] Resuming at {}", format_bytes(offset)));
+        }
+        let total = probe.size.or(match response.status() {
+            StatusCode::PARTIAL_CONTENT => content_range_total(response.headers()),
+            _ => response.content_length(),
+        });
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&part)
+            .await?;
+
+        let started = Instant::now();
+        let mut progress = Progress::new(offset, total);
+        loop {
+            let chunk = tokio::select! {
+                _ = control.stopped() => {
+                    file.flush().await?;
+                    return Ok(Outcome::Cancelled);
+                }
+                chunk = response.chunk() => chunk.map_err(describe)?,
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            if let Some(total) = total
+                && progress.downloaded + chunk.len() as u64 > total
+            {
+                bail!(
+                    "the server sent more than the {} it announced",
+                    format_bytes(total)
+                );
+            }
+            file.write_all(&chunk).await?;
+            progress.downloaded += chunk.len() as u64;
+
+            if progress.due() {
+                progress.report(task);
+                job.progress(task);
+            }
+
+            tokio::select! {
+                _ = control.stopped() => {
+                    file.flush().await?;
+                    return Ok(Outcome::Cancelled);
+                }
+                _ = throttle(self.rate, progress.downloaded - offset, started) => {}
+            }
+        }
+        file.flush().await?;
+
+        if let Some(total) = total
+            && progress.downloaded < total
+        {
+            bail!(
+                "the connection closed after {} of {}",
+                format_bytes(progress.downloaded),
+                format_bytes(total)
+            );
+        }
+        Ok(Outcome::Completed(progress.downloaded))
+    }
+
+    /// Download the file in `count` segments at once, each written in place into the
+    /// partial file. The segments are stored as they progress, a later run continues each
+    /// where it stopped.
+    async fn segmented(
+        &self,
+        task: &mut DownloadTask,
+        size: u64,
+        count: u64,
+        job: &mut Job,
+    ) -> Result<Outcome> {
+        let part = part_path(&self.path);
+        let control = job.control.clone();
+        let segments = match SegmentState::load(&self.path, size) {
+            Some(segments) => {
+                let downloaded = segments.iter().map(|s| s.downloaded).sum();
+                job.info(
+                    task,
+                    format!(
+                        "[http] Resuming {} segments at {}",
+                        segments.len(),
+                        format_bytes(downloaded)
+                    ),
+                );
+                segments
+            }
+            None => {
+                // what an earlier run in one piece got is the start of the first segments
+                let prefix = fs::metadata(&part).map(|m| m.len()).unwrap_or(0).min(size);
+                job.info(task, format!("[http] Downloading in {count} segments"));
+                plan(size, count, prefix)
+            }
+        };
+        fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&part)?
+            .set_len(size)?;
+        SegmentState::save(&self.path, size, &segments);
+
+        let counters = segments
+            .iter()
+            .map(|s| Arc::new(AtomicU64::new(s.downloaded)))
+            .collect::<Vec<_>>();
+        let snapshot = || {
+            segments
+                .iter()
+                .zip(&counters)
+                .map(|(segment, counter)| Segment {
+                    downloaded: counter.load(Ordering::Relaxed),
+                    ..segment.clone()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let pending = segments.iter().filter(|s| s.downloaded < s.len()).count() as u64;
+        let rate = self.rate.map(|rate| (rate / pending.max(1)).max(1));
+        let mut workers = JoinSet::new();
+        for (segment, counter) in segments.iter().zip(&counters) {
+            if segment.downloaded < segment.len() {
+                let (transfer, segment, counter) =
+                    (self.clone(), segment.clone(), Arc::clone(counter));
+                workers.spawn(async move { transfer.fetch(segment, counter, rate).await });
+            }
+        }
+
+        let mut progress = Progress::new(segments.iter().map(|s| s.downloaded).sum(), Some(size));
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = control.stopped() => {
+                    workers.shutdown().await;
+                    SegmentState::save(&self.path, size, &snapshot());
+                    return Ok(Outcome::Cancelled);
+                }
+                _ = ticker.tick() => {
+                    task.segments = snapshot();
+                    SegmentState::save(&self.path, size, &task.segments);
+                    progress.downloaded = task.segments.iter().map(|s| s.downloaded).sum();
+                    progress.report(task);
+                    job.progress(task);
+                }
+                joined = workers.join_next() => {
+                    match joined.map(|result| result.map_err(color_eyre::Report::from).and_then(|r| r)) {
+                        None => break,
+                        Some(Ok(())) => {}
+                        Some(Err(e)) => {
+                            workers.shutdown().await;
+                            SegmentState::save(&self.path, size, &snapshot());
+                            if e.is::<RangesIgnored>() {
+                                return Ok(Outcome::RangesIgnored);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+
+        task.segments = snapshot();
+        Ok(Outcome::Completed(size))
+    }
+
+    /// Fetch what is missing of the segment, trying again a few times when the connection
+    /// fails
+    async fn fetch(
+        &self,
+        segment: Segment,
+        counter: Arc<AtomicU64>,
+        rate: Option<u64>,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_once(&segment, &counter, rate).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < SEGMENT_RETRIES && !e.is::<RangesIgnored>() => {
+                    attempt += 1;
+                    log::debug!(
+                        "Segment at {} of {} failed, trying again. {e}",
+                        segment.start,
+                        self.url
+                    );
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn fetch_once(
+        &self,
+        segment: &Segment,
+        counter: &AtomicU64,
+        rate: Option<u64>,
+    ) -> Result<()> {
+        let start = segment.start + counter.load(Ordering::Relaxed);
+        if start > segment.end {
+            return Ok(());
+        }
+        let mut response = self
+            .client
+            .get(self.url.clone())
+            .headers(self.headers.clone())
+            .header(header::RANGE, format!("bytes={start}-{}", segment.end))
+            .send()
+            .await
+            .map_err(describe)?;
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            status if status.is_success() => return Err(RangesIgnored.into()),
+            status => bail!("{}", status_error(status)),
+        }
+        // bytes from anywhere else would end up in the wrong place of the file
+        match content_range(response.headers()) {
+            Some((first, last, _)) if first == start && last <= segment.end => {}
+            _ => bail!(
+                "the server answered bytes={start}-{} with another range",
+                segment.end
+            ),
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(part_path(&self.path))
+            .await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        let started = Instant::now();
+        let mut fetched = 0;
+        while let Some(chunk) = response.chunk().await.map_err(describe)? {
+            let left = segment.len() - counter.load(Ordering::Relaxed);
+            let chunk = &chunk[..chunk.len().min(left as usize)];
+            file.write_all(chunk).await?;
+            // only what reached the file counts, the rest is fetched again on resume
+            file.flush().await?;
+            counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            fetched += chunk.len() as u64;
+            if counter.load(Ordering::Relaxed) == segment.len() {
+                return Ok(());
+            }
+            throttle(rate, fetched, started).await;
+        }
+        bail!(
+            "the connection of the segment at {} closed early",
+            format_bytes(segment.start)
+        )
+    }
+}
+
+/// A server answered a segment's `Range` request with the whole file
+#[derive(Debug)]
+struct RangesIgnored;
+
+impl fmt::Display for RangesIgnored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the server ignored the range of a segment")
+    }
+}
+
+impl std::error::Error for RangesIgnored {}
+
+/// Sleep off what was downloaded faster than `rate` bytes per second allows
+async fn throttle(rate: Option<u64>, downloaded: u64, started: Instant) {
+    if let Some(rate) = rate {
+        let expected = Duration::from_secs_f64(downloaded as f64 / rate as f64);
+        if let Some(ahead) = expected.checked_sub(started.elapsed()) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+}
+
+/// The referer and the cookies for `url` the browser extension handed over with the task
+pub fn task_headers(task: &DownloadTask, url: &Url) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(referer) = task.referer.as_deref().and_then(|r| r.parse().ok()) {
+        headers.insert(header::REFERER, referer);
+    }
+    if let Some(cookies) = task
+        .cookie_file
+        .as_deref()
+        .and_then(|path| cookie_header(path, url))
+        .and_then(|c| c.parse().ok())
+    {
+        headers.insert(header::COOKIE, cookies);
+    }
+    headers
+}
+
+/// Bytes and speed of a running download, reported every `PROGRESS_INTERVAL`
+struct Progress {
+    downloaded: u64,
+    total: Option<u64>,
+    last_report: Instant,
+    last_downloaded: u64,
+}
+
+impl Progress {
+    fn new(downloaded: u64, total: Option<u64>) -> Self {
+        Self {
+            downloaded,
+            total,
+            last_report: Instant::now(),
+            last_downloaded: downloaded,
+        }
+    }
+
+    fn due(&self) -> bool {
+        self.last_report.elapsed() >= PROGRESS_INTERVAL
+    }
+
+    /// Write the progress into the task the way yt-dlp's progress lines fill it
+    fn report(&mut self, task: &mut DownloadTask) {
+        let elapsed = self.last_report.elapsed().as_secs_f64();
+        let speed = (self.downloaded - self.last_downloaded) as f64 / elapsed.max(0.001);
+        self.last_report = Instant::now();
+        self.last_downloaded = self.downloaded;
+
+        task.status = DownloadStatus::Running;
+        task.download_speed = format!("{}/s", format_bytes(speed as u64));
+        match self.total {
+            Some(total) if total > 0 => {
+                task.file_size = format_bytes(total);
+                task.progress_percent = (self.downloaded as f64 / total as f64 * 100.0) as f32;
+                task.estimated_time = match speed {
+                    0.0 => "Unknown".to_string(),
+                    _ => format_eta((total.saturating_sub(self.downloaded) as f64 / speed) as u64),
+                };
+            }
+            _ => {
+                task.file_size = format!("~{}", format_bytes(self.downloaded));
+                task.estimated_time = "Unknown".to_string();
+            }
+        }
+    }
+}
+
+/// Format a byte count the way yt-dlp does, e.g. `10.00MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.2}{}", UNITS[unit])
+}
+
+/// Format seconds the way yt-dlp does, `MM:SS` or `HH:MM:SS`
+pub fn format_eta(secs: u64) -> String {
+    match secs {
+        0..3600 => format!("{:02}:{:02}", secs / 60, secs % 60),
+        _ => format!(
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        ),
+    }
+}
+
+/// The error of a status the way yt-dlp words it, e.g. `HTTP Error 404: Not Found`
+fn status_error(status: StatusCode) -> String {
+    format!(
+        "HTTP Error {}: {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Unknown")
+    )
+}
+
+/// A request error with its causes, reqwest's own message leaves out why it failed
+pub fn describe(error: reqwest::Error) -> color_eyre::Report {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    color_eyre::eyre::eyre!(message)
+}
+
+/// The total of `Content-Range: bytes 0-99/1234`
+fn content_range_total(headers: &HeaderMap) -> Option<u64> {
+    content_range(headers)?.2
+}
+
+/// The first and last byte and the total of `Content-Range: bytes 0-99/1234`, the total is
+/// `None` when the server sends `*`
+fn content_range(headers: &HeaderMap) -> Option<(u64, u64, Option<u64>)> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    Some((
+        first.trim().parse().ok()?,
+        last.trim().parse().ok()?,
+        total.trim().parse().ok(),
+    ))
+}
+
+fn mime_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let mime = value.split(';').next()?.trim();
+    (!mime.is_empty()).then(|| mime.to_string())
+}
+
+/// The file name of `Content-Disposition: attachment; filename="a.zip"`, preferring the
+/// encoded `filename*=UTF-8''a%20b.zip`
+fn disposition_name(value: &str) -> Option<String> {
+    let params = value.split(';').filter_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        Some((key.trim().to_lowercase(), value.trim()))
+    });
+    let mut plain = None;
+    for (key, value) in params {
+        match key.as_str() {
+            "filename*" => {
+                let (_, encoded) = value.split_once("''")?;
+                return sanitize(&percent_decode(encoded));
+            }
+            "filename" => plain = sanitize(value.trim_matches('"')),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// The last segment of the URL's path, decoded
+pub fn url_name(url: &Url) -> Option<String> {
+    let name = url.path_segments()?.next_back()?;
+    sanitize(&percent_decode(name))
+}
+
+/// Keep a name a server or a page picked from leaving the destination directory, hiding in it or
+/// reading as an option. `.` and `..` have nothing left and are rejected
+pub fn sanitize(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.replace('\0', "");
+    let name = name.trim().trim_start_matches(['.', '-']).trim_start();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The `Cookie` header of a Netscape cookie file for the URL, the browser extensions store
+/// one per task
+fn cookie_header(path: &Path, url: &Url) -> Option<String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| log::warn!("Failed to read the cookies in {}. {e}", path.display()))
+        .ok()?;
+    cookies_for(&contents, url)
+}
+
+/// The cookies of the file that belong to the URL's host, so a redirect to another host
+/// doesn't get them
+fn cookies_for(contents: &str, url: &Url) -> Option<String> {
+    let host = url.host_str()?.to_lowercase();
+    let cookies = contents
+        .lines()
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [domain, subdomains, _, secure, _, name, value] = fields[..] else {
+                return None;
+            };
+            let domain = domain.trim_start_matches('.').to_lowercase();
+            let matches =
+                host == domain || (subdomains == "TRUE" && host.ends_with(&format!(".{domain}")));
+            let sent = secure != "TRUE" || url.scheme() == "https";
+            (matches && sent).then(|| format!("{name}={value}"))
+        })
+        .collect::<Vec<_>>();
+    (!cookies.is_empty()).then(|| cookies.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_disposition_names() {
+        assert_eq!(
+            disposition_name(r#"attachment; filename="a b.zip""#).as_deref(),
+            Some("a b.zip")
+        );
+        assert_eq!(
+            disposition_name(r#"attachment; filename="a.zip"; filename*=UTF-8''%C3%A4%20b.zip"#)
+                .as_deref(),
+            Some("ä b.zip")
+        );
+        assert_eq!(
+            disposition_name(r#"attachment; FILENAME="../../etc/passwd""#).as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(disposition_name(r#"attachment; filename="..""#), None);
+        assert_eq!(disposition_name("inline"), None);
+    }
+
+    #[test]
+    fn decodes_percents() {
+        assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("%E2%9C%93"), "✓");
+        // not an escape, kept as it is
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+    }
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize("a.zip").as_deref(), Some("a.zip"));
+        assert_eq!(sanitize("dir/sub\\a.zip").as_deref(), Some("a.zip"));
+        assert_eq!(sanitize(" .hidden").as_deref(), Some("hidden"));
+        assert_eq!(sanitize("--exec a").as_deref(), Some("exec a"));
+        assert_eq!(sanitize("a\0b").as_deref(), Some("ab"));
+        for name in ["", " ", ".", "..", "a/..", "a/", "-"] {
+            assert_eq!(sanitize(name), None, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn reads_content_range_totals() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_RANGE, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(content_range_total(&headers("bytes 0-99/1234")), Some(1234));
+        assert_eq!(content_range_total(&headers("bytes 0-99/*")), None);
+        assert_eq!(content_range_total(&HeaderMap::new()), None);
+        assert_eq!(
+            content_range(&headers("bytes 100-199/1234")),
+            Some((100, 199, Some(1234)))
+        );
+        assert_eq!(content_range(&headers("bytes */1234")), None);
+        assert_eq!(content_range(&headers("items 0-9/10")), None);
+    }
+
+    #[test]
+    fn plans_segments() {
+        let segment = |start, end, downloaded| Segment {
+            start,
+            end,
+            downloaded,
+        };
+        assert_eq!(
+            plan(10, 3, 0),
+            [segment(0, 3, 0), segment(4, 7, 0), segment(8, 9, 0)]
+        );
+        // what a download in one piece got counts for the segments it covers
+        assert_eq!(
+            plan(10, 3, 5),
+            [segment(0, 3, 4), segment(4, 7, 1), segment(8, 9, 0)]
+        );
+        assert_eq!(plan(2, 4, 0), [segment(0, 0, 0), segment(1, 1, 0)]);
+        let segments = plan(1000, 7, 0);
+        assert_eq!(segments.iter().map(Segment::len).sum::<u64>(), 1000);
+        assert!(segments.windows(2).all(|w| w[0].end + 1 == w[1].start));
+    }
+
+    #[test]
+    fn resumes_stored_segments() {
+        let dir = std::env::temp_dir().join(format!("cupcake-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.bin");
+        let mut segments = plan(10, 2, 0);
+        segments[0].downloaded = 3;
+
+        SegmentState::save(&path, 10, &segments);
+        // the partial file isn't there yet
+        assert_eq!(SegmentState::load(&path, 10), None);
+        fs::write(part_path(&path), [0; 10]).unwrap();
+        assert_eq!(SegmentState::load(&path, 10), Some(segments.clone()));
+        // the file changed on the server
+        assert_eq!(SegmentState::load(&path, 12), None);
+        fs::write(part_path(&path), [0; 4]).unwrap();
+        assert_eq!(SegmentState::load(&path, 10), None);
+
+        remove_partial(&path);
+        assert!(!state_path(&path).exists() && !part_path(&path).exists());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn sends_cookies_to_their_hosts() {
+        let contents = "# Netscape HTTP Cookie File\n\
+            .example.com\tTRUE\t/\tFALSE\t0\ta\t1\n\
+            #HttpOnly_cdn.example.com\tFALSE\t/\tFALSE\t0\tb\t2\n\
+            example.com\tFALSE\t/\tTRUE\t0\tc\t3\n\
+            other.org\tTRUE\t/\tFALSE\t0\td\t4\n\
+            broken line\n";
+        let cookies = |url: &str| cookies_for(contents, &Url::parse(url).unwrap());
+
+        assert_eq!(
+            cookies("https://example.com/f").as_deref(),
+            Some("a=1; c=3")
+        );
+        assert_eq!(cookies("http://example.com/f").as_deref(), Some("a=1"));
+        assert_eq!(
+            cookies("http://cdn.example.com/f").as_deref(),
+            Some("a=1; b=2")
+        );
+        assert_eq!(
+            cookies("http://www.cdn.example.com/f").as_deref(),
+            Some("a=1")
+        );
+        assert_eq!(cookies("http://notexample.com/f"), None);
+        assert_eq!(cookies("http://mirror.net/f"), None);
+    }
+}
@@ -1,3 +1,15 @@
+pub(crate) mod app_log;
+pub(crate) mod aria2;
+pub(crate) mod backend;
+pub(crate) mod config;
+pub(crate) mod counts;
 pub(crate) mod downloader;
+pub(crate) mod filter;
+pub(crate) mod hooks;
+pub(crate) mod http;
+pub(crate) mod plugins;
 pub(crate) mod state;
+pub(crate) mod task_log;
 pub(crate) mod theme;
+pub(crate) mod webhooks;
+pub(crate) mod ytdlp;
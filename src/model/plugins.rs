@@ -0,0 +1,879 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use mlua::{
+    Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, Table, Value, Variadic, VmState,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    config::Config,
+    downloader::DownloadTask,
+    hooks::{HookEvent, HookTask},
+};
+
+/// Tree identifier of the sidebar node holding the filters of the plugins
+pub const PLUGIN_FILTERS_GROUP: &str = "plugins";
+
+/// How long a plugin function may run before it is stopped
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Memory all plugins together may use
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// yt-dlp options an argument rewriter can't add. They run commands or other programs,
+/// update yt-dlp, load code, take more options or URLs from files, or write files outside
+/// the task's destination. yt-dlp takes abbreviations, so anything these start with counts
+/// too.
+const LOCKED_OPTIONS: &[&str] = &[
+    "--exec",
+    "--exec-before-download",
+    "--netrc-cmd",
+    "--update",
+    "--update-to",
+    "--config-location",
+    "--config-locations",
+    "--batch-file",
+    "--load-info-json",
+    "--enable-file-urls",
+    "--output",
+    "--paths",
+    "--print-to-file",
+    "--download-archive",
+    "--cookies",
+    "--cache-dir",
+    "--alias",
+    "--downloader",
+    "--external-downloader",
+    "--downloader-args",
+    "--external-downloader-args",
+    "--postprocessor-args",
+    "--ppa",
+    "--ffmpeg-location",
+    "--plugin-dirs",
+    "--use-postprocessor",
+];
+
+/// Options that start like a locked one but aren't, yt-dlp takes them as they are
+const UNLOCKED_OPTIONS: &[&str] = &["print"];
+
+/// Short forms of the locked options, `-o`, `-a`, `-P` and `-U`
+const LOCKED_SHORT_OPTIONS: &[char] = &['o', 'a', 'P', 'U'];
+
+/// Settings of the Lua plugins in the `plugins` directory next to `config.json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    pub enabled: bool,
+    /// Directories plugins may read and write files in with `cupcake.read_file` and
+    /// `cupcake.write_file`, none by default
+    pub allow_paths: Vec<PathBuf>,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allow_paths: Vec::new(),
+        }
+    }
+}
+
+/// A palette command a plugin registered
+#[derive(Clone, Debug)]
+pub struct PluginCommand {
+    pub name: String,
+    /// Shown next to the name like the built-in commands' arguments
+    pub args: String,
+    pub description: String,
+}
+
+/// Keys a plugin bound to a command line, like `config.json` binds them to actions
+#[derive(Clone, Debug)]
+pub struct PluginBinding {
+    pub context: String,
+    pub keys: String,
+    /// Run as if typed into the palette, e.g. `tag archived`
+    pub command: String,
+}
+
+/// A function a plugin registered, with the file it came from for the log
+#[derive(Clone)]
+struct Registration {
+    plugin: String,
+    function: Function,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// File name of the plugin being loaded, `None` once loading is over
+    loading: Option<String>,
+    handlers: Vec<(HookEvent, Registration)>,
+    url_rewriters: Vec<Registration>,
+    arg_rewriters: Vec<Registration>,
+    commands: Vec<(PluginCommand, Registration)>,
+    bindings: Vec<PluginBinding>,
+    filters: Vec<(String, Registration)>,
+    /// Command lines `cupcake.dispatch` queued during the running command
+    dispatched: Vec<String>,
+}
+
+struct Host {
+    lua: Lua,
+    registry: Arc<Mutex<Registry>>,
+    /// When the running call is stopped
+    deadline: Arc<Mutex<Option<Instant>>>,
+    commands: Vec<PluginCommand>,
+    bindings: Vec<PluginBinding>,
+    filters: Vec<String>,
+    /// What the filters said about a task by filter name and task id, with the hash of the
+    /// task as the filter saw it, so they only run again once something they see changes
+    verdicts: Mutex<HashMap<(String, u64), (u64, bool)>>,
+}
+
+/// The task as plugins see it
+#[derive(Serialize, Hash)]
+struct LuaTask<'a> {
+    id: String,
+    title: &'a str,
+    url: &'a str,
+    /// The file yt-dlp last said it writes in events, the destination otherwise
+    path: &'a Path,
+    status: &'static str,
+    tags: &'a [String],
+    /// In bytes, once known
+    size: Option<u64>,
+    site: String,
+    added_at: u64,
+    preset: Option<&'a str>,
+    /// yt-dlp's error in the `failed` event
+    error: Option<&'a str>,
+}
+
+impl<'a> LuaTask<'a> {
+    fn new(task: &'a DownloadTask, hook: Option<&'a HookTask>) -> Self {
+        Self {
+            id: task.id().to_string(),
+            title: &task.title,
+            url: &task.source_url,
+            path: hook.map_or(&task.destination_path, |hook| &hook.path),
+            status: task.status.as_str(),
+            tags: &task.tags,
+            size: task.size_bytes(),
+            site: task.site(),
+            added_at: task.added_at,
+            preset: task.preset.as_deref(),
+            error: hook.and_then(|hook| hook.error.as_deref()),
+        }
+    }
+}
+
+/// The Lua plugins, loaded once at startup. Their functions run one at a time on the
+/// caller's thread, a plugin that fails is logged and otherwise ignored.
+#[derive(Clone, Default)]
+pub struct Plugins {
+    host: Option<Arc<Host>>,
+}
+
+impl fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugins")
+            .field("loaded", &self.host.is_some())
+            .finish()
+    }
+}
+
+impl Plugins {
+    /// Directory the plugins are loaded from, `*.lua` in file name order
+    pub fn dir() -> PathBuf {
+        Config::dir().join("plugins")
+    }
+
+    /// Load every plugin, the ones that fail to load are returned as problems
+    pub fn load(config: &PluginConfig) -> (Self, Vec<String>) {
+        let mut files = match fs::read_dir(Self::dir()) {
+            Ok(entries) if config.enabled => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+                .collect::<Vec<_>>(),
+            _ => return (Self::default(), Vec::new()),
+        };
+        if files.is_empty() {
+            return (Self::default(), Vec::new());
+        }
+        files.sort();
+
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let deadline = Arc::new(Mutex::new(None));
+        let lua = match sandbox(config, &registry, &deadline) {
+            Ok(lua) => lua,
+            Err(e) => return (Self::default(), vec![format!("Failed to start Lua. {e}")]),
+        };
+
+        let mut problems = Vec::new();
+        for path in files {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            lock(&registry).loading = Some(name.clone());
+            let result = fs::read_to_string(&path)
+                .map_err(|e| eyre!(e))
+                .and_then(|source| {
+                    guarded(&deadline, || lua.load(&source).set_name(&name).exec())
+                        .map_err(|e| eyre!(e))
+                });
+            if let Err(e) = result {
+                problems.push(format!("Failed to load the plugin {name}. {e}"));
+            }
+        }
+
+        let (commands, bindings, filters) = {
+            let mut registry = lock(&registry);
+            registry.loading = None;
+            (
+                registry.commands.iter().map(|(c, _)| c.clone()).collect(),
+                registry.bindings.clone(),
+                registry
+                    .filters
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            )
+        };
+        let host = Host {
+            lua,
+            registry,
+            deadline,
+            commands,
+            bindings,
+            filters,
+            verdicts: Mutex::new(HashMap::new()),
+        };
+        (
+            Self {
+                host: Some(Arc::new(host)),
+            },
+            problems,
+        )
+    }
+
+    pub fn wants(&self, event: HookEvent) -> bool {
+        self.host.as_ref().is_some_and(|host| {
+            lock(&host.registry)
+                .handlers
+                .iter()
+                .any(|(e, _)| *e == event)
+        })
+    }
+
+    /// Call the handlers of the event with the task, if the event has one
+    pub fn emit(&self, event: HookEvent, task: Option<(&DownloadTask, &HookTask)>) {
+        let Some(host) = &self.host else {
+            return;
+        };
+        let handlers = lock(&host.registry)
+            .handlers
+            .iter()
+            .filter(|(e, _)| *e == event)
+            .map(|(_, r)| r.clone())
+            .collect::<Vec<_>>();
+        for handler in handlers {
+            let result = match task {
+                Some((task, hook)) => host
+                    .lua
+                    .to_value(&LuaTask::new(task, Some(hook)))
+                    .and_then(|task| host.call::<()>(&handler, task)),
+                None => host.call::<()>(&handler, Value::Nil),
+            };
+            if let Err(e) = result {
+                log::warn!(
+                    "The {} handler of {} failed. {e}",
+                    event.as_str(),
+                    handler.plugin
+                );
+            }
+        }
+    }
+
+    /// The URL after every plugin had a chance to rewrite it, a rewriter returning `nil`
+    /// keeps the URL as it is
+    pub fn rewrite_url(&self, url: &str) -> String {
+        let Some(host) = &self.host else {
+            return url.to_string();
+        };
+        let rewriters = lock(&host.registry).url_rewriters.clone();
+        let mut url = url.to_string();
+        for rewriter in rewriters {
+            match host.call::<Option<String>>(&rewriter, url.clone()) {
+                Ok(Some(rewritten)) if !rewritten.is_empty() => url = rewritten,
+                Ok(_) => {}
+                Err(e) => log::warn!("The URL rewriter of {} failed. {e}", rewriter.plugin),
+            }
+        }
+        url
+    }
+
+    /// The yt-dlp command line after every plugin had a chance to rewrite it, a rewrite that
+    /// `check_args` turns down is logged and left out
+    pub fn rewrite_args(&self, task: &DownloadTask, args: Vec<String>) -> Vec<String> {
+        let Some(host) = &self.host else {
+            return args;
+        };
+        let rewriters = lock(&host.registry).arg_rewriters.clone();
+        let mut args = args;
+        for rewriter in rewriters {
+            let result = host
+                .lua
+                .to_value(&args)
+                .and_then(|list| Ok((list, host.lua.to_value(&LuaTask::new(task, None))?)))
+                .and_then(|call_args| host.call::<Value>(&rewriter, call_args))
+                .and_then(|value| match value {
+                    Value::Nil => Ok(None),
+                    value => host.lua.from_value::<Vec<String>>(value).map(Some),
+                });
+            match result {
+                Ok(Some(rewritten)) if !rewritten.is_empty() => {
+                    match check_args(&args, &rewritten) {
+                        Ok(()) => args = rewritten,
+                        Err(e) => {
+                            log::warn!("Left out the argument rewrite of {}. {e}", rewriter.plugin)
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("The argument rewriter of {} failed. {e}", rewriter.plugin),
+            }
+        }
+        args
+    }
+
+    pub fn commands(&self) -> &[PluginCommand] {
+        self.host.as_ref().map_or(&[], |host| &host.commands)
+    }
+
+    pub fn bindings(&self) -> &[PluginBinding] {
+        self.host.as_ref().map_or(&[], |host| &host.bindings)
+    }
+
+    /// Names of the sidebar filters, in registration order
+    pub fn filters(&self) -> &[String] {
+        self.host.as_ref().map_or(&[], |host| &host.filters)
+    }
+
+    /// Run a plugin's command, returns the command lines it dispatched
+    pub fn run_command(&self, name: &str, args: &str) -> Result<Vec<String>> {
+        let host = self
+            .host
+            .as_ref()
+            .ok_or_else(|| eyre!("unknown command `{name}`"))?;
+        let command = lock(&host.registry)
+            .commands
+            .iter()
+            .find(|(c, _)| c.name == name)
+            .map(|(_, r)| r.clone())
+            .ok_or_else(|| eyre!("unknown command `{name}`"))?;
+
+        lock(&host.registry).dispatched.clear();
+        let result = host.call::<()>(&command, args.to_string());
+        let dispatched = std::mem::take(&mut lock(&host.registry).dispatched);
+        result.map_err(|e| eyre!("{name} of {} failed. {e}", command.plugin))?;
+        Ok(dispatched)
+    }
+
+    /// Whether the task belongs in the plugin's sidebar filter, `false` when the filter fails
+    pub fn matches(&self, filter: &str, task: &DownloadTask) -> bool {
+        let Some(host) = &self.host else {
+            return false;
+        };
+        let Some(registration) = lock(&host.registry)
+            .filters
+            .iter()
+            .find(|(name, _)| name == filter)
+            .map(|(_, r)| r.clone())
+        else {
+            return false;
+        };
+        let lua_task = LuaTask::new(task, None);
+        let mut hasher = DefaultHasher::new();
+        lua_task.hash(&mut hasher);
+        let version = hasher.finish();
+        let key = (filter.to_string(), task.id());
+        if let Some((seen, verdict)) = lock(&host.verdicts).get(&key)
+            && *seen == version
+        {
+            return *verdict;
+        }
+
+        let result = host
+            .lua
+            .to_value(&lua_task)
+            .and_then(|task| host.call::<bool>(&registration, task));
+        let verdict = result.unwrap_or_else(|e| {
+            log::warn!("The filter {filter} of {} failed. {e}", registration.plugin);
+            false
+        });
+        lock(&host.verdicts).insert(key, (version, verdict));
+        verdict
+    }
+}
+
+impl Host {
+    fn call<R: mlua::FromLuaMulti>(
+        &self,
+        registration: &Registration,
+        args: impl mlua::IntoLuaMulti,
+    ) -> mlua::Result<R> {
+        guarded(&self.deadline, || registration.function.call::<R>(args))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // a plugin that panicked mid-call leaves nothing half-written worth giving up over
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run `f` with the call timeout armed
+fn guarded<R>(
+    deadline: &Mutex<Option<Instant>>,
+    f: impl FnOnce() -> mlua::Result<R>,
+) -> mlua::Result<R> {
+    let outer = lock(deadline).replace(Instant::now() + CALL_TIMEOUT);
+    let result = f();
+    // a plugin function called from within another keeps the outer deadline
+    *lock(deadline) = outer;
+    result
+}
+
+/// A Lua state without `io`, `os`, `package` and `debug`, with the `cupcake` table
+fn sandbox(
+    config: &PluginConfig,
+    registry: &Arc<Mutex<Registry>>,
+    deadline: &Arc<Mutex<Option<Instant>>>,
+) -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH | StdLib::COROUTINE,
+        LuaOptions::default(),
+    )?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+
+    let hook_deadline = Arc::clone(deadline);
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(10_000),
+        move |_, _| match *lock(&hook_deadline) {
+            Some(deadline) if Instant::now() > deadline => Err(mlua::Error::RuntimeError(format!(
+                "stopped after {} seconds",
+                CALL_TIMEOUT.as_secs()
+            ))),
+            _ => Ok(VmState::Continue),
+        },
+    );
+
+    let globals = lua.globals();
+    // these read files
+    globals.set("dofile", Value::Nil)?;
+    globals.set("loadfile", Value::Nil)?;
+    // crafted bytecode breaks out of the sandbox, so only source is loaded or dumped
+    globals.get::<Table>("string")?.set("dump", Value::Nil)?;
+    lua.load(
+        r#"
+        local load = load
+        function _G.load(chunk, name, _, ...)
+            return load(chunk, name, "t", ...)
+        end
+        "#,
+    )
+    .exec()?;
+    globals.set(
+        "print",
+        lua.create_function(|_, values: Variadic<Value>| {
+            let line = values
+                .iter()
+                .map(|value| value.to_string().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("\t");
+            log::info!("{line}");
+            Ok(())
+        })?,
+    )?;
+
+    let cupcake = lua.create_table()?;
+    register(&lua, &cupcake, registry)?;
+    files(&lua, &cupcake, config)?;
+    globals.set("cupcake", cupcake)?;
+    Ok(lua)
+}
+
+/// The `cupcake` functions plugins register themselves with
+fn register(lua: &Lua, cupcake: &Table, registry: &Arc<Mutex<Registry>>) -> mlua::Result<()> {
+    // registering only works while loading, so the commands, keys and filters stay put
+    fn registration(registry: &Mutex<Registry>, function: Function) -> mlua::Result<Registration> {
+        let plugin = lock(registry).loading.clone().ok_or_else(|| {
+            mlua::Error::RuntimeError("plugins can only register while loading".to_string())
+        })?;
+        Ok(Registration { plugin, function })
+    }
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "on",
+        lua.create_function(move |lua, (event, function): (String, Function)| {
+            let event = lua
+                .from_value::<HookEvent>(Value::String(lua.create_string(&event)?))
+                .map_err(|_| {
+                    mlua::Error::RuntimeError(format!(
+                        "unknown event `{event}`, use added, started, completed, failed or \
+                         queue_empty"
+                    ))
+                })?;
+            let registration = registration(&r, function)?;
+            lock(&r).handlers.push((event, registration));
+            Ok(())
+        })?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "rewrite_url",
+        lua.create_function(move |_, function: Function| {
+            let registration = registration(&r, function)?;
+            lock(&r).url_rewriters.push(registration);
+            Ok(())
+        })?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "rewrite_args",
+        lua.create_function(move |_, function: Function| {
+            let registration = registration(&r, function)?;
+            lock(&r).arg_rewriters.push(registration);
+            Ok(())
+        })?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "command",
+        lua.create_function(move |_, (spec, function): (Table, Function)| {
+            let command = PluginCommand {
+                name: spec.get("name")?,
+                args: spec.get::<Option<String>>("args")?.unwrap_or_default(),
+                description: spec
+                    .get::<Option<String>>("description")?
+                    .unwrap_or_default(),
+            };
+            if command.name.is_empty() || command.name.contains(char::is_whitespace) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "invalid command name `{}`",
+                    command.name
+                )));
+            }
+            let registration = registration(&r, function)?;
+            let mut registry = lock(&r);
+            registry.commands.retain(|(c, _)| c.name != command.name);
+            registry.commands.push((command, registration));
+            Ok(())
+        })?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "bind",
+        lua.create_function(
+            move |_, (context, keys, command): (String, String, String)| {
+                if lock(&r).loading.is_none() {
+                    return Err(mlua::Error::RuntimeError(
+                        "plugins can only register while loading".to_string(),
+                    ));
+                }
+                lock(&r).bindings.push(PluginBinding {
+                    context,
+                    keys,
+                    command,
+                });
+                Ok(())
+            },
+        )?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "filter",
+        lua.create_function(move |_, (name, function): (String, Function)| {
+            let registration = registration(&r, function)?;
+            let mut registry = lock(&r);
+            registry.filters.retain(|(n, _)| n != &name);
+            registry.filters.push((name, registration));
+            Ok(())
+        })?,
+    )?;
+
+    let r = Arc::clone(registry);
+    cupcake.set(
+        "dispatch",
+        lua.create_function(move |_, line: String| {
+            lock(&r).dispatched.push(line);
+            Ok(())
+        })?,
+    )?;
+
+    cupcake.set(
+        "log",
+        lua.create_function(|_, message: String| {
+            log::info!("{message}");
+            Ok(())
+        })?,
+    )?;
+    cupcake.set(
+        "warn",
+        lua.create_function(|_, message: String| {
+            log::warn!("{message}");
+            Ok(())
+        })?,
+    )?;
+    Ok(())
+}
+
+/// `cupcake.read_file` and `cupcake.write_file`, limited to the allowed directories
+fn files(lua: &Lua, cupcake: &Table, config: &PluginConfig) -> mlua::Result<()> {
+    let allowed = config
+        .allow_paths
+        .iter()
+        .filter_map(|dir| expand_home(dir).canonicalize().ok())
+        .collect::<Arc<[PathBuf]>>();
+
+    let dirs = Arc::clone(&allowed);
+    cupcake.set(
+        "read_file",
+        lua.create_function(move |_, path: String| {
+            let path = permitted(&dirs, &path)?;
+            fs::read_to_string(path).map_err(mlua::Error::external)
+        })?,
+    )?;
+
+    let dirs = Arc::clone(&allowed);
+    cupcake.set(
+        "write_file",
+        lua.create_function(move |_, (path, contents): (String, String)| {
+            let path = permitted(&dirs, &path)?;
+            fs::write(path, contents).map_err(mlua::Error::external)
+        })?,
+    )?;
+    Ok(())
+}
+
+/// The path if it lies in one of the allowed directories, after resolving `..` and links
+fn permitted(allowed: &[PathBuf], path: &str) -> mlua::Result<PathBuf> {
+    let path = expand_home(Path::new(path));
+    // the file may not exist yet, the directory it goes in must
+    let resolved = match (path.canonicalize(), path.parent(), path.file_name()) {
+        (Ok(resolved), _, _) => Some(resolved),
+        (_, Some(dir), Some(name)) if name != ".." => {
+            dir.canonicalize().ok().map(|dir| dir.join(name))
+        }
+        _ => None,
+    };
+    match resolved {
+        Some(resolved) if allowed.iter().any(|dir| resolved.starts_with(dir)) => Ok(resolved),
+        _ => Err(mlua::Error::RuntimeError(format!(
+            "{} is outside of plugins.allow_paths",
+            path.display()
+        ))),
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// A rewrite keeps the program and the locked options the command already has with their
+/// values, such as its `-o`, and adds none of the `LOCKED_OPTIONS`
+fn check_args(args: &[String], rewritten: &[String]) -> Result<()> {
+    if rewritten.first() != args.first() {
+        bail!("it replaced the program `{}`", args[0]);
+    }
+    let mut rewritten = rewritten[1..].to_vec();
+    let mut at = 1;
+    while at < args.len() && args[at] != "--" {
+        if !locked(&args[at]) {
+            at += 1;
+            continue;
+        }
+        let end = if args[at].contains('=') {
+            at + 1
+        } else {
+            (at + 2).min(args.len())
+        };
+        let option = &args[at..end];
+        let Some(kept) = rewritten.windows(option.len()).position(|w| w == option) else {
+            bail!("it changed `{}`", option.join(" "));
+        };
+        rewritten.drain(kept..kept + option.len());
+        at = end;
+    }
+
+    match rewritten
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .find(|arg| locked(arg))
+    {
+        Some(arg) => bail!("`{arg}` isn't allowed"),
+        None => Ok(()),
+    }
+}
+
+fn locked(arg: &str) -> bool {
+    match arg.strip_prefix("--") {
+        Some(name) => {
+            let name = name.split('=').next().unwrap_or_default();
+            !name.is_empty()
+                && !UNLOCKED_OPTIONS.contains(&name)
+                && LOCKED_OPTIONS
+                    .iter()
+                    .any(|option| option[2..].starts_with(name))
+        }
+        // a group of short options, the value of the last one may follow directly
+        None => arg
+            .strip_prefix('-')
+            .is_some_and(|flags| flags.contains(LOCKED_SHORT_OPTIONS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn checks_rewritten_args() {
+        let command = args(&["yt-dlp", "--newline", "https://a.b/v", "-o", "/dl/v.mp4"]);
+        let check = |rewritten: &[&str]| check_args(&command, &args(rewritten));
+
+        assert!(
+            check(&[
+                "yt-dlp",
+                "--newline",
+                "https://a.b/v",
+                "-o",
+                "/dl/v.mp4",
+                "-x"
+            ])
+            .is_ok()
+        );
+        assert!(check(&["yt-dlp", "-f", "best", "-o", "/dl/v.mp4", "https://a.b/v"]).is_ok());
+        assert!(check(&["yt-dlp", "-o", "/dl/v.mp4", "--", "--exec"]).is_ok());
+        assert!(check(&["yt-dlp", "-o", "/dl/v.mp4", "--print", "filename"]).is_ok());
+
+        assert!(check(&["sh", "-c", "id", "-o", "/dl/v.mp4"]).is_err());
+        assert!(check(&["yt-dlp", "https://a.b/v", "-o", "/tmp/v.mp4"]).is_err());
+        assert!(check(&["yt-dlp", "-o", "/etc/x", "/dl/v.mp4"]).is_err());
+        for locked in [
+            "--exec=rm",
+            "--exec-before-download",
+            "--exe",
+            "--config-location",
+            "--batch-file",
+            "--output",
+            "-o",
+            "-xo",
+            "-a",
+            "-P",
+            "--update",
+            "--update-to",
+            "-U",
+            "--print-to-file",
+            "--download-archive",
+            "--cookies",
+            "--postprocessor-args",
+            "--ppa",
+            "--downloader-args",
+            "--external-downloader-args",
+            "--enable-file-urls",
+            "--cache-dir",
+        ] {
+            let rewritten = ["yt-dlp", "https://a.b/v", "-o", "/dl/v.mp4", locked, "x"];
+            assert!(check(&rewritten).is_err(), "{locked}");
+        }
+    }
+
+    #[test]
+    fn loads_only_source() {
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let lua = sandbox(
+            &PluginConfig::default(),
+            &registry,
+            &Arc::new(Mutex::new(None)),
+        )
+        .unwrap();
+        let eval = |code: &str| lua.load(code).eval::<Value>();
+
+        assert_eq!(
+            eval("return load('return 1 + 1')()").unwrap(),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            eval("return load('return x', 'chunk', 'b', { x = 3 })()").unwrap(),
+            Value::Integer(3)
+        );
+        // the signature binary chunks start with
+        let error = eval(r"return select(2, load('\27Lua', 'chunk', 'b'))").unwrap();
+        assert!(error.to_string().unwrap().contains("binary chunk"));
+        assert!(eval("return string.dump").unwrap().is_nil());
+        assert!(eval("return (''):dump()").is_err());
+    }
+
+    #[test]
+    fn keeps_the_locked_options_the_command_has() {
+        let command = args(&[
+            "yt-dlp",
+            "--cookies",
+            "/c.txt",
+            "https://a.b/v",
+            "-o",
+            "/dl/v.mp4",
+        ]);
+        let check = |rewritten: &[&str]| check_args(&command, &args(rewritten));
+
+        assert!(
+            check(&[
+                "yt-dlp",
+                "-x",
+                "--cookies",
+                "/c.txt",
+                "https://a.b/v",
+                "-o",
+                "/dl/v.mp4"
+            ])
+            .is_ok()
+        );
+        assert!(
+            check(&[
+                "yt-dlp",
+                "--cookies",
+                "/x.txt",
+                "https://a.b/v",
+                "-o",
+                "/dl/v.mp4"
+            ])
+            .is_err()
+        );
+        assert!(check(&["yt-dlp", "https://a.b/v", "-o", "/dl/v.mp4"]).is_err());
+    }
+}
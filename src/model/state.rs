@@ -1,22 +1,49 @@
-use std::sync::{Arc, atomic::AtomicBool};
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    sync::{Arc, atomic::AtomicBool},
+    time::{Duration, Instant},
+};
+
+use color_eyre::Result;
+use crossterm::event::Event;
 
 use ratatui::{
+    layout::{Position, Rect},
     style::{Modifier, Style},
-    text::Text,
+    text::{Line, Span, Text},
 };
 
 use tokio::sync::{RwLock, mpsc::UnboundedSender};
-use tui_input::Input;
+use tui_input::{Input, backend::crossterm::EventHandler};
 use tui_tree_widget::{TreeItem, TreeState};
 
 use crate::{
+    daemon::{
+        client::{self, Remote},
+        protocol::{Event as RemoteEvent, Request},
+    },
+    handoff::Listener,
     model::{
-        downloader::{DownloadManager, Downloader},
+        app_log,
+        backend::BackendKind,
+        config::{Config, OTHERS_CATEGORY},
+        counts::{STATUS_GROUPS, TaskCounts},
+        downloader::{DownloadManager, DownloadTask, Downloader, StoreLock},
+        filter::{SAVED_FILTERS_GROUP, SavedFilter},
+        plugins::{PLUGIN_FILTERS_GROUP, Plugins},
         theme::Theme,
     },
-    update::message::Message,
+    update::{
+        keymap::Keymap,
+        message::Message,
+        palette::{self, Entry},
+    },
 };
 
+/// How often the sidebar counts of filters on the time tasks were added are brought up to date
+const COUNTS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
 pub(crate) struct Model {
     pub(crate) message_tx: Option<UnboundedSender<Message>>,
     pub(crate) running: Arc<AtomicBool>,
@@ -24,11 +51,37 @@ pub(crate) struct Model {
     pub(crate) active_tab: Arc<RwLock<ActiveTab>>,
     pub(crate) downloader: Downloader,
     pub(crate) progress: f32,
-    pub(crate) menu_state: TreeState<&'static str>,
-    pub(crate) menu_items: Vec<TreeItem<'static, &'static str>>,
+    pub(crate) menu_state: TreeState<String>,
+    pub(crate) menu_items: Vec<TreeItem<'static, String>>,
     pub(crate) input_state: InputState,
     pub(crate) modal_prompt: String,
     pub(crate) theme: Theme,
+    pub(crate) config: Config,
+    /// Message dispatched when the confirm modal is accepted
+    pub(crate) pending_action: Option<Message>,
+    /// Keys of an unfinished key sequence, e.g. `3g`
+    pub(crate) pending_keys: String,
+    pub(crate) keymap: Keymap,
+    /// Lua plugins, their commands and key bindings are added to the palette and the keymap
+    pub(crate) plugins: Plugins,
+    /// Panel the help overlay or the command palette was opened from, and returns to
+    pub(crate) overlay_panel: ActivePanel,
+    pub(crate) help_scroll: u16,
+    pub(crate) palette: PaletteState,
+    pub(crate) details: DetailsState,
+    /// Size of the last drawn frame, mouse events are hit-tested against its layout
+    pub(crate) screen: Rect,
+    pub(crate) last_click: Option<(Instant, Position)>,
+    /// Whether the log panel is shown below the task list
+    pub(crate) show_log: bool,
+    /// Warnings and errors logged while the log panel was hidden
+    pub(crate) unseen_log: usize,
+    /// The daemon when attached to one, it runs the jobs and every change goes through it
+    pub(crate) remote: Option<Remote>,
+    /// Takes the URLs of `cupcake <url>` while this TUI is open
+    pub(crate) handoff: Option<Listener>,
+    /// Held while this TUI owns `tasks.json`, so the command line doesn't write it meanwhile
+    pub(crate) store_lock: Option<StoreLock>,
 }
 
 impl Default for Model {
@@ -45,78 +98,221 @@ impl Default for Model {
             input_state: InputState::default(),
             modal_prompt: String::new(),
             menu_items: Default::default(),
+            config: Config::default(),
+            pending_action: None,
+            pending_keys: String::new(),
+            keymap: Keymap::default(),
+            plugins: Plugins::default(),
+            overlay_panel: ActivePanel::default(),
+            help_scroll: 0,
+            palette: PaletteState::default(),
+            details: DetailsState::default(),
+            screen: Rect::default(),
+            last_click: None,
+            show_log: false,
+            unseen_log: 0,
+            remote: None,
+            handoff: None,
+            store_lock: None,
         }
     }
 }
 
-// TODO: clean this menu_items dogshit; refactor to use a more structured approach
 impl Model {
     pub fn new(message_tx: UnboundedSender<Message>) -> Self {
-        Self {
-            message_tx: Some(message_tx),
-            menu_items: vec![
-                TreeItem::new(
-                    "all",
-                    Text::from("ALL DOWNLOADS")
-                        .style(Style::default().add_modifier(Modifier::BOLD)),
-                    vec![
-                        TreeItem::new_leaf("all-music", "󰎆 Music"),
-                        TreeItem::new_leaf("all-vids", " Videos"),
-                        TreeItem::new_leaf("all-docs", "󰈙 Documents"),
-                        TreeItem::new_leaf("all-compressed", " Compressed"),
-                        TreeItem::new_leaf("all-programs", " Programs"),
-                        TreeItem::new_leaf("all-others", " Others"),
-                    ],
-                )
-                .expect("all item identifiers must be unique"),
+        let (config, config_problem) = Config::load_or_default();
+        let mut model = Self {
+            message_tx: Some(message_tx.clone()),
+            config,
+            ..Default::default()
+        };
+        let mut problems = Vec::from_iter(config_problem);
+        if let Some(problem) = app_log::init(model.config.log_level.as_deref(), message_tx.clone())
+        {
+            problems.push(problem);
+        }
+        let (plugins, plugin_problems) = Plugins::load(&model.config.plugins);
+        problems.extend(plugin_problems);
+        model.downloader.set_plugins(plugins.clone());
+        model.plugins = plugins;
+        model.downloader.recount(&model.config);
+        if let Some(theme) = model.config.theme.as_deref().and_then(Theme::by_name) {
+            model.theme = theme;
+        }
+        model
+            .downloader
+            .set_rate_limit(model.config.rate_limit.clone());
+        model
+            .downloader
+            .set_log_retention(model.config.task_logs.clone());
+        model.downloader.set_presets(model.config.presets.clone());
+        model.downloader.set_connections(model.config.connections);
+        model.downloader.set_aria2(model.config.aria2.clone());
+        model
+            .downloader
+            .set_hooks(model.config.hooks.clone(), model.config.webhooks.clone());
+
+        model.remote = Remote::attach(message_tx.clone());
+        if model.remote.is_none() && model.config.daemon {
+            match client::spawn_daemon() {
+                Ok(_) => model.remote = Remote::attach(message_tx.clone()),
+                Err(e) => problems.push(format!("Failed to start the daemon. {e}")),
+            }
+        }
+        if model.remote.is_none() {
+            match Downloader::lock_store() {
+                Ok(lock) => model.store_lock = Some(lock),
+                Err(e) => problems.push(format!(
+                    "Changes to the tasks may be lost when cupcake quits, {e}."
+                )),
+            }
+        }
+        model.handoff = Listener::bind(message_tx.clone());
+        // the daemon's tasks arrive with its snapshot
+        if model.remote.is_none() {
+            model.downloader.start_pending(message_tx.clone());
+        }
+        model.palette.history = PaletteState::load_history().unwrap_or_default();
+        model.menu_items = model.build_menu_items(&model.downloader.single.state.counts);
+        let tx = message_tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(COUNTS_REFRESH_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if tx.send(Message::RefreshCounts).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (mut keymap, mut key_problems) = Keymap::from_config(&model.config.keys);
+        key_problems.extend(keymap.bind_commands(model.plugins.bindings()));
+        model.keymap = keymap;
+        if !key_problems.is_empty() {
+            problems.push(format!(
+                "Problems with the key bindings:\n{}",
+                key_problems.join("\n")
+            ));
+        }
+        if !problems.is_empty() {
+            for problem in &problems {
+                log::warn!("{problem}");
+            }
+            model.modal_prompt = problems.join("\n\n");
+            model.active_panel = Arc::new(RwLock::new(ActivePanel::Modal(ModalType::Error)));
+        }
+
+        model
+    }
+
+    /// Generate the sidebar tree, one node per status group with the configured categories
+    /// as children, followed by the saved filters. Identifiers are the group name and the
+    /// category name, so a selected path looks like `["finished", "Videos"]`.
+    /// Every label carries the number of matching tasks from `counts`.
+    pub fn build_menu_items(&self, counts: &TaskCounts) -> Vec<TreeItem<'static, String>> {
+        let badge = |path: &[String]| {
+            Span::styled(
+                format!(" ({})", counts.get(path)),
+                Style::default().fg(self.theme.muted),
+            )
+        };
+
+        STATUS_GROUPS
+            .into_iter()
+            .zip(["ALL DOWNLOADS", "UNFINISHED", "FINISHED", "FAILED"])
+            .map(|(group, label)| {
+                let children = self
+                    .config
+                    .categories
+                    .iter()
+                    .map(|c| (c.name.clone(), c.label()))
+                    .chain(std::iter::once((
+                        OTHERS_CATEGORY.to_string(),
+                        format!(" {OTHERS_CATEGORY}"),
+                    )))
+                    .map(|(name, label)| {
+                        let path = [group.to_string(), name.clone()];
+                        TreeItem::new_leaf(name, Line::from(vec![label.into(), badge(&path)]))
+                    })
+                    .collect();
+
+                let path = [group.to_string()];
+                let style = match group {
+                    "failed" if counts.get(&path) > 0 => Style::default()
+                        .fg(self.theme.destructive)
+                        .add_modifier(Modifier::BOLD),
+                    _ => Style::default().add_modifier(Modifier::BOLD),
+                };
+
                 TreeItem::new(
-                    "unfinished",
-                    Text::from("UNFINISHED").style(Style::default().add_modifier(Modifier::BOLD)),
-                    vec![
-                        TreeItem::new_leaf("unfinished-music", "󰎆 Music"),
-                        TreeItem::new_leaf("unfinished-vids", " Videos"),
-                        TreeItem::new_leaf("unfinished-docs", "󰈙 Documents"),
-                        TreeItem::new_leaf("unfinished-compressed", " Compressed"),
-                        TreeItem::new_leaf("unfinished-programs", " Programs"),
-                        TreeItem::new_leaf("unfinished-others", " Others"),
-                    ],
+                    group.to_string(),
+                    Line::from(vec![Span::styled(label, style), badge(&path)]),
+                    children,
                 )
-                .expect("all item identifiers must be unique"),
+                .expect("all item identifiers must be unique")
+            })
+            .chain(std::iter::once(
                 TreeItem::new(
-                    "finished",
-                    Text::from("FINISHED").style(Style::default().add_modifier(Modifier::BOLD)),
-                    vec![
-                        TreeItem::new_leaf("finished-music", "󰎆 Music"),
-                        TreeItem::new_leaf("finished-vids", " Videos"),
-                        TreeItem::new_leaf("finished-docs", "󰈙 Documents"),
-                        TreeItem::new_leaf("finished-compressed", " Compressed"),
-                        TreeItem::new_leaf("finished-programs", " Programs"),
-                        TreeItem::new_leaf("finished-others", " Others"),
-                    ],
+                    SAVED_FILTERS_GROUP.to_string(),
+                    Text::from("SAVED FILTERS")
+                        .style(Style::default().add_modifier(Modifier::BOLD)),
+                    self.config
+                        .filters
+                        .iter()
+                        .map(|f| {
+                            let path = [SAVED_FILTERS_GROUP.to_string(), f.name.clone()];
+                            TreeItem::new_leaf(
+                                f.name.clone(),
+                                Line::from(vec![format!("󰈲 {}", f.name).into(), badge(&path)]),
+                            )
+                        })
+                        .collect(),
                 )
                 .expect("all item identifiers must be unique"),
+            ))
+            .chain((!self.plugins.filters().is_empty()).then(|| {
                 TreeItem::new(
-                    "failed",
-                    Text::from("FAILED").style(Style::default().add_modifier(Modifier::BOLD)),
-                    vec![
-                        TreeItem::new_leaf("failed-rec", "Recoverable"),
-                        TreeItem::new_leaf("failed-unr", "Unrecoverable"),
-                    ],
+                    PLUGIN_FILTERS_GROUP.to_string(),
+                    Text::from("PLUGIN FILTERS")
+                        .style(Style::default().add_modifier(Modifier::BOLD)),
+                    self.plugins
+                        .filters()
+                        .iter()
+                        .map(|name| {
+                            let path = [PLUGIN_FILTERS_GROUP.to_string(), name.clone()];
+                            TreeItem::new_leaf(
+                                name.clone(),
+                                Line::from(vec![format!("󰢱 {name}").into(), badge(&path)]),
+                            )
+                        })
+                        .collect(),
                 )
-                .expect("all item identifiers must be unique"),
-            ],
-            ..Default::default()
-        }
+                .expect("all item identifiers must be unique")
+            }))
+            .collect()
+    }
+
+    /// Rebuild the sidebar labels from the counts of the active tab
+    pub async fn refresh_menu_items(&mut self) {
+        let tab = *self.active_tab.read().await;
+        self.menu_items = self.build_menu_items(&self.downloader.manager(&tab).state.counts);
     }
 
     pub async fn previous_tab(&mut self) {
-        let mut tab = self.active_tab.write().await;
-        *tab = tab.previous();
+        {
+            let mut tab = self.active_tab.write().await;
+            *tab = tab.previous();
+        }
+        self.refresh_menu_items().await;
     }
 
     pub async fn next_tab(&mut self) {
-        let mut tab = self.active_tab.write().await;
-        *tab = tab.next();
+        {
+            let mut tab = self.active_tab.write().await;
+            *tab = tab.next();
+        }
+        self.refresh_menu_items().await;
     }
 
     pub async fn focus_content(&mut self) {
@@ -139,7 +335,46 @@ impl Model {
         *active_panel = ActivePanel::Modal(ModalType::DestinationInput);
     }
 
-    pub async fn show_confirm_modal(&mut self) {
+    pub async fn show_filter_input_modal(&mut self) {
+        self.input_state.focused = FocusedInput::FilterName;
+        let mut active_panel = self.active_panel.write().await;
+        *active_panel = ActivePanel::Modal(ModalType::FilterInput);
+    }
+
+    pub async fn show_prompt_modal(&mut self, kind: PromptKind) {
+        let tab = *self.active_tab.read().await;
+        let manager = self.downloader.manager(&tab);
+        self.input_state.prompt = match kind {
+            PromptKind::Destination => Input::new(
+                manager
+                    .highlighted()
+                    .map(|t| t.destination_dir().to_string_lossy().to_string())
+                    .unwrap_or_else(|| self.input_state.destination.value().to_string()),
+            ),
+            PromptKind::Tags => Input::default(),
+            PromptKind::Connections => Input::new(
+                manager
+                    .highlighted()
+                    .and_then(|t| t.connections)
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+            ),
+            PromptKind::Backend => Input::new(
+                manager
+                    .highlighted()
+                    .and_then(|t| t.backend)
+                    .map(|b| b.to_string())
+                    .unwrap_or_default(),
+            ),
+        };
+        let mut active_panel = self.active_panel.write().await;
+        *active_panel = ActivePanel::Modal(ModalType::Prompt(kind));
+    }
+
+    /// Ask for confirmation before dispatching `action`
+    pub async fn show_confirm_modal(&mut self, prompt: String, action: Message) {
+        self.modal_prompt = prompt;
+        self.pending_action = Some(action);
         let mut active_panel = self.active_panel.write().await;
         *active_panel = ActivePanel::Modal(ModalType::Confirm);
     }
@@ -156,9 +391,315 @@ impl Model {
         *active_panel = ActivePanel::Modal(ModalType::Error);
     }
 
+    /// Show an error that isn't the user's doing and keep it in the app log
+    pub async fn show_failure(&mut self, message: String) {
+        log::error!("{message}");
+        self.show_error_modal(message).await;
+    }
+
     pub async fn close_modal(&mut self) {
         let mut active_panel = self.active_panel.write().await;
-        *active_panel = ActivePanel::Content;
+        *active_panel = match *active_panel {
+            ActivePanel::Modal(ModalType::Help | ModalType::Palette | ModalType::Details) => {
+                self.overlay_panel
+            }
+            _ => ActivePanel::Content,
+        };
+    }
+
+    pub async fn show_palette(&mut self) {
+        let mut active_panel = self.active_panel.write().await;
+        if !matches!(*active_panel, ActivePanel::Modal(_)) {
+            self.overlay_panel = *active_panel;
+        }
+        self.palette.input = Input::default();
+        self.palette.selected = 0;
+        *active_panel = ActivePanel::Modal(ModalType::Palette);
+    }
+
+    pub fn handle_palette_input_event(&mut self, event: &Event) {
+        self.palette.input.handle_event(event);
+        self.palette.selected = 0;
+    }
+
+    /// Run the highlighted palette entry with the arguments typed after the command name,
+    /// or the typed command line as is when it starts with the full name of a command
+    pub async fn run_palette_command(&mut self) {
+        let query = self.palette.input.value().trim().to_string();
+        let (name, args) = query.split_once(' ').unwrap_or((&query, ""));
+
+        let entries = palette::entries(&query, &self.palette.history, self.plugins.commands());
+        let line = match entries.get(self.palette.selected) {
+            Some(Entry::History(line)) => line.to_string(),
+            Some(Entry::Command(command)) if command.name != name => {
+                format!("{} {}", command.name, args.trim())
+                    .trim()
+                    .to_string()
+            }
+            Some(Entry::Plugin(command)) if command.name != name => {
+                format!("{} {}", command.name, args.trim())
+                    .trim()
+                    .to_string()
+            }
+            _ => query.clone(),
+        };
+
+        self.close_modal().await;
+        if line.is_empty() {
+            return;
+        }
+
+        match palette::parse(&line, self.plugins.commands()) {
+            Ok(messages) => {
+                self.palette.remember(line);
+                self.dispatch(messages);
+            }
+            Err(e) => self.show_error_modal(e.to_string()).await,
+        }
+    }
+
+    /// Run the command line a plugin bound to keys
+    pub async fn run_bound_command(&mut self, index: usize) {
+        let Some(line) = self.keymap.command(index).map(str::to_string) else {
+            return;
+        };
+        match palette::parse(&line, self.plugins.commands()) {
+            Ok(messages) => self.dispatch(messages),
+            Err(e) => self.show_error_modal(e.to_string()).await,
+        }
+    }
+
+    /// Run a plugin's command, then the command lines it dispatched
+    pub async fn run_plugin_command(&mut self, name: &str, args: &str) {
+        let lines = match self.plugins.run_command(name, args) {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.show_error_modal(format!("The {name} command failed. {e}"))
+                    .await;
+                return;
+            }
+        };
+        for line in lines {
+            match palette::parse(&line, self.plugins.commands()) {
+                Ok(messages) => self.dispatch(messages),
+                Err(e) => log::warn!("The {name} command dispatched `{line}`: {e}"),
+            }
+        }
+    }
+
+    fn dispatch(&self, messages: Vec<Message>) {
+        if let Some(tx) = &self.message_tx {
+            for message in messages {
+                let _ = tx.send(message);
+            }
+        }
+    }
+
+    /// Mirror a change in the daemon the TUI is attached to
+    pub async fn apply_remote(&mut self, event: RemoteEvent) {
+        match event {
+            RemoteEvent::Snapshot { downloader } => {
+                self.downloader.replace_remote(
+                    *downloader,
+                    self.menu_state.selected(),
+                    &self.config,
+                );
+                self.refresh_menu_items().await;
+            }
+            RemoteEvent::Task { task } => {
+                let manager = match self.downloader.manager_of_mut(task.id()) {
+                    Some(manager) => manager,
+                    None => &mut self.downloader.single,
+                };
+                if manager.apply_remote(*task) {
+                    self.refresh_menu_items().await;
+                }
+            }
+            RemoteEvent::Output { id, line } => {
+                if let Some(manager) = self.downloader.manager_of_mut(id) {
+                    manager.push_output(id, line);
+                }
+            }
+            RemoteEvent::Error { message } => self.show_error_modal(message).await,
+            RemoteEvent::Done | RemoteEvent::Tasks { .. } => {}
+        }
+    }
+
+    /// Fall back to the stored tasks after the daemon went away. Its downloads aren't taken
+    /// over, as another attached TUI could do the same.
+    pub async fn detach(&mut self) {
+        if self.remote.take().is_none() {
+            return;
+        }
+        if let Ok(downloader) = Downloader::new().load() {
+            self.downloader
+                .replace_remote(downloader, self.menu_state.selected(), &self.config);
+            self.refresh_menu_items().await;
+        }
+        self.show_failure(
+            "Lost the connection to the daemon. Restart cupcake to continue the downloads."
+                .to_string(),
+        )
+        .await;
+    }
+
+    pub fn toggle_log(&mut self) {
+        self.show_log = !self.show_log;
+        self.unseen_log = 0;
+    }
+
+    /// Open the detail pane of the highlighted task
+    pub async fn show_details(&mut self) {
+        let Some(id) = self.active_manager().await.highlighted().map(|t| t.id()) else {
+            return;
+        };
+
+        let mut active_panel = self.active_panel.write().await;
+        if !matches!(*active_panel, ActivePanel::Modal(_)) {
+            self.overlay_panel = *active_panel;
+        }
+        if let Some(manager) = self.downloader.manager_of_mut(id) {
+            manager.load_output(id);
+        }
+        self.details.task = id;
+        self.details.scroll = None;
+        *active_panel = ActivePanel::Modal(ModalType::Details);
+    }
+
+    /// Open the add task flow with the source already filled in
+    pub async fn prefill_add_task(&mut self, source: String) {
+        self.input_state.source = Input::new(source);
+        self.switch_tab(ActiveTab::Single).await;
+        self.show_source_input_model().await;
+    }
+
+    pub async fn switch_tab(&mut self, tab: ActiveTab) {
+        *self.active_tab.write().await = tab;
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn set_theme(&mut self, name: String) {
+        let Some(theme) = Theme::by_name(&name) else {
+            return;
+        };
+        self.theme = theme;
+        self.config.theme = Some(name);
+        self.refresh_menu_items().await;
+        if let Err(e) = self.config.save() {
+            self.show_failure(format!("Failed to save the config. {e}"))
+                .await;
+        }
+    }
+
+    pub async fn set_rate_limit(&mut self, rate: Option<String>) {
+        match &self.remote {
+            Some(remote) => remote.send(Request::SetRateLimit { rate: rate.clone() }),
+            None => self.downloader.set_rate_limit(rate.clone()),
+        }
+        self.config.rate_limit = rate.clone();
+        if let Err(e) = self.config.save() {
+            self.show_failure(format!("Failed to save the config. {e}"))
+                .await;
+            return;
+        }
+
+        self.show_info_modal(match rate {
+            Some(rate) => format!("Downloads started from now on are limited to {rate}/s"),
+            None => "Downloads started from now on are not limited".to_string(),
+        })
+        .await;
+    }
+
+    pub async fn pause_all(&mut self) {
+        if let Some(remote) = &self.remote {
+            remote.send(Request::PauseAll);
+            return;
+        }
+        self.downloader.pause_all();
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn resume_all(&mut self) {
+        if let Some(remote) = &self.remote {
+            remote.send(Request::ResumeAll);
+            return;
+        }
+        let tx = self.message_tx.clone().unwrap();
+        self.downloader.resume_all(tx);
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn show_help(&mut self) {
+        let mut active_panel = self.active_panel.write().await;
+        if !matches!(*active_panel, ActivePanel::Modal(ModalType::Help)) {
+            self.overlay_panel = *active_panel;
+        }
+        self.help_scroll = 0;
+        *active_panel = ActivePanel::Modal(ModalType::Help);
+    }
+
+    /// Scroll the focused scrollable view by `lines`, negative scrolls up
+    pub async fn scroll(&mut self, lines: i32) {
+        match *self.active_panel.read().await {
+            ActivePanel::Modal(ModalType::Help) => {
+                self.help_scroll = self.help_scroll.saturating_add_signed(lines as i16);
+            }
+            ActivePanel::Modal(ModalType::Details) => {
+                let current = self.details.scroll.unwrap_or(self.details.max_scroll);
+                let scroll = current.saturating_add_signed(lines as i16);
+                // scrolling back to the bottom follows the output again
+                self.details.scroll = (scroll < self.details.max_scroll).then_some(scroll);
+            }
+            ActivePanel::Modal(ModalType::Palette) => {
+                let len = palette::entries(
+                    self.palette.input.value(),
+                    &self.palette.history,
+                    self.plugins.commands(),
+                )
+                .len();
+                if len > 0 {
+                    self.palette.selected =
+                        (self.palette.selected as i32 + lines).rem_euclid(len as i32) as usize;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Scroll the focused scrollable view to the top, or to the bottom
+    pub async fn scroll_to_end(&mut self, bottom: bool) {
+        match *self.active_panel.read().await {
+            ActivePanel::Modal(ModalType::Help) => {
+                // clamped to the content height while rendering
+                self.help_scroll = if bottom { u16::MAX } else { 0 };
+            }
+            ActivePanel::Modal(ModalType::Details) => {
+                self.details.scroll = if bottom { None } else { Some(0) };
+            }
+            ActivePanel::Modal(ModalType::Palette) => {
+                let len = palette::entries(
+                    self.palette.input.value(),
+                    &self.palette.history,
+                    self.plugins.commands(),
+                )
+                .len();
+                self.palette.selected = if bottom { len.saturating_sub(1) } else { 0 };
+            }
+            _ => {}
+        }
+    }
+
+    pub async fn confirm(&mut self) {
+        self.close_modal().await;
+        if let (Some(action), Some(tx)) = (self.pending_action.take(), &self.message_tx) {
+            let _ = tx.send(action);
+        }
+    }
+
+    pub async fn refresh_counts(&mut self) {
+        if self.downloader.refresh_counts() {
+            self.refresh_menu_items().await;
+        }
     }
 
     pub fn undate_progress_single(&mut self) {
@@ -181,7 +722,7 @@ impl Model {
             }
 
             Err(e) => {
-                self.show_error_modal(format!("Failed to extract metadata. {e}"))
+                self.show_failure(format!("Failed to extract metadata. {e}"))
                     .await;
                 // Reset input state after adding a task
                 self.input_state = InputState::new();
@@ -204,18 +745,319 @@ impl Model {
             return;
         }
 
-        let tx = self.message_tx.clone().unwrap();
-        self.downloader.single.start_download(
+        let task = DownloadTask::new(
             self.input_state.source.value(),
             self.input_state.destination.value().into(),
             self.input_state.name.value().to_string(),
-            tx,
         );
+        self.start_task(task).await;
 
         // Reset input state after adding a task
         self.input_state = InputState::new();
         self.focus_content().await;
     }
+
+    /// Add a task for the source to the default directory without asking, named by yt-dlp
+    pub async fn enqueue_url(&mut self, source: String) {
+        let name = match DownloadManager::extract_filename(&source) {
+            Ok(name) if !name.is_empty() => name,
+            Ok(_) => {
+                self.show_failure(format!(
+                    "Failed to extract metadata of {source}. No file name"
+                ))
+                .await;
+                return;
+            }
+            Err(e) => {
+                self.show_failure(format!("Failed to extract metadata of {source}. {e}"))
+                    .await;
+                return;
+            }
+        };
+
+        let destination = InputState::default().destination.value().into();
+        self.start_task(DownloadTask::new(&source, destination, name))
+            .await;
+    }
+
+    /// Start a new task, with the default preset unless it has one, in the daemon when attached
+    pub async fn start_task(&mut self, mut task: DownloadTask) {
+        task.preset = task.preset.or(self.config.default_preset.clone());
+        match &self.remote {
+            Some(remote) => remote.send(Request::Add {
+                task: Box::new(task),
+            }),
+            None => {
+                let tx = self.message_tx.clone().unwrap();
+                self.downloader.single.start_download(task, tx);
+                self.refresh_menu_items().await;
+            }
+        }
+    }
+
+    async fn active_manager(&mut self) -> &mut DownloadManager {
+        let tab = *self.active_tab.read().await;
+        self.downloader.manager_mut(&tab)
+    }
+
+    pub async fn select_first_row(&mut self) {
+        self.active_manager().await.first_row();
+    }
+
+    pub async fn select_last_row(&mut self) {
+        self.active_manager().await.last_row();
+    }
+
+    pub async fn toggle_selection(&mut self) {
+        self.active_manager().await.toggle_selection();
+    }
+
+    pub async fn toggle_visual_mode(&mut self) {
+        self.active_manager().await.toggle_visual_mode();
+    }
+
+    pub async fn clear_selection(&mut self) {
+        self.active_manager().await.clear_selection();
+    }
+
+    pub async fn pause_selected(&mut self) {
+        let remote = self.remote.clone();
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        match remote {
+            Some(remote) => remote.send(Request::Pause { ids }),
+            None => manager.pause(&ids),
+        }
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn resume_selected(&mut self) {
+        let tx = self.message_tx.clone().unwrap();
+        let remote = self.remote.clone();
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        match remote {
+            Some(remote) => remote.send(Request::Resume { ids }),
+            None => manager.resume(&ids, tx),
+        }
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn retry_selected(&mut self) {
+        let tx = self.message_tx.clone().unwrap();
+        let remote = self.remote.clone();
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        match remote {
+            Some(remote) => remote.send(Request::Retry { ids }),
+            None => manager.retry(&ids, tx),
+        }
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn confirm_remove_selected(&mut self) {
+        let count = self.active_manager().await.targets().len();
+        if count == 0 {
+            return;
+        }
+        self.show_confirm_modal(
+            format!(
+                "Remove {count} task(s)? Downloaded files are kept.\n\n[Enter] remove  [Esc] cancel"
+            ),
+            Message::RemoveSelected,
+        )
+        .await;
+    }
+
+    pub async fn remove_selected(&mut self) {
+        let remote = self.remote.clone();
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        match remote {
+            Some(remote) => remote.send(Request::Remove { ids }),
+            None => manager.remove(&ids),
+        }
+        self.refresh_menu_items().await;
+    }
+
+    pub async fn export_selected(&mut self) {
+        let directory = PathBuf::from(self.input_state.destination.value());
+        self.export_selected_to(directory).await;
+    }
+
+    pub async fn export_selected_to(&mut self, directory: PathBuf) {
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        if ids.is_empty() {
+            return;
+        }
+        match manager.export(&ids, directory) {
+            Ok(path) => {
+                self.show_info_modal(format!(
+                    "Exported {} task(s) to {}",
+                    ids.len(),
+                    path.display()
+                ))
+                .await
+            }
+            Err(e) => {
+                self.show_failure(format!("Failed to export tasks. {e}"))
+                    .await
+            }
+        }
+    }
+
+    /// Apply the value of the prompt modal to the selected tasks
+    pub async fn submit_prompt(&mut self, kind: PromptKind) {
+        let value = self.input_state.prompt.value().trim().to_string();
+        self.input_state.prompt = Input::default();
+        self.close_modal().await;
+        self.apply_prompt(kind, value).await;
+    }
+
+    /// Apply a destination or tags to the selected tasks
+    pub async fn apply_prompt(&mut self, kind: PromptKind, value: String) {
+        let remote = self.remote.clone();
+        let manager = self.active_manager().await;
+        let ids = manager.targets();
+        match kind {
+            PromptKind::Destination if !value.is_empty() => {
+                let moved = match remote {
+                    // the daemon reports its failures itself
+                    Some(remote) => {
+                        remote.send(Request::ChangeDestination {
+                            ids: ids.clone(),
+                            destination: value.into(),
+                        });
+                        Ok(ids.len())
+                    }
+                    None => manager.change_destination(&ids, value.into()),
+                };
+                match moved {
+                    Ok(moved) if moved < ids.len() => {
+                        self.show_info_modal(format!(
+                            "Moved {moved} task(s), {} running task(s) were skipped",
+                            ids.len() - moved
+                        ))
+                        .await
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.show_failure(format!("Failed to move tasks. {e}"))
+                            .await
+                    }
+                }
+            }
+            PromptKind::Tags => {
+                let tags = value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect::<Vec<_>>();
+                match remote {
+                    Some(remote) => remote.send(Request::Tag { ids, tags }),
+                    None => manager.tag(&ids, &tags),
+                }
+            }
+            PromptKind::Connections => {
+                let connections = match value.as_str() {
+                    "" | "default" => None,
+                    value => match value.parse::<u32>() {
+                        Ok(n @ 1..=32) => Some(n),
+                        _ => {
+                            self.show_error_modal(format!(
+                                "Invalid number of connections `{value}`, use 1 to 32"
+                            ))
+                            .await;
+                            return;
+                        }
+                    },
+                };
+                match remote {
+                    Some(remote) => remote.send(Request::SetConnections { ids, connections }),
+                    None => manager.set_task_connections(&ids, connections),
+                }
+            }
+            PromptKind::Backend => {
+                let backend = match value.as_str() {
+                    "" | "auto" => None,
+                    value => match value.parse::<BackendKind>() {
+                        Ok(backend) => Some(backend),
+                        Err(_) => {
+                            self.show_error_modal(format!(
+                                "Unknown backend `{value}`, use yt-dlp, http, aria2 or auto"
+                            ))
+                            .await;
+                            return;
+                        }
+                    },
+                };
+                match remote {
+                    Some(remote) => remote.send(Request::SetBackend { ids, backend }),
+                    None => manager.set_task_backend(&ids, backend),
+                }
+            }
+            _ => {}
+        }
+        self.refresh_menu_items().await;
+    }
+
+    /// Validate the filter expression from the input state and store it as a sidebar entry,
+    /// replacing any saved filter with the same name.
+    pub async fn save_filter(&mut self) {
+        let filter = SavedFilter {
+            name: self.input_state.filter_name.value().trim().to_string(),
+            query: self.input_state.filter_query.value().trim().to_string(),
+        };
+
+        if filter.name.is_empty() {
+            self.show_error_modal("Filter name cannot be empty".to_string())
+                .await;
+            return;
+        }
+
+        if let Err(e) = filter.parse() {
+            self.show_error_modal(format!("Invalid filter expression. {e}"))
+                .await;
+            return;
+        }
+
+        match self
+            .config
+            .filters
+            .iter_mut()
+            .find(|f| f.name == filter.name)
+        {
+            Some(existing) => *existing = filter,
+            None => self.config.filters.push(filter),
+        }
+        self.input_state = InputState::new();
+        self.persist_filters().await;
+    }
+
+    /// Remove the saved filter currently selected in the sidebar, if any
+    pub async fn delete_selected_filter(&mut self) {
+        let selected = self.menu_state.selected().to_owned();
+        if let [group, name] = selected.as_slice()
+            && group == SAVED_FILTERS_GROUP
+        {
+            self.config.filters.retain(|f| &f.name != name);
+            self.menu_state.select(vec![group.clone()]);
+            self.persist_filters().await;
+        }
+    }
+
+    async fn persist_filters(&mut self) {
+        self.downloader.recount(&self.config);
+        self.refresh_menu_items().await;
+        match self.config.save() {
+            Ok(_) => self.focus_menu().await,
+            Err(e) => {
+                self.show_failure(format!("Failed to save config. {e}"))
+                    .await
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Default, Debug)]
@@ -233,7 +1075,21 @@ pub(crate) enum ModalType {
     Error,
     SourceInput,
     DestinationInput,
+    FilterInput,
+    Prompt(PromptKind),
     Confirm,
+    Help,
+    Palette,
+    Details,
+}
+
+/// What the single line prompt modal asks for, applied to the selected tasks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PromptKind {
+    Destination,
+    Tags,
+    Connections,
+    Backend,
 }
 
 #[derive(Default, Clone, Copy, Debug)]
@@ -259,6 +1115,17 @@ impl std::fmt::Display for ActiveTab {
 }
 
 impl ActiveTab {
+    pub(crate) fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(ActiveTab::Single),
+            1 => Some(ActiveTab::Batch),
+            2 => Some(ActiveTab::Playlist),
+            3 => Some(ActiveTab::Settings),
+            4 => Some(ActiveTab::About),
+            _ => None,
+        }
+    }
+
     /// Get the previous tab, if there is no previous tab return the current tab.
     pub(crate) fn previous(self) -> Self {
         let current_index = self as usize;
@@ -293,6 +1160,54 @@ pub enum FocusedInput {
     #[default]
     Name,
     Destination,
+    FilterName,
+    FilterQuery,
+}
+
+/// The task shown in the detail pane and how far its output is scrolled
+#[derive(Debug, Clone, Default)]
+pub struct DetailsState {
+    pub(crate) task: u64,
+    /// First visible output line, `None` follows the end of the output
+    pub(crate) scroll: Option<u16>,
+    /// Set while rendering, the scroll position showing the last line
+    pub(crate) max_scroll: u16,
+}
+
+/// Input and history of the command palette
+#[derive(Debug, Clone, Default)]
+pub struct PaletteState {
+    pub(crate) input: Input,
+    /// Index into the entries matching the input
+    pub(crate) selected: usize,
+    /// Recently run command lines, most recent first
+    pub(crate) history: Vec<String>,
+}
+
+impl PaletteState {
+    const HISTORY_SIZE: usize = 50;
+
+    fn history_path() -> PathBuf {
+        Downloader::dir().join("history.json")
+    }
+
+    pub fn load_history() -> Result<Vec<String>> {
+        let file = File::open(Self::history_path())?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Move the command line to the front of the history and persist it
+    pub fn remember(&mut self, line: String) {
+        self.history.retain(|l| l != &line);
+        self.history.insert(0, line);
+        self.history.truncate(Self::HISTORY_SIZE);
+
+        // losing the history is not worth interrupting the user for
+        let _ = fs::create_dir_all(Downloader::dir()).and_then(|_| {
+            let file = File::create(Self::history_path())?;
+            serde_json::to_writer(file, &self.history).map_err(std::io::Error::from)
+        });
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -300,6 +1215,9 @@ pub struct InputState {
     pub(crate) source: Input,
     pub(crate) destination: Input,
     pub(crate) name: Input,
+    pub(crate) filter_name: Input,
+    pub(crate) filter_query: Input,
+    pub(crate) prompt: Input,
     pub(crate) focused: FocusedInput,
 }
 
@@ -315,6 +1233,9 @@ impl Default for InputState {
             destination: Input::new(download_dir),
             source: Input::default(),
             name: Input::default(),
+            filter_name: Input::default(),
+            filter_query: Input::default(),
+            prompt: Input::default(),
             focused: FocusedInput::default(),
         }
     }
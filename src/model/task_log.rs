@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    downloader::{Downloader, OutputLine, format_timestamp, unix_now},
+    filter::parse_size,
+};
+
+/// Prefix marking the lines a job printed to stderr
+const STDERR_PREFIX: &str = "[stderr] ";
+
+/// Ids of the tasks whose logs are open, pruning leaves them be
+static OPEN: Mutex<BTreeSet<u64>> = Mutex::new(BTreeSet::new());
+
+/// How many task logs are kept and how large they may get, the oldest logs go first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogRetention {
+    /// Number of task logs to keep
+    pub max_files: usize,
+    /// Total size of all task logs, such as `200M`
+    pub max_total_size: String,
+    /// Size a single log grows to before it is rotated to `<id>.log.1`, such as `5M`
+    pub max_file_size: String,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            max_files: 200,
+            max_total_size: "200M".to_string(),
+            max_file_size: "5M".to_string(),
+        }
+    }
+}
+
+impl LogRetention {
+    fn max_total_bytes(&self) -> u64 {
+        parse_size(&self.max_total_size).unwrap_or(u64::MAX)
+    }
+
+    fn max_file_bytes(&self) -> u64 {
+        parse_size(&self.max_file_size).unwrap_or(u64::MAX)
+    }
+}
+
+/// Directory the task logs are written to
+pub fn dir() -> PathBuf {
+    Downloader::dir().join("logs")
+}
+
+/// Path of the log of the task with the given id
+pub fn path(id: u64) -> PathBuf {
+    dir().join(format!("{id}.log"))
+}
+
+/// Everything a task's jobs printed, appended to `logs/<id>.log` across runs and restarts.
+///
+/// Once the log grows past `max_file_size` it is moved to `<id>.log.1`, replacing the
+/// previous rotation, and a fresh log is started.
+pub struct TaskLog {
+    id: u64,
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl TaskLog {
+    /// Open the task's log for appending and write a header for the new run
+    pub fn open(id: u64, command: &str, retention: &LogRetention) -> Result<Self> {
+        fs::create_dir_all(dir())?;
+        let path = path(id);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        OPEN.lock().unwrap().insert(id);
+        let mut log = Self {
+            id,
+            path,
+            file,
+            size,
+            max_size: retention.max_file_bytes(),
+        };
+        log.note(command)?;
+        Ok(log)
+    }
+
+    /// Write a timestamped line of our own, such as the command or how the run ended
+    pub fn note(&mut self, text: &str) -> Result<()> {
+        self.write(&format!("# {} {text}", format_timestamp(unix_now())))
+    }
+
+    pub fn push(&mut self, line: &OutputLine) -> Result<()> {
+        if line.stderr {
+            self.write(&format!("{STDERR_PREFIX}{}", line.text))
+        } else {
+            self.write(&line.text)
+        }
+    }
+
+    fn write(&mut self, line: &str) -> Result<()> {
+        if self.size > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        fs::rename(&self.path, rotated)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Drop for TaskLog {
+    fn drop(&mut self) {
+        OPEN.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// The last `limit` lines of the task's log, to show the output of jobs that ran before a restart
+pub fn tail(path: &Path, limit: usize) -> Result<VecDeque<OutputLine>> {
+    let mut lines = VecDeque::with_capacity(limit);
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if lines.len() == limit {
+            lines.pop_front();
+        }
+        lines.push_back(match line.strip_prefix(STDERR_PREFIX) {
+            Some(text) => OutputLine {
+                stderr: true,
+                text: text.to_string(),
+            },
+            None => OutputLine {
+                stderr: false,
+                text: line,
+            },
+        });
+    }
+    Ok(lines)
+}
+
+/// Delete the oldest logs until at most `max_files` remain and they fit in `max_total_size`.
+/// Rotated logs count towards the size and go together with their task's log. The logs of
+/// running tasks still count but are never deleted.
+pub fn prune(retention: &LogRetention) -> Result<()> {
+    let open = OPEN.lock().unwrap().clone();
+    prune_in(&dir(), retention, &open)
+}
+
+fn prune_in(dir: &Path, retention: &LogRetention, open: &BTreeSet<u64>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    // last modified, total size and files of every task's log
+    let mut logs: HashMap<String, (SystemTime, u64, Vec<PathBuf>)> = HashMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(id) = name
+            .strip_suffix(".log")
+            .or_else(|| name.strip_suffix(".log.1"))
+        else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+        let (last, size, files) =
+            logs.entry(id.to_string())
+                .or_insert((SystemTime::UNIX_EPOCH, 0, Vec::new()));
+        *last = (*last).max(modified);
+        *size += metadata.len();
+        files.push(path);
+    }
+
+    let mut logs = logs.into_iter().collect::<Vec<_>>();
+    logs.sort_by(|(_, (a, _, _)), (_, (b, _, _))| b.cmp(a));
+
+    let max_total = retention.max_total_bytes();
+    let mut total = 0;
+    for (i, (id, (_, size, files))) in logs.into_iter().enumerate() {
+        total += size;
+        if i >= retention.max_files || total > max_total {
+            if id.parse().is_ok_and(|id| open.contains(&id)) {
+                continue;
+            }
+            for file in files {
+                fs::remove_file(file)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn prunes_the_oldest_logs_first() {
+        let dir = std::env::temp_dir().join(format!("cupcake-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        // task 1 is the oldest, its rotated log was the last written
+        let logs = [("1.log", 10), ("2.log", 20), ("3.log", 30), ("1.log.1", 25)];
+        for (name, age) in logs {
+            let mut file = File::create(dir.join(name)).unwrap();
+            file.write_all(&[0; 10]).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(1000 - age);
+            file.set_modified(modified).unwrap();
+        }
+        let remaining = || {
+            let mut names = fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        let retention = |max_files, max_total_size: &str| LogRetention {
+            max_files,
+            max_total_size: max_total_size.to_string(),
+            ..LogRetention::default()
+        };
+
+        // a task's logs go together, by the last one written
+        prune_in(&dir, &retention(2, "1M"), &BTreeSet::new()).unwrap();
+        assert_eq!(remaining(), ["1.log", "1.log.1", "3.log"]);
+        // the open log of a running task stays
+        prune_in(&dir, &retention(1, "1M"), &BTreeSet::from([1])).unwrap();
+        assert_eq!(remaining(), ["1.log", "1.log.1", "3.log"]);
+        prune_in(&dir, &retention(2, "25"), &BTreeSet::new()).unwrap();
+        assert_eq!(remaining(), ["3.log"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -32,6 +32,28 @@ impl Default for Theme {
 }
 
 impl Theme {
+    /// Names of the built-in themes, as used in the config and the command palette
+    pub const NAMES: [&str; 6] = [
+        "default",
+        "catppuccin_mocha",
+        "catppuccin_latte",
+        "dracula",
+        "rose_pine",
+        "nord",
+    ];
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.replace('-', "_").as_str() {
+            "default" => Some(Self::default()),
+            "catppuccin_mocha" | "mocha" => Some(Self::catppuccin_mocha()),
+            "catppuccin_latte" | "latte" => Some(Self::catppuccin_latte()),
+            "dracula" => Some(Self::dracula()),
+            "rose_pine" => Some(Self::rose_pine()),
+            "nord" => Some(Self::nord()),
+            _ => None,
+        }
+    }
+
     /// Sets the background to transparent
     pub fn transparent(mut self) -> Self {
         self.background = None;
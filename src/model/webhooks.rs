@@ -0,0 +1,228 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use color_eyre::{Result, eyre::bail};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::model::hooks::HookEvent;
+
+/// Header carrying the signature of the body, `sha256=<hex>`
+const SIGNATURE_HEADER: &str = "X-Cupcake-Signature";
+
+/// A URL the task events are POSTed to as JSON, e.g.
+/// `{"url": "http://localhost:8080/cupcake", "secret": "…"}`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events it is called for, `completed` and `failed` by default
+    pub events: Vec<HookEvent>,
+    /// Extra headers, such as `Authorization`
+    pub headers: BTreeMap<String, String>,
+    /// Key the body is signed with as HMAC-SHA256 in `X-Cupcake-Signature`, unsigned if empty
+    pub secret: String,
+    /// How many more times a failed delivery is tried, waiting longer each time
+    pub retries: u32,
+    /// Seconds a delivery may take
+    pub timeout: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: vec![HookEvent::Completed, HookEvent::Failed],
+            headers: BTreeMap::new(),
+            secret: String::new(),
+            retries: 3,
+            timeout: 10,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, event: HookEvent) -> bool {
+        !self.url.is_empty() && self.events.contains(&event)
+    }
+
+    /// POST the body, retrying on network errors, 429 and 5xx answers
+    pub async fn deliver(&self, client: &reqwest::Client, event: HookEvent, body: Vec<u8>) {
+        let mut delay = Duration::from_secs(1);
+        for attempt in 0..=self.retries {
+            match self.post(client, event, body.clone()).await {
+                Ok(()) => return,
+                Err(e) if attempt == self.retries => {
+                    log::warn!(
+                        "Gave up on the webhook {} after {} attempts. {e}",
+                        self.url,
+                        attempt + 1
+                    );
+                }
+                Err(e) => {
+                    log::info!("The webhook {} failed, trying again. {e}", self.url);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    async fn post(&self, client: &reqwest::Client, event: HookEvent, body: Vec<u8>) -> Result<()> {
+        let mut request = client
+            .post(&self.url)
+            .timeout(Duration::from_secs(self.timeout))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Cupcake-Event", event.as_str());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if !self.secret.is_empty() {
+            request = request.header(SIGNATURE_HEADER, sign(&self.secret, &body));
+        }
+
+        let response = request.body(body).send().await?;
+        let status = response.status();
+        if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+            // the request itself is wrong, sending it again won't help
+            log::warn!("The webhook {} answered {status}", self.url);
+            return Ok(());
+        }
+        if !status.is_success() {
+            bail!("it answered {status}");
+        }
+        Ok(())
+    }
+}
+
+/// `sha256=<hex>` of the HMAC-SHA256 of the body
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    use super::*;
+
+    /// Answers the requests with the statuses in turn, and never once they run out.
+    /// Hands back the URL and the head and body of every request it gets.
+    async fn serve(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            // kept open so the unanswered requests time out rather than fail
+            let mut waiting = Vec::new();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = tx.send(read_request(&mut stream).await);
+                match statuses.next() {
+                    Some(status) => {
+                        let response = format!(
+                            "HTTP/1.1 {status} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                    }
+                    None => waiting.push(stream),
+                }
+            }
+        });
+        (url, rx)
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> (String, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut buf = [0; 1024];
+        let end = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            data.extend_from_slice(&buf[..n]);
+            if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&data[..end]).to_lowercase();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map_or(0, |length| length.trim().parse().unwrap());
+        while data.len() < end + length {
+            let n = stream.read(&mut buf).await.unwrap();
+            data.extend_from_slice(&buf[..n]);
+        }
+        (head, data[end..].to_vec())
+    }
+
+    fn webhook(url: String) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer t".to_string())]),
+            secret: "key".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_bodies_and_retries_server_errors() {
+        let (url, mut requests) = serve(vec![503, 200]).await;
+        let body = b"The quick brown fox jumps over the lazy dog".to_vec();
+        webhook(url)
+            .deliver(&reqwest::Client::new(), HookEvent::Completed, body.clone())
+            .await;
+
+        for _ in 0..2 {
+            let (head, sent) = requests.try_recv().unwrap();
+            assert!(head.starts_with("post /hook "));
+            assert!(head.contains("x-cupcake-event: completed\r\n"));
+            assert!(head.contains("authorization: bearer t\r\n"));
+            // the HMAC-SHA256 test vector for this key and message
+            assert!(head.contains(
+                "x-cupcake-signature: sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8\r\n"
+            ));
+            assert_eq!(sent, body);
+        }
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_client_errors() {
+        let (url, mut requests) = serve(vec![400, 200]).await;
+        webhook(url)
+            .deliver(&reqwest::Client::new(), HookEvent::Failed, b"{}".to_vec())
+            .await;
+
+        assert!(requests.try_recv().is_ok());
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn times_out_and_stops_after_the_retries() {
+        let (url, mut requests) = serve(vec![]).await;
+        let webhook = WebhookConfig {
+            timeout: 1,
+            retries: 1,
+            ..webhook(url)
+        };
+        let started = Instant::now();
+        webhook
+            .deliver(&reqwest::Client::new(), HookEvent::Failed, b"{}".to_vec())
+            .await;
+
+        // two timeouts and the wait in between
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(3) && elapsed < Duration::from_secs(6));
+        assert!(requests.try_recv().is_ok());
+        assert!(requests.try_recv().is_ok());
+        assert!(requests.try_recv().is_err());
+    }
+}
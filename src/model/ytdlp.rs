@@ -0,0 +1,157 @@
+use std::process::{Command, Stdio};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use tokio::io::AsyncBufReadExt;
+
+use crate::model::{
+    backend::{Backend, Job, JobOptions, Metadata},
+    downloader::{DownloadStatus, DownloadTask, OutputLine},
+};
+
+/// Downloads with yt-dlp, which knows how to get the media out of most sites
+pub(crate) struct YtDlp;
+
+impl Backend for YtDlp {
+    /// yt-dlp is what everything no other backend handles falls back to
+    fn handles(&self, _url: &str) -> bool {
+        false
+    }
+
+    fn probe(&self, url: &str) -> Result<Metadata> {
+        let output = Command::new("yt-dlp")
+            .arg("--no-warnings")
+            .arg("--print")
+            .arg("filename")
+            .arg(url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| eyre!("failed to start yt-dlp. {e}"))?
+            .wait_with_output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr
+                .lines()
+                .rfind(|line| line.starts_with("ERROR:"))
+                .or(stderr.lines().last())
+                .unwrap_or("yt-dlp failed");
+            bail!("{}", message.trim_start_matches("ERROR:").trim());
+        }
+        Ok(Metadata {
+            name: String::from_utf8(output.stdout)?,
+            mime_type: None,
+        })
+    }
+
+    fn command(&self, task: &DownloadTask, options: &JobOptions) -> Vec<String> {
+        let mut command = vec!["yt-dlp".to_string()];
+        if let Some(rate) = &options.rate_limit {
+            command.extend(["--limit-rate".to_string(), rate.clone()]);
+        }
+        if let Some(args) = &options.preset {
+            command.extend(args.iter().cloned());
+        }
+        if let Some(referer) = &task.referer {
+            command.extend(["--referer".to_string(), referer.clone()]);
+        }
+        if let Some(path) = &task.cookie_file {
+            command.extend(["--cookies".to_string(), path.to_string_lossy().to_string()]);
+        }
+        command.extend([
+            "--no-warnings".to_string(),
+            "--newline".to_string(),
+            "--progress-template".to_string(),
+            "[CUPCAKE] %(progress._percent_str)s %(progress._total_bytes_str)s %(progress._speed_str)s ETA %(progress._eta_str)s".to_string(),
+            task.source_url.clone(),
+            "-o".to_string(),
+            format!(
+                "{}/{}",
+                task.destination_dir().to_string_lossy(),
+                task.title
+            ),
+        ]);
+        command
+    }
+
+    /// Run the task's command until it exits or the job is stopped, forwarding everything
+    /// it prints except the progress lines as output. yt-dlp picks up its partial file the
+    /// next time, so pausing and cancelling both just kill it.
+    async fn start(
+        &self,
+        task: &mut DownloadTask,
+        job: &mut Job,
+    ) -> Result<Option<DownloadStatus>> {
+        // the command's program comes from the task, only ever run yt-dlp
+        let mut child = tokio::process::Command::new("yt-dlp")
+            .args(&task.command[1..])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // don't leave yt-dlp running untracked when the app exits
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| eyre!("failed to start yt-dlp. {e}"))?;
+
+        let stdout = child.stdout.take().expect("Failed to capture stdout");
+        let stderr = child.stderr.take().expect("Failed to capture stderr");
+        let mut lines = tokio::io::BufReader::new(stdout).lines();
+        let mut errors = tokio::io::BufReader::new(stderr).lines();
+        let (mut stdout_open, mut stderr_open) = (true, true);
+        let control = job.control.clone();
+
+        while stdout_open || stderr_open {
+            let (line, stderr) = tokio::select! {
+                _ = control.stopped() => {
+                    child.kill().await?;
+                    return Ok(None);
+                }
+                line = lines.next_line(), if stdout_open => (line?, false),
+                line = errors.next_line(), if stderr_open => (line?, true),
+            };
+
+            let Some(line) = line else {
+                if stderr {
+                    stderr_open = false;
+                } else {
+                    stdout_open = false;
+                }
+                continue;
+            };
+
+            if !stderr && let Some(progress_line) = line.strip_prefix("[CUPCAKE] ") {
+                let parts: Vec<&str> = progress_line.split_whitespace().collect();
+
+                if parts.len() >= 5 {
+                    let progress = parts[0].trim_end_matches('%').parse::<f32>().unwrap_or(0.0);
+                    let status = match progress {
+                        _ if progress > 0.0 => DownloadStatus::Running,
+                        _ => DownloadStatus::Queued,
+                    };
+
+                    task.file_size = parts[1].to_string();
+                    task.estimated_time = parts[4].to_string();
+                    task.download_speed = parts[2].to_string();
+                    task.status = status;
+                    task.progress_percent = progress;
+                    job.progress(task);
+                }
+                continue;
+            }
+
+            job.output(task, OutputLine { stderr, text: line });
+        }
+
+        let exit = child.wait().await?;
+        Ok(Some(if exit.success() {
+            DownloadStatus::Completed
+        } else {
+            DownloadStatus::Failed
+        }))
+    }
+
+    /// yt-dlp decides itself whether to continue the partial files it finds
+    fn discard(&self, _task: &DownloadTask, _options: &JobOptions) {}
+}
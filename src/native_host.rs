@@ -0,0 +1,283 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{runtime::Handle, task};
+
+use crate::{
+    daemon::{client, protocol::Request},
+    handoff::{self, Handoff},
+    model::{
+        backend::BackendKind,
+        config::Config,
+        downloader::{DownloadManager, DownloadTask, Downloader},
+        hooks::Hooks,
+        http,
+    },
+};
+
+/// Largest message taken from the browser
+const MAX_REQUEST_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest message browsers take from a native host
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// What the browser extension asks for. Ids are strings, JavaScript numbers can't hold them.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HostRequest {
+    /// Queue a download, `title` names the file when yt-dlp can't
+    Add {
+        url: String,
+        title: Option<String>,
+        referer: Option<String>,
+        /// The `Cookie` header the browser sends for the URL, e.g. `a=1; b=2`
+        cookies: Option<String>,
+        destination: Option<PathBuf>,
+        preset: Option<String>,
+    },
+    /// State of the given tasks, of every task without ids
+    Status {
+        #[serde(default)]
+        ids: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum HostResponse {
+    Added {
+        id: String,
+        /// Who took the task: `daemon`, `tui`, or `store` when it starts the next time
+        /// cupcake runs
+        to: &'static str,
+    },
+    Status {
+        tasks: Vec<TaskStatus>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct TaskStatus {
+    id: String,
+    title: String,
+    url: String,
+    status: &'static str,
+    progress: f32,
+    speed: String,
+    eta: String,
+    path: PathBuf,
+}
+
+impl From<&DownloadTask> for TaskStatus {
+    fn from(task: &DownloadTask) -> Self {
+        Self {
+            id: task.id().to_string(),
+            title: task.title.clone(),
+            url: task.source_url.clone(),
+            status: task.status.as_str(),
+            progress: task.progress_percent,
+            speed: task.download_speed.clone(),
+            eta: task.estimated_time.clone(),
+            path: task.destination_path.clone(),
+        }
+    }
+}
+
+/// Answer the browser's messages until it closes the connection. Every message, both ways,
+/// is JSON preceded by its length as a native-endian `u32`.
+pub fn run(config: &Config) -> Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    while let Some(message) = read_message(&mut input)? {
+        let response = match serde_json::from_slice::<HostRequest>(&message) {
+            Ok(request) => handle(request, config).unwrap_or_else(|e| HostResponse::Error {
+                message: e.to_string(),
+            }),
+            Err(e) => HostResponse::Error {
+                message: format!("invalid message. {e}"),
+            },
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = u32::from_ne_bytes(len) as usize;
+    if len > MAX_REQUEST_SIZE {
+        bail!("message of {len} bytes is too large");
+    }
+    let mut message = vec![0; len];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(output: &mut impl Write, response: &HostResponse) -> Result<()> {
+    let mut message = serde_json::to_vec(response)?;
+    if message.len() > MAX_RESPONSE_SIZE {
+        message = serde_json::to_vec(&HostResponse::Error {
+            message: "the response is too large, ask for fewer tasks".to_string(),
+        })?;
+    }
+    output.write_all(&(message.len() as u32).to_ne_bytes())?;
+    output.write_all(&message)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn handle(request: HostRequest, config: &Config) -> Result<HostResponse> {
+    match request {
+        HostRequest::Add {
+            url,
+            title,
+            referer,
+            cookies,
+            destination,
+            preset,
+        } => {
+            let preset = preset.or(config.default_preset.clone());
+            if let Some(preset) = &preset
+                && !config.presets.contains_key(preset)
+            {
+                bail!("unknown preset `{preset}`");
+            }
+
+            let destination = match destination {
+                Some(destination) => destination,
+                None => match dirs::download_dir() {
+                    Some(dir) => dir,
+                    None => std::env::current_dir()?,
+                },
+            };
+            let name = DownloadManager::extract_filename(&url)
+                .ok()
+                .filter(|name| !name.is_empty())
+                .or_else(|| {
+                    title.and_then(|title| http::sanitize(&title.replace(['/', '\\'], "_")))
+                })
+                .ok_or_else(|| {
+                    let backend = BackendKind::detect(&url);
+                    eyre!("failed to get the file name from {backend}, pass a title")
+                })?;
+
+            let mut task = DownloadTask::new(&url, destination, name);
+            task.preset = preset;
+            task.referer = referer;
+            if let Some(cookies) = cookies {
+                task.cookie_file = Some(write_cookies(&task, &cookies)?);
+            }
+            let id = task.id();
+            let cookie_file = task.cookie_file.clone();
+            // nothing removes the cookies of a task no one took
+            let to = deliver(task, url, config).inspect_err(|_| {
+                if let Some(path) = cookie_file {
+                    let _ = fs::remove_file(path);
+                }
+            })?;
+            Ok(HostResponse::Added {
+                id: id.to_string(),
+                to,
+            })
+        }
+        HostRequest::Status { ids } => {
+            // without the daemon, as they were last saved
+            let downloader = match client::snapshot() {
+                Some(downloader) => downloader,
+                None => load()?,
+            };
+            let tasks = downloader
+                .tasks()
+                .filter(|t| ids.is_empty() || ids.contains(&t.id().to_string()))
+                .map(TaskStatus::from)
+                .collect();
+            Ok(HostResponse::Status { tasks })
+        }
+    }
+}
+
+/// Hand the task to the daemon, else the TUI, else store it for the next run
+fn deliver(task: DownloadTask, url: String, config: &Config) -> Result<&'static str> {
+    let id = task.id();
+    if client::snapshot().is_some() {
+        client::send(&Request::Add {
+            task: Box::new(task),
+        })?;
+        return Ok("daemon");
+    }
+    if handoff::send(&Handoff {
+        url,
+        enqueue: true,
+        task: Some(Box::new(task.clone())),
+    }) {
+        return Ok("tui");
+    }
+    let store = Downloader::lock_store()?;
+    let mut downloader = load()?;
+    downloader.single.enqueue(task);
+    downloader.save()?;
+    drop(store);
+    if let Some(task) = downloader.single.task(id) {
+        task::block_in_place(|| Handle::current().block_on(Hooks::stored(config, task)));
+    }
+    Ok("store")
+}
+
+fn load() -> Result<Downloader> {
+    if !Downloader::dir().join("tasks.json").exists() {
+        return Ok(Downloader::new());
+    }
+    Downloader::new()
+        .load()
+        .map_err(|e| eyre!("failed to read the tasks. {e}"))
+}
+
+/// Store the cookies for the task's site as a Netscape cookie file only the user can read
+fn write_cookies(task: &DownloadTask, cookies: &str) -> Result<PathBuf> {
+    let dir = Downloader::dir().join("cookies");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.txt", task.id()));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+
+    let site = task.site();
+    // a tab or a line break would start another field or another cookie
+    let domain = cookie_field(site.split(':').next().unwrap_or_default());
+    writeln!(file, "# Netscape HTTP Cookie File")?;
+    for cookie in cookies.split(';') {
+        if let Some((name, value)) = cookie.trim().split_once('=') {
+            let (name, value) = (cookie_field(name), cookie_field(value));
+            if !name.is_empty() {
+                writeln!(file, ".{domain}\tTRUE\t/\tFALSE\t0\t{name}\t{value}")?;
+            }
+        }
+    }
+    Ok(path)
+}
+
+fn cookie_field(field: &str) -> String {
+    field
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\r' | '\n'))
+        .collect()
+}
@@ -0,0 +1,1233 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    model::{
+        plugins::PluginBinding,
+        state::{ActivePanel, ActiveTab, ModalType, PromptKind},
+    },
+    update::message::Message,
+};
+
+/// A single key press, compared by key code and modifiers.
+///
+/// Shift is folded into the character for printable keys, so `G` and `<S-g>` are the same key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for KeyPress {
+    fn from(key: KeyEvent) -> Self {
+        let mut modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if key.modifiers.contains(KeyModifiers::SHIFT)
+            && !matches!(key.code, KeyCode::Char(_) | KeyCode::BackTab)
+        {
+            modifiers |= KeyModifiers::SHIFT;
+        }
+
+        // terminals report ctrl+letter in either case depending on the shift state
+        let code = match key.code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+
+        Self { code, modifiers }
+    }
+}
+
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(c) if self.modifiers.is_empty() => return write!(f, "{c}"),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::BackTab => "S-Tab".to_string(),
+            KeyCode::Backspace => "BS".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::Insert => "Insert".to_string(),
+            KeyCode::Up => "Up".to_string(),
+            KeyCode::Down => "Down".to_string(),
+            KeyCode::Left => "Left".to_string(),
+            KeyCode::Right => "Right".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::PageUp => "PageUp".to_string(),
+            KeyCode::PageDown => "PageDown".to_string(),
+            KeyCode::F(n) => format!("F{n}"),
+            other => format!("{other:?}"),
+        };
+
+        write!(f, "<")?;
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "A-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "S-")?;
+        }
+        write!(f, "{name}>")
+    }
+}
+
+/// A sequence of key presses in vim notation, e.g. `gg`, `<C-h>` or `<S-Tab>`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<KeyPress>);
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|key| write!(f, "{key}"))
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut keys = Vec::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '<' {
+                keys.push(KeyPress {
+                    code: KeyCode::Char(c),
+                    modifiers: KeyModifiers::NONE,
+                });
+                continue;
+            }
+
+            let name = chars.by_ref().take_while(|&c| c != '>').collect::<String>();
+            keys.push(Self::parse_special(&name)?);
+        }
+
+        if keys.is_empty() {
+            bail!("empty key sequence");
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl KeySequence {
+    fn parse_special(name: &str) -> Result<KeyPress> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        while let Some((prefix, tail)) = rest
+            .split_once('-')
+            .filter(|(p, t)| p.len() == 1 && !t.is_empty())
+        {
+            modifiers |= match prefix.to_ascii_uppercase().as_str() {
+                "C" => KeyModifiers::CONTROL,
+                "A" | "M" => KeyModifiers::ALT,
+                "S" => KeyModifiers::SHIFT,
+                _ => bail!("unknown modifier `{prefix}` in `<{name}>`"),
+            };
+            rest = tail;
+        }
+
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "gt" => KeyCode::Char('>'),
+            "enter" | "cr" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            }
+            "tab" => KeyCode::Tab,
+            "bs" | "backspace" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(f[1..].parse().unwrap_or_default())
+            }
+            _ if rest.chars().count() == 1 => {
+                let c = rest.chars().next().unwrap_or_default();
+                // shift is folded into the character, <S-g> is G
+                if modifiers.contains(KeyModifiers::SHIFT) {
+                    modifiers -= KeyModifiers::SHIFT;
+                    KeyCode::Char(c.to_ascii_uppercase())
+                } else if modifiers.contains(KeyModifiers::CONTROL) {
+                    KeyCode::Char(c.to_ascii_lowercase())
+                } else {
+                    KeyCode::Char(c)
+                }
+            }
+            _ => return Err(eyre!("unknown key `<{name}>`")),
+        };
+
+        Ok(KeyPress { code, modifiers })
+    }
+}
+
+/// Where a binding applies. Bindings in `Global` apply everywhere and take precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Context {
+    Global,
+    Content,
+    Menu,
+    SourceInput,
+    DestinationInput,
+    FilterInput,
+    Prompt,
+    Info,
+    Error,
+    Confirm,
+    Help,
+    Palette,
+    Details,
+}
+
+impl From<&ActivePanel> for Context {
+    fn from(panel: &ActivePanel) -> Self {
+        match panel {
+            ActivePanel::Content => Context::Content,
+            ActivePanel::Menu => Context::Menu,
+            ActivePanel::Modal(modal_type) => match modal_type {
+                ModalType::SourceInput => Context::SourceInput,
+                ModalType::DestinationInput => Context::DestinationInput,
+                ModalType::FilterInput => Context::FilterInput,
+                ModalType::Prompt(_) => Context::Prompt,
+                ModalType::Info => Context::Info,
+                ModalType::Error => Context::Error,
+                ModalType::Confirm => Context::Confirm,
+                ModalType::Help => Context::Help,
+                ModalType::Palette => Context::Palette,
+                ModalType::Details => Context::Details,
+            },
+        }
+    }
+}
+
+impl Context {
+    pub const ALL: [Context; 13] = [
+        Context::Global,
+        Context::Content,
+        Context::Menu,
+        Context::SourceInput,
+        Context::DestinationInput,
+        Context::FilterInput,
+        Context::Prompt,
+        Context::Info,
+        Context::Error,
+        Context::Confirm,
+        Context::Help,
+        Context::Palette,
+        Context::Details,
+    ];
+
+    /// Name used for the context in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Context::Global => "global",
+            Context::Content => "content",
+            Context::Menu => "menu",
+            Context::SourceInput => "source_input",
+            Context::DestinationInput => "destination_input",
+            Context::FilterInput => "filter_input",
+            Context::Prompt => "prompt",
+            Context::Info => "info",
+            Context::Error => "error",
+            Context::Confirm => "confirm",
+            Context::Help => "help",
+            Context::Palette => "palette",
+            Context::Details => "details",
+        }
+    }
+
+    /// Contexts with a text input, keys that are not bound are typed into the input
+    pub fn is_text_input(self) -> bool {
+        matches!(
+            self,
+            Context::SourceInput
+                | Context::DestinationInput
+                | Context::FilterInput
+                | Context::Prompt
+                | Context::Palette
+        )
+    }
+
+    /// Actions worth a place in the one-line hint footer, most relevant first
+    pub fn hints(self) -> &'static [Action] {
+        match self {
+            Context::Global => &[],
+            Context::Content => &[
+                Action::AddTask,
+                Action::Details,
+                Action::Pause,
+                Action::Resume,
+                Action::Remove,
+                Action::VisualMode,
+                Action::Palette,
+                Action::Help,
+                Action::Quit,
+            ],
+            Context::Menu => &[
+                Action::ApplyFilter,
+                Action::ToggleMenuItem,
+                Action::SaveFilter,
+                Action::FocusContent,
+                Action::Help,
+                Action::Quit,
+            ],
+            Context::SourceInput | Context::Prompt => &[Action::Submit, Action::Close],
+            Context::DestinationInput | Context::FilterInput => {
+                &[Action::Submit, Action::ToggleInput, Action::Close]
+            }
+            Context::Info | Context::Error => &[Action::Close, Action::Help],
+            Context::Confirm => &[Action::Submit, Action::Close],
+            Context::Help => &[Action::ScrollDown, Action::ScrollUp, Action::Close],
+            Context::Details => &[
+                Action::ScrollDown,
+                Action::ScrollUp,
+                Action::ScrollBottom,
+                Action::Close,
+            ],
+            Context::Palette => &[
+                Action::Submit,
+                Action::ScrollDown,
+                Action::ScrollUp,
+                Action::Close,
+            ],
+        }
+    }
+
+    /// Message for a key that is not bound in this context
+    pub fn fallback(self, event: Event) -> Option<Message> {
+        match self {
+            Context::SourceInput => Some(Message::HandleSourceInputEvent(event)),
+            Context::DestinationInput => Some(Message::HandleDestinationInputEvent(event)),
+            Context::FilterInput => Some(Message::HandleFilterInputEvent(event)),
+            Context::Prompt => Some(Message::HandlePromptInputEvent(event)),
+            Context::Palette => Some(Message::HandlePaletteInputEvent(event)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Context {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|context| context.name() == s)
+            .ok_or_else(|| eyre!("unknown context `{s}`"))
+    }
+}
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    AddTask,
+    FocusMenu,
+    FocusContent,
+    NextTab,
+    PreviousTab,
+    NextRow,
+    PreviousRow,
+    FirstRow,
+    LastRow,
+    ProgressUp,
+    ProgressDown,
+    ToggleSelection,
+    VisualMode,
+    ClearSelection,
+    Pause,
+    Resume,
+    Retry,
+    Remove,
+    ChangeDestination,
+    Tag,
+    Export,
+    ToggleMenuItem,
+    CollapseMenuItem,
+    ExpandMenuItem,
+    NextMenuItem,
+    PreviousMenuItem,
+    FirstMenuItem,
+    LastMenuItem,
+    ApplyFilter,
+    SaveFilter,
+    DeleteFilter,
+    ToggleInput,
+    Submit,
+    Close,
+    Help,
+    ScrollDown,
+    ScrollUp,
+    ScrollTop,
+    ScrollBottom,
+    Palette,
+    Details,
+    ToggleLog,
+    /// A command line bound by a plugin, by its index in the keymap
+    Command(usize),
+}
+
+impl Action {
+    pub const ALL: [Action; 43] = [
+        Action::Quit,
+        Action::AddTask,
+        Action::FocusMenu,
+        Action::FocusContent,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::NextRow,
+        Action::PreviousRow,
+        Action::FirstRow,
+        Action::LastRow,
+        Action::ProgressUp,
+        Action::ProgressDown,
+        Action::ToggleSelection,
+        Action::VisualMode,
+        Action::ClearSelection,
+        Action::Pause,
+        Action::Resume,
+        Action::Retry,
+        Action::Remove,
+        Action::ChangeDestination,
+        Action::Tag,
+        Action::Export,
+        Action::ToggleMenuItem,
+        Action::CollapseMenuItem,
+        Action::ExpandMenuItem,
+        Action::NextMenuItem,
+        Action::PreviousMenuItem,
+        Action::FirstMenuItem,
+        Action::LastMenuItem,
+        Action::ApplyFilter,
+        Action::SaveFilter,
+        Action::DeleteFilter,
+        Action::ToggleInput,
+        Action::Submit,
+        Action::Close,
+        Action::Help,
+        Action::ScrollDown,
+        Action::ScrollUp,
+        Action::ScrollTop,
+        Action::ScrollBottom,
+        Action::Palette,
+        Action::Details,
+        Action::ToggleLog,
+    ];
+
+    /// Name used for the action in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::AddTask => "add_task",
+            Action::FocusMenu => "focus_menu",
+            Action::FocusContent => "focus_content",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+            Action::NextRow => "next_row",
+            Action::PreviousRow => "previous_row",
+            Action::FirstRow => "first_row",
+            Action::LastRow => "last_row",
+            Action::ProgressUp => "progress_up",
+            Action::ProgressDown => "progress_down",
+            Action::ToggleSelection => "toggle_selection",
+            Action::VisualMode => "visual_mode",
+            Action::ClearSelection => "clear_selection",
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::Retry => "retry",
+            Action::Remove => "remove",
+            Action::ChangeDestination => "change_destination",
+            Action::Tag => "tag",
+            Action::Export => "export",
+            Action::ToggleMenuItem => "toggle_menu_item",
+            Action::CollapseMenuItem => "collapse_menu_item",
+            Action::ExpandMenuItem => "expand_menu_item",
+            Action::NextMenuItem => "next_menu_item",
+            Action::PreviousMenuItem => "previous_menu_item",
+            Action::FirstMenuItem => "first_menu_item",
+            Action::LastMenuItem => "last_menu_item",
+            Action::ApplyFilter => "apply_filter",
+            Action::SaveFilter => "save_filter",
+            Action::DeleteFilter => "delete_filter",
+            Action::ToggleInput => "toggle_input",
+            Action::Submit => "submit",
+            Action::Close => "close",
+            Action::Help => "help",
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollTop => "scroll_top",
+            Action::ScrollBottom => "scroll_bottom",
+            Action::Palette => "palette",
+            Action::Details => "details",
+            Action::ToggleLog => "toggle_log",
+            Action::Command(_) => "command",
+        }
+    }
+
+    /// What the action does, as listed in the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::AddTask => "add a task",
+            Action::FocusMenu => "focus the sidebar",
+            Action::FocusContent => "focus the task list",
+            Action::NextTab => "next tab",
+            Action::PreviousTab => "previous tab",
+            Action::NextRow => "next task",
+            Action::PreviousRow => "previous task",
+            Action::FirstRow => "first task",
+            Action::LastRow => "last task",
+            Action::ProgressUp => "increase progress",
+            Action::ProgressDown => "decrease progress",
+            Action::ToggleSelection => "select/unselect the task",
+            Action::VisualMode => "start/stop visual mode",
+            Action::ClearSelection => "clear the selection",
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::Retry => "retry",
+            Action::Remove => "remove",
+            Action::ChangeDestination => "change destination",
+            Action::Tag => "tag",
+            Action::Export => "export",
+            Action::ToggleMenuItem => "open/close the group",
+            Action::CollapseMenuItem => "close the group",
+            Action::ExpandMenuItem => "open the group",
+            Action::NextMenuItem => "next item",
+            Action::PreviousMenuItem => "previous item",
+            Action::FirstMenuItem => "first item",
+            Action::LastMenuItem => "last item",
+            Action::ApplyFilter => "show the tasks of the item",
+            Action::SaveFilter => "save a filter",
+            Action::DeleteFilter => "delete the saved filter",
+            Action::ToggleInput => "switch input",
+            Action::Submit => "submit",
+            Action::Close => "close",
+            Action::Help => "help",
+            Action::ScrollDown => "scroll down",
+            Action::ScrollUp => "scroll up",
+            Action::ScrollTop => "scroll to the top",
+            Action::ScrollBottom => "scroll to the bottom",
+            Action::Palette => "commands",
+            Action::Details => "details",
+            Action::ToggleLog => "show/hide the log",
+            Action::Command(_) => "run a command",
+        }
+    }
+
+    /// Motions are repeated when prefixed with a count, e.g. `5j`
+    pub fn repeatable(self) -> bool {
+        matches!(
+            self,
+            Action::NextTab
+                | Action::PreviousTab
+                | Action::NextRow
+                | Action::PreviousRow
+                | Action::ProgressUp
+                | Action::ProgressDown
+                | Action::NextMenuItem
+                | Action::PreviousMenuItem
+                | Action::ScrollDown
+                | Action::ScrollUp
+        )
+    }
+
+    pub fn message(self, active_panel: &ActivePanel, active_tab: &ActiveTab) -> Option<Message> {
+        let per_tab = |single, batch, playlist| match active_tab {
+            ActiveTab::Single => Some(single),
+            ActiveTab::Batch => Some(batch),
+            ActiveTab::Playlist => Some(playlist),
+            _ => None,
+        };
+
+        match self {
+            Action::Quit => Some(Message::Quit),
+            Action::AddTask => Some(Message::ShowAddTaskModal),
+            Action::FocusMenu => Some(Message::FocusMenu),
+            Action::FocusContent => Some(Message::FocusContent),
+            Action::NextTab => Some(Message::SwitchNextTab),
+            Action::PreviousTab => Some(Message::SwitchPreviousTab),
+            Action::NextRow => per_tab(
+                Message::SelectNextRowSingle,
+                Message::SelectNextRowBatch,
+                Message::SelectNextRowPlaylist,
+            ),
+            Action::PreviousRow => per_tab(
+                Message::SelectPreviousRowSingle,
+                Message::SelectPreviousRowBatch,
+                Message::SelectPreviousRowPlaylist,
+            ),
+            Action::FirstRow => Some(Message::SelectFirstRow),
+            Action::LastRow => Some(Message::SelectLastRow),
+            Action::ProgressUp => Some(Message::ProgressUp),
+            Action::ProgressDown => Some(Message::ProgressDown),
+            Action::ToggleSelection => Some(Message::ToggleSelection),
+            Action::VisualMode => Some(Message::ToggleVisualMode),
+            Action::ClearSelection => Some(Message::ClearSelection),
+            Action::Pause => Some(Message::PauseSelected),
+            Action::Resume => Some(Message::ResumeSelected),
+            Action::Retry => Some(Message::RetrySelected),
+            Action::Remove => Some(Message::ConfirmRemoveSelected),
+            Action::ChangeDestination => Some(Message::ShowPromptModal(PromptKind::Destination)),
+            Action::Tag => Some(Message::ShowPromptModal(PromptKind::Tags)),
+            Action::Export => Some(Message::ExportSelected),
+            Action::ToggleMenuItem => Some(Message::ToggleSelected),
+            Action::CollapseMenuItem => Some(Message::CollapseMenuItem),
+            Action::ExpandMenuItem => Some(Message::ExpandMenuItem),
+            Action::NextMenuItem => Some(Message::SelectNextMenuItem),
+            Action::PreviousMenuItem => Some(Message::SelectPrevMenuItem),
+            Action::FirstMenuItem => Some(Message::SelectFirstMenuItem),
+            Action::LastMenuItem => Some(Message::SelectLastMenuItem),
+            Action::ApplyFilter => per_tab(
+                Message::ApplyCategoryFilterSingle,
+                Message::ApplyCategoryFilterBatch,
+                Message::ApplyCategoryFilterPlaylist,
+            ),
+            Action::SaveFilter => Some(Message::ShowSaveFilterModal),
+            Action::DeleteFilter => Some(Message::DeleteSelectedFilter),
+            Action::ToggleInput => Some(Message::ToggleFocusedInput),
+            Action::Close => Some(Message::CloseModal),
+            Action::Help => Some(Message::ShowHelp),
+            Action::ScrollDown => Some(Message::ScrollDown),
+            Action::ScrollUp => Some(Message::ScrollUp),
+            Action::ScrollTop => Some(Message::ScrollTop),
+            Action::ScrollBottom => Some(Message::ScrollBottom),
+            Action::Palette => Some(Message::ShowPalette),
+            Action::Details => Some(Message::ShowDetails),
+            Action::ToggleLog => Some(Message::ToggleLog),
+            Action::Command(index) => Some(Message::RunBoundCommand(index)),
+            Action::Submit => match active_panel {
+                ActivePanel::Modal(ModalType::SourceInput) => match active_tab {
+                    ActiveTab::Single => Some(Message::ExtractMetadata),
+                    _ => None,
+                },
+                ActivePanel::Modal(ModalType::DestinationInput) => Some(Message::AddTaskSingle),
+                ActivePanel::Modal(ModalType::FilterInput) => Some(Message::SaveFilter),
+                ActivePanel::Modal(ModalType::Prompt(kind)) => Some(Message::SubmitPrompt(*kind)),
+                ActivePanel::Modal(ModalType::Confirm) => Some(Message::Confirm),
+                ActivePanel::Modal(ModalType::Palette) => Some(Message::RunPaletteCommand),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| eyre!("unknown action `{s}`"))
+    }
+}
+
+/// Key bindings per context.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: HashMap<Context, Vec<(KeySequence, Action)>>,
+    /// Command lines bound by plugins, `Action::Command` indexes them
+    commands: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            commands: Vec::new(),
+        };
+
+        keymap.bind_all(
+            Context::Global,
+            &[("<C-c>", Action::Quit), ("<C-n>", Action::AddTask)],
+        );
+
+        keymap.bind_all(
+            Context::Content,
+            &[
+                ("a", Action::AddTask),
+                ("q", Action::Quit),
+                ("<C-h>", Action::FocusMenu),
+                ("<C-Left>", Action::FocusMenu),
+                ("<Tab>", Action::NextTab),
+                ("L", Action::NextTab),
+                ("<S-Tab>", Action::PreviousTab),
+                ("H", Action::PreviousTab),
+                ("<Up>", Action::PreviousRow),
+                ("k", Action::PreviousRow),
+                ("<Down>", Action::NextRow),
+                ("j", Action::NextRow),
+                ("gg", Action::FirstRow),
+                ("<Home>", Action::FirstRow),
+                ("G", Action::LastRow),
+                ("<End>", Action::LastRow),
+                ("<Right>", Action::ProgressUp),
+                ("<Left>", Action::ProgressDown),
+                ("<Space>", Action::ToggleSelection),
+                ("v", Action::VisualMode),
+                ("<Esc>", Action::ClearSelection),
+                ("p", Action::Pause),
+                ("r", Action::Resume),
+                ("R", Action::Retry),
+                ("dd", Action::Remove),
+                ("<Del>", Action::Remove),
+                ("m", Action::ChangeDestination),
+                ("t", Action::Tag),
+                ("e", Action::Export),
+                ("<Enter>", Action::Details),
+                ("?", Action::Help),
+                (":", Action::Palette),
+                ("<C-p>", Action::Palette),
+                ("gl", Action::ToggleLog),
+            ],
+        );
+
+        keymap.bind_all(
+            Context::Menu,
+            &[
+                ("a", Action::AddTask),
+                ("q", Action::Quit),
+                ("<C-l>", Action::FocusContent),
+                ("<C-Right>", Action::FocusContent),
+                ("<Enter>", Action::ApplyFilter),
+                ("<Space>", Action::ToggleMenuItem),
+                ("<Left>", Action::CollapseMenuItem),
+                ("h", Action::CollapseMenuItem),
+                ("<Right>", Action::ExpandMenuItem),
+                ("l", Action::ExpandMenuItem),
+                ("<Down>", Action::NextMenuItem),
+                ("j", Action::NextMenuItem),
+                ("<Up>", Action::PreviousMenuItem),
+                ("k", Action::PreviousMenuItem),
+                ("<Home>", Action::FirstMenuItem),
+                ("gg", Action::FirstMenuItem),
+                ("<End>", Action::LastMenuItem),
+                ("G", Action::LastMenuItem),
+                ("f", Action::SaveFilter),
+                ("x", Action::DeleteFilter),
+                ("<Del>", Action::DeleteFilter),
+                ("?", Action::Help),
+                (":", Action::Palette),
+                ("<C-p>", Action::Palette),
+                ("gl", Action::ToggleLog),
+            ],
+        );
+
+        keymap.bind_all(
+            Context::SourceInput,
+            &[("<Esc>", Action::Close), ("<Enter>", Action::Submit)],
+        );
+
+        for context in [Context::DestinationInput, Context::FilterInput] {
+            keymap.bind_all(
+                context,
+                &[
+                    ("<Esc>", Action::Close),
+                    ("<Enter>", Action::Submit),
+                    ("<Up>", Action::ToggleInput),
+                    ("<Down>", Action::ToggleInput),
+                    ("<Tab>", Action::ToggleInput),
+                    ("<S-Tab>", Action::ToggleInput),
+                ],
+            );
+        }
+
+        keymap.bind_all(
+            Context::Prompt,
+            &[("<Esc>", Action::Close), ("<Enter>", Action::Submit)],
+        );
+        keymap.bind_all(
+            Context::Info,
+            &[("<Esc>", Action::Close), ("?", Action::Help)],
+        );
+        keymap.bind_all(
+            Context::Error,
+            &[("<Esc>", Action::Close), ("?", Action::Help)],
+        );
+        keymap.bind_all(
+            Context::Confirm,
+            &[("<Esc>", Action::Close), ("<Enter>", Action::Submit)],
+        );
+        keymap.bind_all(
+            Context::Help,
+            &[
+                ("<Down>", Action::ScrollDown),
+                ("j", Action::ScrollDown),
+                ("<Up>", Action::ScrollUp),
+                ("k", Action::ScrollUp),
+                ("<Home>", Action::ScrollTop),
+                ("gg", Action::ScrollTop),
+                ("<End>", Action::ScrollBottom),
+                ("G", Action::ScrollBottom),
+                ("<Esc>", Action::Close),
+                ("q", Action::Close),
+                ("?", Action::Close),
+            ],
+        );
+        keymap.bind_all(
+            Context::Details,
+            &[
+                ("<Down>", Action::ScrollDown),
+                ("j", Action::ScrollDown),
+                ("<Up>", Action::ScrollUp),
+                ("k", Action::ScrollUp),
+                ("<Home>", Action::ScrollTop),
+                ("gg", Action::ScrollTop),
+                ("<End>", Action::ScrollBottom),
+                ("G", Action::ScrollBottom),
+                ("<Esc>", Action::Close),
+                ("q", Action::Close),
+            ],
+        );
+        keymap.bind_all(
+            Context::Palette,
+            &[
+                ("<Esc>", Action::Close),
+                ("<Enter>", Action::Submit),
+                ("<Down>", Action::ScrollDown),
+                ("<Tab>", Action::ScrollDown),
+                ("<C-j>", Action::ScrollDown),
+                ("<Up>", Action::ScrollUp),
+                ("<S-Tab>", Action::ScrollUp),
+                ("<C-k>", Action::ScrollUp),
+            ],
+        );
+
+        keymap
+    }
+}
+
+impl Keymap {
+    fn bind_all(&mut self, context: Context, bindings: &[(&str, Action)]) {
+        for (keys, action) in bindings {
+            let keys = keys.parse().expect("default key bindings must be valid");
+            self.bind(context, keys, *action);
+        }
+    }
+
+    pub fn bind(&mut self, context: Context, keys: KeySequence, action: Action) {
+        let bindings = self.bindings.entry(context).or_default();
+        bindings.retain(|(k, _)| k != &keys);
+        bindings.push((keys, action));
+    }
+
+    pub fn unbind(&mut self, context: Context, keys: &KeySequence) {
+        if let Some(bindings) = self.bindings.get_mut(&context) {
+            bindings.retain(|(k, _)| k != keys);
+        }
+    }
+
+    /// The command line a plugin bound to `Action::Command`
+    pub fn command(&self, index: usize) -> Option<&str> {
+        self.commands.get(index).map(String::as_str)
+    }
+
+    /// What the action does, the command line for the ones bound by plugins
+    pub fn describe(&self, action: Action) -> String {
+        match action {
+            Action::Command(index) => format!(":{}", self.command(index).unwrap_or_default()),
+            action => action.description().to_string(),
+        }
+    }
+
+    pub fn bindings(&self, context: Context) -> &[(KeySequence, Action)] {
+        self.bindings.get(&context).map_or(&[], Vec::as_slice)
+    }
+
+    /// Bindings that do something in the given panel and tab, grouped by context and action
+    /// in binding order
+    pub fn active(
+        &self,
+        active_panel: &ActivePanel,
+        active_tab: &ActiveTab,
+    ) -> Vec<(Context, Action, Vec<&KeySequence>)> {
+        let mut active: Vec<(Context, Action, Vec<&KeySequence>)> = Vec::new();
+
+        for context in [Context::Global, Context::from(active_panel)] {
+            for (keys, action) in self.bindings(context) {
+                if action.message(active_panel, active_tab).is_none() {
+                    continue;
+                }
+                match active
+                    .iter_mut()
+                    .find(|(c, a, _)| *c == context && a == action)
+                {
+                    Some((_, _, all)) => all.push(keys),
+                    None => active.push((context, *action, vec![keys])),
+                }
+            }
+        }
+
+        active
+    }
+
+    /// One-line summary of the most relevant keys, e.g. `a add a task · p pause`
+    pub fn hints(
+        &self,
+        active_panel: &ActivePanel,
+        active_tab: &ActiveTab,
+    ) -> Vec<(String, &'static str)> {
+        let active = self.active(active_panel, active_tab);
+
+        Context::from(active_panel)
+            .hints()
+            .iter()
+            .filter_map(|hint| {
+                // prefer the panel's own keys, they tend to be the short ones
+                let (_, action, keys) = active.iter().rev().find(|(_, a, _)| a == hint)?;
+                Some((keys.first()?.to_string(), action.description()))
+            })
+            .collect()
+    }
+
+    /// The default keymap with the user's bindings from the config applied on top.
+    ///
+    /// Bindings that can't be applied, and bindings that can never fire because another one
+    /// takes precedence, are returned as problems to report instead of failing to start.
+    pub fn from_config(keys: &BTreeMap<String, BTreeMap<String, String>>) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let mut problems = Vec::new();
+
+        for (context, bindings) in keys {
+            let context = match context.parse::<Context>() {
+                Ok(context) => context,
+                Err(e) => {
+                    problems.push(e.to_string());
+                    continue;
+                }
+            };
+
+            let mut seen: Vec<(KeySequence, &str)> = Vec::new();
+            for (keys, action) in bindings {
+                let sequence = match keys.parse::<KeySequence>() {
+                    Ok(sequence) => sequence,
+                    Err(e) => {
+                        problems.push(format!("{}: {e}", context.name()));
+                        continue;
+                    }
+                };
+
+                if let Some((_, other)) = seen.iter().find(|(s, _)| s == &sequence) {
+                    problems.push(format!(
+                        "{}: `{keys}` and `{other}` are the same keys",
+                        context.name()
+                    ));
+                    continue;
+                }
+                seen.push((sequence.clone(), keys));
+
+                // an empty action or "none" removes the default binding
+                if action.is_empty() || action == "none" {
+                    keymap.unbind(context, &sequence);
+                    continue;
+                }
+
+                let action = match action.parse::<Action>() {
+                    Ok(action) => action,
+                    Err(e) => {
+                        problems.push(format!("{}: `{keys}`: {e}", context.name()));
+                        continue;
+                    }
+                };
+
+                if context.is_text_input()
+                    && let Some(first) = sequence.0.first()
+                    && matches!(first.code, KeyCode::Char(_))
+                    && first.modifiers.is_empty()
+                {
+                    problems.push(format!(
+                        "{}: `{keys}` would make `{first}` impossible to type",
+                        context.name()
+                    ));
+                    continue;
+                }
+
+                keymap.bind(context, sequence, action);
+            }
+        }
+
+        problems.extend(keymap.conflicts());
+        (keymap, problems)
+    }
+
+    /// Bind the command lines of the plugins, on top of the user's bindings. Returns the
+    /// bindings that can't be applied as problems, like `from_config`.
+    pub fn bind_commands(&mut self, bindings: &[PluginBinding]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for binding in bindings {
+            let keys = match (
+                binding.context.parse::<Context>(),
+                binding.keys.parse::<KeySequence>(),
+            ) {
+                (Ok(context), Ok(keys)) => (context, keys),
+                (Err(e), _) | (_, Err(e)) => {
+                    problems.push(format!("plugin binding `{}`: {e}", binding.keys));
+                    continue;
+                }
+            };
+            let (context, sequence) = keys;
+            if context.is_text_input() {
+                problems.push(format!(
+                    "plugin binding `{}`: plugins can't bind keys in {}",
+                    binding.keys,
+                    context.name()
+                ));
+                continue;
+            }
+
+            self.commands.push(binding.command.clone());
+            self.bind(context, sequence, Action::Command(self.commands.len() - 1));
+        }
+        problems
+    }
+
+    /// Bindings shadowed by a global binding with the same keys or a prefix of them, and
+    /// bindings that are the prefix of a longer one in their context, which only fire once
+    /// the sequence times out
+    fn conflicts(&self) -> Vec<String> {
+        let global = self.bindings(Context::Global);
+        let mut problems = Vec::new();
+
+        for context in Context::ALL {
+            let bindings = self.bindings(context);
+            for (keys, action) in bindings {
+                if context != Context::Global
+                    && let Some((global_keys, global_action)) =
+                        global.iter().find(|(g, _)| keys.0.starts_with(&g.0))
+                {
+                    problems.push(format!(
+                        "{}: `{keys}` ({}) is shadowed by the global `{global_keys}` ({})",
+                        context.name(),
+                        action.name(),
+                        global_action.name()
+                    ));
+                    continue;
+                }
+                if let Some((longer, longer_action)) = bindings
+                    .iter()
+                    .find(|(other, _)| other.0.len() > keys.0.len() && other.0.starts_with(&keys.0))
+                {
+                    problems.push(format!(
+                        "{}: `{keys}` ({}) is a prefix of `{longer}` ({}), it only fires after \
+                         a pause",
+                        context.name(),
+                        action.name(),
+                        longer_action.name()
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyPress {
+        KeyPress { code, modifiers }
+    }
+
+    fn sequence(keys: &str) -> Vec<KeyPress> {
+        keys.parse::<KeySequence>().unwrap().0
+    }
+
+    #[test]
+    fn parses_key_sequences() {
+        let g = press(KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(sequence("gg"), [g, g]);
+        assert_eq!(
+            sequence("<C-h>"),
+            [press(KeyCode::Char('h'), KeyModifiers::CONTROL)]
+        );
+        assert_eq!(
+            sequence("<c-a-Left>"),
+            [press(
+                KeyCode::Left,
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )]
+        );
+        assert_eq!(
+            sequence("<S-Tab>"),
+            [press(KeyCode::BackTab, KeyModifiers::NONE)]
+        );
+        assert_eq!(sequence("<S-g>"), sequence("G"));
+        assert_eq!(sequence("<C-H>"), sequence("<C-h>"));
+        assert_eq!(
+            sequence("<Space><lt><gt><CR><F12>"),
+            [
+                press(KeyCode::Char(' '), KeyModifiers::NONE),
+                press(KeyCode::Char('<'), KeyModifiers::NONE),
+                press(KeyCode::Char('>'), KeyModifiers::NONE),
+                press(KeyCode::Enter, KeyModifiers::NONE),
+                press(KeyCode::F(12), KeyModifiers::NONE),
+            ]
+        );
+        assert_eq!(
+            sequence("<M-x>"),
+            [press(KeyCode::Char('x'), KeyModifiers::ALT)]
+        );
+        // a dash on its own is a key, not a modifier
+        assert_eq!(
+            sequence("<C-->"),
+            [press(KeyCode::Char('-'), KeyModifiers::CONTROL)]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_key_sequences() {
+        for keys in ["", "<>", "<Hyper>", "<X-a>", "<C-Nope>"] {
+            assert!(keys.parse::<KeySequence>().is_err(), "{keys}");
+        }
+    }
+
+    #[test]
+    fn prints_key_sequences_back() {
+        for keys in [
+            "gg",
+            "<C-h>",
+            "<S-Tab>",
+            "G",
+            "<Space>",
+            "<lt>",
+            "<C-A-Left>",
+            "<F5>",
+        ] {
+            assert_eq!(keys.parse::<KeySequence>().unwrap().to_string(), keys);
+        }
+    }
+
+    fn from_config(bindings: &[(&str, &str, &str)]) -> (Keymap, Vec<String>) {
+        let mut keys: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        for (context, sequence, action) in bindings {
+            keys.entry(context.to_string())
+                .or_default()
+                .insert(sequence.to_string(), action.to_string());
+        }
+        Keymap::from_config(&keys)
+    }
+
+    fn bound(keymap: &Keymap, context: Context, keys: &str) -> Option<Action> {
+        let keys = keys.parse::<KeySequence>().unwrap();
+        keymap
+            .bindings(context)
+            .iter()
+            .find(|(k, _)| *k == keys)
+            .map(|(_, action)| *action)
+    }
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        assert!(Keymap::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn applies_config_bindings() {
+        let (keymap, problems) = from_config(&[
+            ("content", "n", "next_row"),
+            ("content", "j", "none"),
+            ("content", "<C-d>", ""),
+            ("menu", "<C-x>", "quit"),
+        ]);
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(bound(&keymap, Context::Content, "n"), Some(Action::NextRow));
+        assert_eq!(bound(&keymap, Context::Content, "j"), None);
+        assert_eq!(
+            bound(&keymap, Context::Content, "k"),
+            Some(Action::PreviousRow)
+        );
+        assert_eq!(bound(&keymap, Context::Menu, "<C-x>"), Some(Action::Quit));
+    }
+
+    #[test]
+    fn reports_invalid_config_bindings() {
+        let (keymap, problems) = from_config(&[
+            ("nowhere", "x", "quit"),
+            ("content", "<Nope>", "quit"),
+            ("content", "x", "explode"),
+            ("content", "<C-h>", "next_row"),
+            ("content", "<c-h>", "previous_row"),
+            ("prompt", "x", "close"),
+            ("prompt", "<C-x>", "close"),
+        ]);
+        assert_eq!(problems.len(), 5, "{problems:?}");
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.contains("unknown context `nowhere`"))
+        );
+        assert!(problems.iter().any(|p| p.contains("unknown key `<Nope>`")));
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.contains("unknown action `explode`"))
+        );
+        assert!(problems.iter().any(|p| p.contains("are the same keys")));
+        assert!(problems.iter().any(|p| p.contains("impossible to type")));
+        // the valid ones still apply
+        assert_eq!(
+            bound(&keymap, Context::Prompt, "<C-x>"),
+            Some(Action::Close)
+        );
+        assert_eq!(bound(&keymap, Context::Content, "x"), None);
+    }
+
+    #[test]
+    fn reports_shadowed_bindings() {
+        let (_, problems) = from_config(&[("global", "g", "help")]);
+        assert!(
+            problems
+                .iter()
+                .any(|p| p == "content: `gg` (first_row) is shadowed by the global `g` (help)"),
+            "{problems:?}"
+        );
+
+        let (_, problems) = from_config(&[("content", "g", "last_row")]);
+        assert_eq!(
+            problems,
+            [
+                "content: `g` (last_row) is a prefix of `gg` (first_row), it only fires after a \
+                 pause"
+            ]
+        );
+
+        let (_, problems) = from_config(&[("content", "ggx", "export")]);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("content: `gg` (first_row) is a prefix of `ggx`"));
+    }
+
+    #[test]
+    fn folds_shift_into_typed_characters() {
+        let typed = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(KeyPress::from(typed), sequence("G")[0]);
+        let ctrl = KeyEvent::new(KeyCode::Char('H'), KeyModifiers::CONTROL);
+        assert_eq!(KeyPress::from(ctrl), sequence("<C-h>")[0]);
+    }
+}
@@ -1,8 +1,13 @@
-use crate::model::{
-    downloader::DownloadTask,
-    state::{ActivePanel, ActiveTab, ModalType},
+use std::path::PathBuf;
+
+use crate::{
+    daemon::protocol::Event as RemoteEvent,
+    model::{
+        downloader::{DownloadTask, OutputLine},
+        state::{ActiveTab, PromptKind},
+    },
 };
-use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{Event, MouseEvent};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -18,9 +23,22 @@ pub enum Message {
     SelectPreviousRowSingle,
     SelectPreviousRowBatch,
     SelectPreviousRowPlaylist,
+    SelectFirstRow,
+    SelectLastRow,
     ProgressUp,
     ProgressDown,
     UpdateProgressSingle,
+    /// Recount the saved filters on the time tasks were added, sent every minute
+    RefreshCounts,
+    ToggleSelection,
+    ToggleVisualMode,
+    ClearSelection,
+    PauseSelected,
+    ResumeSelected,
+    RetrySelected,
+    ConfirmRemoveSelected,
+    RemoveSelected,
+    ExportSelected,
 
     // Menu actions
     FocusContent,
@@ -34,130 +52,60 @@ pub enum Message {
     ApplyCategoryFilterSingle,
     ApplyCategoryFilterBatch,
     ApplyCategoryFilterPlaylist,
+    DeleteSelectedFilter,
 
     // Modal actions
     ShowAddTaskModal,
     ToggleFocusedInput,
     HandleSourceInputEvent(Event),
     HandleDestinationInputEvent(Event),
+    ShowSaveFilterModal,
+    HandleFilterInputEvent(Event),
+    SaveFilter,
+    ShowPromptModal(PromptKind),
+    HandlePromptInputEvent(Event),
+    SubmitPrompt(PromptKind),
+    Confirm,
     ExtractMetadata,
     AddTaskSingle,
     CloseModal,
+    ShowHelp,
+    ScrollDown,
+    ScrollUp,
+    ScrollTop,
+    ScrollBottom,
+    ShowPalette,
+    HandlePaletteInputEvent(Event),
+    RunPaletteCommand,
+    /// Run the command line a plugin bound to keys, by its index in the keymap
+    RunBoundCommand(usize),
+    /// Run a command a plugin registered, with the arguments typed after its name
+    RunPluginCommand(String, String),
+    PrefillAddTask(String),
+    /// Add a task for the URL right away, named by yt-dlp
+    EnqueueUrl(String),
+    /// Add a task made elsewhere, e.g. by the browser's native messaging host
+    AddTask(Box<DownloadTask>),
+    ApplyPrompt(PromptKind, String),
+    ExportSelectedTo(PathBuf),
+    PauseAll,
+    ResumeAll,
+    SwitchTab(ActiveTab),
+    SetTheme(String),
+    SetRateLimit(Option<String>),
+    ShowDetails,
+    Mouse(MouseEvent),
+    ToggleLog,
+    /// A warning or error was written to the app log
+    Logged,
 
-    UpdateDownloadStatus(DownloadTask),
-}
-
-impl Message {
-    pub fn from_event(
-        event: Event,
-        active_panel: &ActivePanel,
-        active_tab: &ActiveTab,
-    ) -> Option<Self> {
-        if let Event::Key(key) = event {
-            if key.kind != KeyEventKind::Press {
-                return None;
-            }
-
-            match key.code {
-                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    return Some(Message::Quit);
-                }
-                KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    return Some(Message::ShowAddTaskModal);
-                }
-                _ => {}
-            }
-
-            match active_panel {
-                ActivePanel::Content => match key.code {
-                    KeyCode::Char('a') => Some(Message::ShowAddTaskModal),
-                    KeyCode::Char('q') => Some(Message::Quit),
-                    KeyCode::Left | KeyCode::Char('h')
-                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
-                    {
-                        Some(Message::FocusMenu)
-                    }
-                    KeyCode::Tab | KeyCode::Char('L') => Some(Message::SwitchNextTab),
-                    KeyCode::BackTab | KeyCode::Char('H') => Some(Message::SwitchPreviousTab),
-                    KeyCode::Up | KeyCode::Char('k') => match active_tab {
-                        ActiveTab::Single => Some(Message::SelectPreviousRowSingle),
-                        ActiveTab::Batch => Some(Message::SelectPreviousRowBatch),
-                        ActiveTab::Playlist => Some(Message::SelectPreviousRowPlaylist),
-                        _ => None,
-                    },
-                    KeyCode::Down | KeyCode::Char('j') => match active_tab {
-                        ActiveTab::Single => Some(Message::SelectNextRowSingle),
-                        ActiveTab::Batch => Some(Message::SelectNextRowBatch),
-                        ActiveTab::Playlist => Some(Message::SelectNextRowPlaylist),
-                        _ => None,
-                    },
-                    KeyCode::Right => Some(Message::ProgressUp),
-                    KeyCode::Left => Some(Message::ProgressDown),
-                    _ => None,
-                },
-
-                ActivePanel::Menu => match key.code {
-                    KeyCode::Char('a') => Some(Message::ShowAddTaskModal),
-                    KeyCode::Char('q') => Some(Message::Quit),
-                    KeyCode::Right | KeyCode::Char('l')
-                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
-                    {
-                        Some(Message::FocusContent)
-                    }
-                    KeyCode::Enter => match active_tab {
-                        ActiveTab::Single => Some(Message::ApplyCategoryFilterSingle),
-                        ActiveTab::Batch => Some(Message::ApplyCategoryFilterBatch),
-                        ActiveTab::Playlist => Some(Message::ApplyCategoryFilterPlaylist),
-                        _ => None,
-                    },
-                    KeyCode::Char(' ') => Some(Message::ToggleSelected),
-                    KeyCode::Left | KeyCode::Char('h') => Some(Message::CollapseMenuItem),
-                    KeyCode::Right | KeyCode::Char('l') => Some(Message::ExpandMenuItem),
-                    KeyCode::Down | KeyCode::Char('j') => Some(Message::SelectNextMenuItem),
-                    KeyCode::Up | KeyCode::Char('k') => Some(Message::SelectPrevMenuItem),
-                    KeyCode::Home => Some(Message::SelectFirstMenuItem),
-                    KeyCode::End => Some(Message::SelectLastMenuItem),
-                    _ => None,
-                },
-
-                ActivePanel::Modal(modal_type) => match modal_type {
-                    ModalType::SourceInput => match key.code {
-                        KeyCode::Esc => Some(Message::CloseModal),
-                        KeyCode::Enter => match active_tab {
-                            ActiveTab::Single => Some(Message::ExtractMetadata),
-                            _ => None,
-                        },
-                        _ => Some(Message::HandleSourceInputEvent(event)),
-                    },
-
-                    ModalType::DestinationInput => match key.code {
-                        KeyCode::Esc => Some(Message::CloseModal),
-                        KeyCode::Enter => Some(Message::AddTaskSingle),
-
-                        KeyCode::Up | KeyCode::Down | KeyCode::Tab | KeyCode::BackTab => {
-                            Some(Message::ToggleFocusedInput)
-                        }
-                        _ => Some(Message::HandleDestinationInputEvent(event)),
-                    },
-
-                    ModalType::Info => match key.code {
-                        KeyCode::Esc => Some(Message::CloseModal),
-                        _ => None,
-                    },
-
-                    ModalType::Error => match key.code {
-                        KeyCode::Esc => Some(Message::CloseModal),
-                        _ => None,
-                    },
+    /// Something changed in the daemon the TUI is attached to
+    Remote(Box<RemoteEvent>),
+    /// The connection to the daemon was lost
+    Detached,
 
-                    ModalType::Confirm => match key.code {
-                        KeyCode::Esc | KeyCode::Enter => Some(Message::CloseModal),
-                        _ => None,
-                    },
-                },
-            }
-        } else {
-            None
-        }
-    }
+    /// A job's progress, with the run of the job it comes from
+    UpdateDownloadStatus(Box<DownloadTask>, u64),
+    TaskOutput(u64, OutputLine),
+    UpdatePendingKeys(String),
 }
@@ -1,4 +1,8 @@
+pub mod keymap;
 pub mod message;
+pub mod mouse;
+pub mod palette;
+pub mod sequencer;
 
 use std::sync::atomic::Ordering;
 use tui_input::backend::crossterm::EventHandler;
@@ -11,7 +15,12 @@ use crate::{
 pub async fn update(model: &mut Model, msg: Message) {
     match msg {
         Message::Quit => {
-            model.downloader.save().expect("failed to save tasks");
+            // while attached the daemon owns tasks.json
+            if model.remote.is_none()
+                && let Err(e) = model.downloader.save()
+            {
+                log::error!("Failed to save the tasks. {e}");
+            }
             model.running.store(false, Ordering::Relaxed);
         }
 
@@ -25,6 +34,8 @@ pub async fn update(model: &mut Model, msg: Message) {
         Message::SelectPreviousRowSingle => model.downloader.single.previous_row(),
         Message::SelectPreviousRowBatch => model.downloader.batch.previous_row(),
         Message::SelectPreviousRowPlaylist => model.downloader.playlist.previous_row(),
+        Message::SelectFirstRow => model.select_first_row().await,
+        Message::SelectLastRow => model.select_last_row().await,
         Message::ProgressUp => {
             if model.progress < 100.0 {
                 model.progress += 1.0;
@@ -39,6 +50,16 @@ pub async fn update(model: &mut Model, msg: Message) {
         Message::UpdateProgressSingle => {
             model.undate_progress_single();
         }
+        Message::RefreshCounts => model.refresh_counts().await,
+        Message::ToggleSelection => model.toggle_selection().await,
+        Message::ToggleVisualMode => model.toggle_visual_mode().await,
+        Message::ClearSelection => model.clear_selection().await,
+        Message::PauseSelected => model.pause_selected().await,
+        Message::ResumeSelected => model.resume_selected().await,
+        Message::RetrySelected => model.retry_selected().await,
+        Message::ConfirmRemoveSelected => model.confirm_remove_selected().await,
+        Message::RemoveSelected => model.remove_selected().await,
+        Message::ExportSelected => model.export_selected().await,
 
         // Menu
         Message::FocusContent => model.focus_content().await,
@@ -67,21 +88,23 @@ pub async fn update(model: &mut Model, msg: Message) {
         Message::ApplyCategoryFilterSingle => model
             .downloader
             .single
-            .filter_downloads_by_category(model.menu_state.selected().to_owned()),
+            .filter_downloads_by_category(model.menu_state.selected(), &model.config),
         Message::ApplyCategoryFilterBatch => model
             .downloader
             .batch
-            .filter_downloads_by_category(model.menu_state.selected().to_owned()),
+            .filter_downloads_by_category(model.menu_state.selected(), &model.config),
         Message::ApplyCategoryFilterPlaylist => model
             .downloader
             .playlist
-            .filter_downloads_by_category(model.menu_state.selected().to_owned()),
+            .filter_downloads_by_category(model.menu_state.selected(), &model.config),
 
         // Modal
         Message::ShowAddTaskModal => model.show_source_input_model().await,
         Message::ToggleFocusedInput => match model.input_state.focused {
             FocusedInput::Destination => model.input_state.focused = FocusedInput::Name,
             FocusedInput::Name => model.input_state.focused = FocusedInput::Destination,
+            FocusedInput::FilterName => model.input_state.focused = FocusedInput::FilterQuery,
+            FocusedInput::FilterQuery => model.input_state.focused = FocusedInput::FilterName,
         },
         Message::HandleSourceInputEvent(e) => {
             model.input_state.source.handle_event(&e);
@@ -93,7 +116,26 @@ pub async fn update(model: &mut Model, msg: Message) {
             FocusedInput::Destination => {
                 model.input_state.destination.handle_event(&e);
             }
+            _ => {}
         },
+        Message::ShowSaveFilterModal => model.show_filter_input_modal().await,
+        Message::HandleFilterInputEvent(e) => match model.input_state.focused {
+            FocusedInput::FilterName => {
+                model.input_state.filter_name.handle_event(&e);
+            }
+            FocusedInput::FilterQuery => {
+                model.input_state.filter_query.handle_event(&e);
+            }
+            _ => {}
+        },
+        Message::SaveFilter => model.save_filter().await,
+        Message::DeleteSelectedFilter => model.delete_selected_filter().await,
+        Message::ShowPromptModal(kind) => model.show_prompt_modal(kind).await,
+        Message::HandlePromptInputEvent(e) => {
+            model.input_state.prompt.handle_event(&e);
+        }
+        Message::SubmitPrompt(kind) => model.submit_prompt(kind).await,
+        Message::Confirm => model.confirm().await,
         Message::ExtractMetadata => {
             model.extract_metadata().await;
         }
@@ -102,10 +144,48 @@ pub async fn update(model: &mut Model, msg: Message) {
             model.add_task_single().await;
         }
 
-        Message::UpdateDownloadStatus(task) => {
-            model.downloader.single.update_download(task);
+        Message::UpdateDownloadStatus(task, run) => {
+            if model.downloader.update_download(*task, run) {
+                model.refresh_menu_items().await;
+            }
+        }
+        Message::TaskOutput(id, line) => {
+            if let Some(manager) = model.downloader.manager_of_mut(id) {
+                manager.push_output(id, line);
+            }
         }
+        Message::UpdatePendingKeys(keys) => model.pending_keys = keys,
+        Message::Remote(event) => model.apply_remote(*event).await,
+        Message::Detached => model.detach().await,
 
         Message::CloseModal => model.close_modal().await,
+        Message::ShowHelp => model.show_help().await,
+        Message::ScrollDown => model.scroll(1).await,
+        Message::ScrollUp => model.scroll(-1).await,
+        Message::ScrollTop => model.scroll_to_end(false).await,
+        Message::ScrollBottom => model.scroll_to_end(true).await,
+        Message::ShowPalette => model.show_palette().await,
+        Message::HandlePaletteInputEvent(e) => model.handle_palette_input_event(&e),
+        Message::RunPaletteCommand => model.run_palette_command().await,
+        Message::RunBoundCommand(index) => model.run_bound_command(index).await,
+        Message::RunPluginCommand(name, args) => model.run_plugin_command(&name, &args).await,
+        Message::PrefillAddTask(source) => model.prefill_add_task(source).await,
+        Message::EnqueueUrl(source) => model.enqueue_url(source).await,
+        Message::AddTask(task) => model.start_task(*task).await,
+        Message::ApplyPrompt(kind, value) => model.apply_prompt(kind, value).await,
+        Message::ExportSelectedTo(directory) => model.export_selected_to(directory).await,
+        Message::PauseAll => model.pause_all().await,
+        Message::ResumeAll => model.resume_all().await,
+        Message::SwitchTab(tab) => model.switch_tab(tab).await,
+        Message::SetTheme(name) => model.set_theme(name).await,
+        Message::SetRateLimit(rate) => model.set_rate_limit(rate).await,
+        Message::ShowDetails => model.show_details().await,
+        Message::Mouse(event) => mouse::handle(model, event).await,
+        Message::ToggleLog => model.toggle_log(),
+        Message::Logged => {
+            if !model.show_log {
+                model.unseen_log += 1;
+            }
+        }
     }
 }
@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Position;
+
+use crate::{
+    model::state::{ActivePanel, ActiveTab, ModalType, Model},
+    update::{keymap::Action, message::Message},
+    view::{
+        components::{table, tabs},
+        layout::LayoutAreas,
+    },
+};
+
+/// Two clicks on the same cell within this time are a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Hit-test a mouse event against the areas of the last drawn frame
+pub async fn handle(model: &mut Model, event: MouseEvent) {
+    let active_panel = *model.active_panel.read().await;
+    let active_tab = *model.active_tab.read().await;
+    let layout = LayoutAreas::compute(model.screen, model.show_log);
+    let position = Position::new(event.column, event.row);
+
+    match event.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            let double = model
+                .last_click
+                .is_some_and(|(at, p)| p == position && at.elapsed() < DOUBLE_CLICK);
+            model.last_click = (!double).then(|| (Instant::now(), position));
+
+            click(model, &layout, position, double, &active_panel, &active_tab).await;
+        }
+        MouseEventKind::ScrollDown => {
+            scroll(model, &layout, position, 1, &active_panel, &active_tab).await
+        }
+        MouseEventKind::ScrollUp => {
+            scroll(model, &layout, position, -1, &active_panel, &active_tab).await
+        }
+        _ => {}
+    }
+}
+
+async fn click(
+    model: &mut Model,
+    layout: &LayoutAreas,
+    position: Position,
+    double: bool,
+    active_panel: &ActivePanel,
+    active_tab: &ActiveTab,
+) {
+    let tx = model.message_tx.clone().unwrap();
+
+    // modals take all the clicks while open, only the input modals have buttons
+    if let ActivePanel::Modal(modal_type) = active_panel {
+        if let ModalType::SourceInput
+        | ModalType::DestinationInput
+        | ModalType::FilterInput
+        | ModalType::Prompt(_) = modal_type
+        {
+            let [cancel, submit] = LayoutAreas::modal_buttons(layout.modal);
+            if cancel.contains(position) {
+                let _ = tx.send(Message::CloseModal);
+            } else if submit.contains(position)
+                && let Some(message) = Action::Submit.message(active_panel, active_tab)
+            {
+                let _ = tx.send(message);
+            }
+        }
+        return;
+    }
+
+    if layout.action_button.contains(position) {
+        let _ = tx.send(Message::ShowAddTaskModal);
+    } else if layout.tabs.contains(position) {
+        if let Some(tab) = tabs::tab_at(layout.tabs, position.x).and_then(ActiveTab::from_index) {
+            model.switch_tab(tab).await;
+        }
+    } else if layout.menu.contains(position) {
+        model.focus_menu().await;
+        if model.menu_state.click_at(position)
+            && let Some(message) = Action::ApplyFilter.message(&ActivePanel::Menu, active_tab)
+        {
+            let _ = tx.send(message);
+        }
+    } else if layout.content.contains(position) {
+        model.focus_content().await;
+        let manager = model.downloader.manager_mut(active_tab);
+        let offset = manager.state.table_state.offset();
+        if let Some(row) = table::row_at(layout.content, offset, position.y)
+            && row < manager.state.filtered_downloads.len()
+        {
+            manager.select_row(row);
+            if double {
+                let _ = tx.send(Message::ShowDetails);
+            }
+        }
+    }
+}
+
+async fn scroll(
+    model: &mut Model,
+    layout: &LayoutAreas,
+    position: Position,
+    lines: i32,
+    active_panel: &ActivePanel,
+    active_tab: &ActiveTab,
+) {
+    match active_panel {
+        ActivePanel::Modal(_) => model.scroll(lines).await,
+        _ if layout.menu.contains(position) => {
+            if lines > 0 {
+                model.menu_state.scroll_down(lines as usize);
+            } else {
+                model.menu_state.scroll_up(lines.unsigned_abs() as usize);
+            }
+        }
+        _ if layout.content.contains(position) => {
+            let manager = model.downloader.manager_mut(active_tab);
+            if lines > 0 {
+                manager.next_row();
+            } else {
+                manager.previous_row();
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,316 @@
+use std::path::PathBuf;
+
+use color_eyre::{
+    Result,
+    eyre::{bail, eyre},
+};
+
+use crate::{
+    model::{
+        filter::parse_size,
+        plugins::PluginCommand,
+        state::{ActiveTab, PromptKind},
+        theme::Theme,
+    },
+    update::message::Message,
+};
+
+/// A command of the command palette, run as `name args`.
+pub struct Command {
+    pub name: &'static str,
+    /// Shown next to the name, e.g. `<dir>` or `[url]` for an optional argument
+    pub args: &'static str,
+    pub description: &'static str,
+    run: fn(&str) -> Result<Vec<Message>>,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "add",
+        args: "[url]",
+        description: "add a task",
+        run: |url| {
+            Ok(vec![if url.is_empty() {
+                Message::ShowAddTaskModal
+            } else {
+                Message::PrefillAddTask(url.to_string())
+            }])
+        },
+    },
+    Command {
+        name: "pause",
+        args: "",
+        description: "pause the selected tasks",
+        run: |_| Ok(vec![Message::PauseSelected]),
+    },
+    Command {
+        name: "pause-all",
+        args: "",
+        description: "pause every running task",
+        run: |_| Ok(vec![Message::PauseAll]),
+    },
+    Command {
+        name: "resume",
+        args: "",
+        description: "resume the selected tasks",
+        run: |_| Ok(vec![Message::ResumeSelected]),
+    },
+    Command {
+        name: "resume-all",
+        args: "",
+        description: "resume every paused task",
+        run: |_| Ok(vec![Message::ResumeAll]),
+    },
+    Command {
+        name: "retry",
+        args: "",
+        description: "start the selected tasks over",
+        run: |_| Ok(vec![Message::RetrySelected]),
+    },
+    Command {
+        name: "remove",
+        args: "",
+        description: "remove the selected tasks",
+        run: |_| Ok(vec![Message::ConfirmRemoveSelected]),
+    },
+    Command {
+        name: "dest",
+        args: "<dir>",
+        description: "move the selected tasks",
+        run: |dir| {
+            if dir.is_empty() {
+                bail!("dest needs a directory");
+            }
+            Ok(vec![Message::ApplyPrompt(
+                PromptKind::Destination,
+                expand_home(dir).to_string_lossy().to_string(),
+            )])
+        },
+    },
+    Command {
+        name: "tag",
+        args: "<tags>",
+        description: "tag the selected tasks, -tag removes a tag",
+        run: |tags| {
+            if tags.is_empty() {
+                bail!("tag needs at least one tag");
+            }
+            Ok(vec![Message::ApplyPrompt(
+                PromptKind::Tags,
+                tags.to_string(),
+            )])
+        },
+    },
+    Command {
+        name: "connections",
+        args: "<n|default>",
+        description: "split the selected direct downloads into n connections",
+        run: |value| {
+            if value.is_empty() {
+                bail!("connections needs a number from 1 to 32, or default");
+            }
+            Ok(vec![Message::ApplyPrompt(
+                PromptKind::Connections,
+                value.to_string(),
+            )])
+        },
+    },
+    Command {
+        name: "backend",
+        args: "<name|auto>",
+        description: "download the selected tasks with yt-dlp, http or aria2 from their next run on",
+        run: |value| {
+            if value.is_empty() {
+                bail!("backend needs yt-dlp, http, aria2 or auto");
+            }
+            Ok(vec![Message::ApplyPrompt(
+                PromptKind::Backend,
+                value.to_string(),
+            )])
+        },
+    },
+    Command {
+        name: "export",
+        args: "[dir]",
+        description: "export the selected tasks as JSON",
+        run: |dir| {
+            Ok(vec![if dir.is_empty() {
+                Message::ExportSelected
+            } else {
+                Message::ExportSelectedTo(expand_home(dir))
+            }])
+        },
+    },
+    Command {
+        name: "limit",
+        args: "<rate|off>",
+        description: "limit the download rate of new jobs, e.g. 2M",
+        run: |rate| match rate {
+            "" => bail!("limit needs a rate such as 2M, or off"),
+            "off" | "none" | "0" => Ok(vec![Message::SetRateLimit(None)]),
+            rate if parse_size(rate).is_some() => {
+                Ok(vec![Message::SetRateLimit(Some(rate.to_string()))])
+            }
+            rate => Err(eyre!(
+                "invalid rate `{rate}`, use a size such as 500K or 2M"
+            )),
+        },
+    },
+    Command {
+        name: "theme",
+        args: "<name>",
+        description: "switch the color theme",
+        run: |name| match Theme::by_name(name) {
+            Some(_) => Ok(vec![Message::SetTheme(name.to_string())]),
+            None => Err(eyre!(
+                "unknown theme `{name}`, available: {}",
+                Theme::NAMES.join(", ")
+            )),
+        },
+    },
+    Command {
+        name: "settings",
+        args: "",
+        description: "open the settings",
+        run: |_| Ok(vec![Message::SwitchTab(ActiveTab::Settings)]),
+    },
+    Command {
+        name: "tab",
+        args: "<name>",
+        description: "jump to a tab",
+        run: |name| {
+            let tab = match name.to_lowercase().as_str() {
+                "single" => ActiveTab::Single,
+                "batch" => ActiveTab::Batch,
+                "playlist" => ActiveTab::Playlist,
+                "settings" => ActiveTab::Settings,
+                "about" => ActiveTab::About,
+                _ => bail!("unknown tab `{name}`, use single, batch, playlist, settings or about"),
+            };
+            Ok(vec![Message::SwitchTab(tab)])
+        },
+    },
+    Command {
+        name: "save-filter",
+        args: "",
+        description: "save a filter to the sidebar",
+        run: |_| Ok(vec![Message::ShowSaveFilterModal]),
+    },
+    Command {
+        name: "log",
+        args: "",
+        description: "show/hide the warnings and errors",
+        run: |_| Ok(vec![Message::ToggleLog]),
+    },
+    Command {
+        name: "help",
+        args: "",
+        description: "show the keys",
+        run: |_| Ok(vec![Message::ShowHelp]),
+    },
+    Command {
+        name: "quit",
+        args: "",
+        description: "quit",
+        run: |_| Ok(vec![Message::Quit]),
+    },
+];
+
+/// An item of the palette list
+#[derive(Clone, Copy)]
+pub enum Entry<'a> {
+    /// A previously run command line
+    History(&'a str),
+    Command(&'static Command),
+    /// A command registered by a plugin
+    Plugin(&'a PluginCommand),
+}
+
+impl Entry<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Entry::History(line) => line,
+            Entry::Command(command) => command.name,
+            Entry::Plugin(command) => &command.name,
+        }
+    }
+}
+
+/// Commands fuzzy matching the first word of `query`, best first, the plugins' commands
+/// after the built-in ones. Without a query, the history comes first followed by every
+/// command.
+pub fn entries<'a>(
+    query: &str,
+    history: &'a [String],
+    plugins: &'a [PluginCommand],
+) -> Vec<Entry<'a>> {
+    let commands = COMMANDS
+        .iter()
+        .map(Entry::Command)
+        .chain(plugins.iter().map(Entry::Plugin));
+    let name = query.split_whitespace().next().unwrap_or_default();
+    if name.is_empty() {
+        return history
+            .iter()
+            .map(|line| Entry::History(line))
+            .chain(commands)
+            .collect();
+    }
+
+    let mut matches = commands
+        .filter_map(|entry| fuzzy_score(name, entry.name()).map(|score| (score, entry)))
+        .collect::<Vec<_>>();
+    matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Parse a command line such as `limit 2M` into the messages to dispatch. The commands of
+/// the plugins run in the model, as `Message::RunPluginCommand`.
+pub fn parse(line: &str, plugins: &[PluginCommand]) -> Result<Vec<Message>> {
+    let line = line.trim().trim_start_matches(':');
+    let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+    if let Some(command) = COMMANDS.iter().find(|c| c.name == name) {
+        return (command.run)(args.trim());
+    }
+    if plugins.iter().any(|c| c.name == name) {
+        return Ok(vec![Message::RunPluginCommand(
+            name.to_string(),
+            args.trim().to_string(),
+        )]);
+    }
+    bail!("unknown command `{name}`")
+}
+
+/// Score how well `pattern` matches `text` as a subsequence, `None` if it doesn't.
+/// Consecutive characters and matches at the start of a word score higher.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i32> {
+    let text = text.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+
+    for p in pattern.chars().map(|c| c.to_ascii_lowercase()) {
+        let i = (next..text.len()).find(|&i| text[i] == p)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || text[i - 1] == '-' {
+            score += 10;
+        }
+        previous = Some(i);
+        next = i + 1;
+    }
+
+    // prefer the shorter of otherwise equal matches, `pause` over `pause-all`
+    Some(score * 100 - text.len() as i32)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~") {
+        Some(rest) => dirs::home_dir()
+            .unwrap_or_default()
+            .join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
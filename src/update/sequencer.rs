@@ -0,0 +1,361 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::{
+    model::state::{ActivePanel, ActiveTab},
+    update::{
+        keymap::{Action, Context, KeyPress, Keymap},
+        message::Message,
+    },
+};
+
+/// How long to wait for the next key of a sequence before giving up on it.
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Counts are clamped so a stray `99999j` doesn't flood the message queue.
+const MAX_COUNT: usize = 999;
+
+/// Turns key events into messages, resolving multi-key sequences and count prefixes.
+///
+/// Keys are collected until they match a binding of the current context. When a sequence is
+/// both a binding and the prefix of a longer one (`g` and `gg`), the sequencer waits for the
+/// next key or the timeout before deciding. In text inputs, keys that don't resolve to a
+/// binding are passed on to the input.
+pub struct KeySequencer {
+    keymap: Keymap,
+    pending: Vec<KeyEvent>,
+    count: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+enum Lookup {
+    Exact(Action),
+    /// A binding, but also the prefix of a longer one
+    Ambiguous(Action),
+    Prefix,
+    None,
+}
+
+impl KeySequencer {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            pending: Vec::new(),
+            count: None,
+            deadline: None,
+        }
+    }
+
+    /// Pending count and keys, as shown in the status line
+    pub fn pending(&self) -> String {
+        let count = self.count.map(|c| c.to_string()).unwrap_or_default();
+        let keys = self
+            .pending
+            .iter()
+            .map(|key| KeyPress::from(*key).to_string())
+            .collect::<String>();
+        count + &keys
+    }
+
+    /// Time left until the pending sequence expires, if there is one
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn handle(
+        &mut self,
+        event: Event,
+        active_panel: &ActivePanel,
+        active_tab: &ActiveTab,
+    ) -> Vec<Message> {
+        let Event::Key(key) = event else {
+            return Vec::new();
+        };
+        if key.kind != KeyEventKind::Press {
+            return Vec::new();
+        }
+
+        let context = Context::from(active_panel);
+
+        if self.pending.is_empty()
+            && !context.is_text_input()
+            && let Some(count) = self.push_count(context, key)
+        {
+            self.count = Some(count);
+            self.deadline = Some(Instant::now() + SEQUENCE_TIMEOUT);
+            return Vec::new();
+        }
+
+        self.pending.push(key);
+
+        match self.lookup(context, &self.pending) {
+            Lookup::Exact(action) => self.fire(action, active_panel, active_tab),
+            Lookup::Ambiguous(_) | Lookup::Prefix => {
+                self.deadline = Some(Instant::now() + SEQUENCE_TIMEOUT);
+                Vec::new()
+            }
+            Lookup::None if self.pending.len() > 1 => {
+                // the sequence went nowhere, drop it and start over from the last key
+                let mut messages = self.flush(context, 1);
+                messages.extend(self.handle(event, active_panel, active_tab));
+                messages
+            }
+            Lookup::None => self.flush(context, 0),
+        }
+    }
+
+    /// Resolves the pending sequence once the timeout has passed
+    pub fn expire(&mut self, active_panel: &ActivePanel, active_tab: &ActiveTab) -> Vec<Message> {
+        if self
+            .remaining()
+            .is_none_or(|remaining| !remaining.is_zero())
+        {
+            return Vec::new();
+        }
+
+        let context = Context::from(active_panel);
+        match self.lookup(context, &self.pending) {
+            Lookup::Exact(action) | Lookup::Ambiguous(action) => {
+                self.fire(action, active_panel, active_tab)
+            }
+            _ => self.flush(context, 0),
+        }
+    }
+
+    /// Digits form a count unless they are bound themselves, `0` never starts a count
+    fn push_count(&self, context: Context, key: KeyEvent) -> Option<usize> {
+        let KeyCode::Char(c @ '0'..='9') = key.code else {
+            return None;
+        };
+        if !(key.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return None;
+        }
+        if c == '0' && self.count.is_none() {
+            return None;
+        }
+        if !matches!(self.lookup(context, &[key]), Lookup::None) {
+            return None;
+        }
+
+        let digit = c.to_digit(10).unwrap_or_default() as usize;
+        let count = self.count.unwrap_or_default() * 10 + digit;
+        Some(count.min(MAX_COUNT))
+    }
+
+    fn lookup(&self, context: Context, keys: &[KeyEvent]) -> Lookup {
+        let pending = keys
+            .iter()
+            .map(|key| KeyPress::from(*key))
+            .collect::<Vec<_>>();
+
+        let bindings = self
+            .keymap
+            .bindings(Context::Global)
+            .iter()
+            .chain(self.keymap.bindings(context));
+
+        let mut exact = None;
+        let mut prefix = false;
+        for (keys, action) in bindings {
+            if keys.0 == pending {
+                exact = exact.or(Some(*action));
+            } else if keys.0.starts_with(&pending) {
+                prefix = true;
+            }
+        }
+
+        match (exact, prefix) {
+            (Some(action), false) => Lookup::Exact(action),
+            (Some(action), true) => Lookup::Ambiguous(action),
+            (None, true) => Lookup::Prefix,
+            (None, false) => Lookup::None,
+        }
+    }
+
+    fn fire(
+        &mut self,
+        action: Action,
+        active_panel: &ActivePanel,
+        active_tab: &ActiveTab,
+    ) -> Vec<Message> {
+        let count = if action.repeatable() {
+            self.count.unwrap_or(1)
+        } else {
+            1
+        };
+        self.reset();
+
+        action
+            .message(active_panel, active_tab)
+            .map(|message| vec![message; count])
+            .unwrap_or_default()
+    }
+
+    /// Drops the pending sequence, typing its keys into the input when the context has one.
+    /// The last `keep` keys are left out.
+    fn flush(&mut self, context: Context, keep: usize) -> Vec<Message> {
+        let keys = self.pending.len().saturating_sub(keep);
+        let messages = self
+            .pending
+            .drain(..keys)
+            .filter_map(|key| context.fallback(Event::Key(key)))
+            .collect();
+        self.reset();
+        messages
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+        self.deadline = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::model::state::{ModalType, PromptKind};
+
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+
+    /// Feed the keys to the sequencer, returning the messages they produced
+    fn typed(sequencer: &mut KeySequencer, panel: ActivePanel, keys: &str) -> Vec<String> {
+        keys.chars()
+            .flat_map(|c| sequencer.handle(key(c), &panel, &ActiveTab::Single))
+            .map(|message| format!("{message:?}"))
+            .collect()
+    }
+
+    fn expired(sequencer: &mut KeySequencer, panel: ActivePanel) -> Vec<String> {
+        sequencer.deadline = Some(Instant::now());
+        sequencer
+            .expire(&panel, &ActiveTab::Single)
+            .into_iter()
+            .map(|message| format!("{message:?}"))
+            .collect()
+    }
+
+    fn sequencer() -> KeySequencer {
+        KeySequencer::new(Keymap::default())
+    }
+
+    #[test]
+    fn resolves_multi_key_sequences() {
+        let mut sequencer = sequencer();
+        assert!(typed(&mut sequencer, ActivePanel::Content, "g").is_empty());
+        assert_eq!(sequencer.pending(), "g");
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "g"),
+            ["SelectFirstRow"]
+        );
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "dd"),
+            ["ConfirmRemoveSelected"]
+        );
+        assert_eq!(sequencer.pending(), "");
+        assert!(sequencer.remaining().is_none());
+    }
+
+    #[test]
+    fn repeats_actions_by_count() {
+        let mut sequencer = sequencer();
+        assert!(typed(&mut sequencer, ActivePanel::Content, "5").is_empty());
+        assert_eq!(sequencer.pending(), "5");
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "j"),
+            ["SelectNextRowSingle"; 5]
+        );
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "12k"),
+            ["SelectPreviousRowSingle"; 12]
+        );
+        // counts are clamped
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "123456j").len(),
+            MAX_COUNT
+        );
+        // and don't repeat actions that aren't meant to
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "3gg"),
+            ["SelectFirstRow"]
+        );
+    }
+
+    #[test]
+    fn zero_only_continues_a_count() {
+        let mut sequencer = sequencer();
+        assert!(typed(&mut sequencer, ActivePanel::Content, "0").is_empty());
+        assert_eq!(sequencer.pending(), "");
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "10j"),
+            ["SelectNextRowSingle"; 10]
+        );
+    }
+
+    #[test]
+    fn waits_on_an_ambiguous_prefix_until_the_timeout() {
+        let keys = BTreeMap::from([(
+            "content".to_string(),
+            BTreeMap::from([("g".to_string(), "last_row".to_string())]),
+        )]);
+        let (keymap, _) = Keymap::from_config(&keys);
+        let mut sequencer = KeySequencer::new(keymap);
+
+        assert!(typed(&mut sequencer, ActivePanel::Content, "g").is_empty());
+        assert!(sequencer.remaining().is_some());
+        assert!(
+            sequencer
+                .expire(&ActivePanel::Content, &ActiveTab::Single)
+                .is_empty(),
+            "expired early"
+        );
+        assert_eq!(
+            expired(&mut sequencer, ActivePanel::Content),
+            ["SelectLastRow"]
+        );
+        assert!(sequencer.remaining().is_none());
+
+        // the longer binding still wins when typed in time
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "gg"),
+            ["SelectFirstRow"]
+        );
+    }
+
+    #[test]
+    fn drops_sequences_that_go_nowhere() {
+        let mut sequencer = sequencer();
+        // `gx` isn't bound, the `x` is tried on its own
+        assert!(typed(&mut sequencer, ActivePanel::Content, "gx").is_empty());
+        assert_eq!(
+            typed(&mut sequencer, ActivePanel::Content, "gj"),
+            ["SelectNextRowSingle"]
+        );
+        assert!(typed(&mut sequencer, ActivePanel::Content, "g").is_empty());
+        assert!(expired(&mut sequencer, ActivePanel::Content).is_empty());
+        assert_eq!(sequencer.pending(), "");
+    }
+
+    #[test]
+    fn falls_back_to_text_input() {
+        let mut sequencer = sequencer();
+        let prompt = ActivePanel::Modal(ModalType::Prompt(PromptKind::Tags));
+        let messages = typed(&mut sequencer, prompt, "5gg");
+        assert_eq!(messages.len(), 3);
+        assert!(
+            messages
+                .iter()
+                .all(|m| m.starts_with("HandlePromptInputEvent"))
+        );
+
+        let escape = Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        let messages = sequencer.handle(escape, &prompt, &ActiveTab::Single);
+        assert_eq!(format!("{messages:?}"), "[CloseModal]");
+    }
+}
@@ -6,7 +6,10 @@ use ratatui::{
     widgets::{Block, Clear, Paragraph, Widget},
 };
 
-use crate::model::state::{FocusedInput, Model};
+use crate::{
+    model::state::{FocusedInput, Model},
+    view::layout::LayoutAreas,
+};
 
 pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
     Clear.render(area, frame.buffer_mut());
@@ -76,20 +79,10 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
                 + 2;
             frame.set_cursor_position((layout[2].x + x as u16, layout[2].y + 1));
         }
+        _ => {}
     }
 
-    // weird trick to center but all I know is constraint!
-    let button_layout = Layout::horizontal(vec![
-        Constraint::Min(1),
-        Constraint::Length(14), //button
-        Constraint::Length(1),  // spacer
-        Constraint::Length(14), //button
-        Constraint::Min(1),
-    ])
-    .split(layout[4].inner(Margin {
-        horizontal: 1,
-        vertical: 1,
-    }));
+    let [cancel_area, submit_area] = LayoutAreas::modal_buttons(area);
 
     let cancel_button = Paragraph::new("CENCEL 󱊷")
         .style(Style::default().fg(model.theme.destructive))
@@ -101,6 +94,6 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
         .alignment(ratatui::layout::Alignment::Center)
         .block(Block::bordered());
 
-    frame.render_widget(cancel_button, button_layout[1]);
-    frame.render_widget(submit_button, button_layout[3]);
+    frame.render_widget(cancel_button, cancel_area);
+    frame.render_widget(submit_button, submit_area);
 }
@@ -0,0 +1,201 @@
+/// detail pane of a task, shown over the table
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Widget},
+};
+
+use crate::model::{downloader::format_timestamp, http::format_bytes, state::Model};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
+    Clear.render(area, frame.buffer_mut());
+
+    let id = model.details.task;
+    let Some(manager) = model.downloader.manager_of(id) else {
+        return;
+    };
+    let Some(task) = manager.task(id) else {
+        return;
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(Style::default().fg(model.theme.border_active))
+        .title(Line::from(vec![
+            Span::from("[ "),
+            Span::styled("DETAILS", Style::default().fg(model.theme.primary)),
+            Span::from(" ]"),
+        ]))
+        .style(match model.theme.background {
+            Some(color) => Style::default().bg(color).fg(model.theme.forground),
+            None => Style::default().fg(model.theme.forground),
+        });
+    frame.render_widget(block, area);
+
+    let inner = area.inner(Margin::new(2, 1));
+    let value_width = (inner.width as usize).saturating_sub(10).max(1);
+
+    // values are wrapped by hand so the height of the fields is known up front
+    let field = |name: &str, value: String| {
+        let value = if value.is_empty() {
+            "-".to_string()
+        } else {
+            value
+        };
+        let chars = value.chars().collect::<Vec<_>>();
+        chars
+            .chunks(value_width)
+            .enumerate()
+            .map(|(i, chunk)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:<10}", if i == 0 { name } else { "" }),
+                        Style::default().fg(model.theme.muted),
+                    ),
+                    Span::from(chunk.iter().collect::<String>()),
+                ])
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut progress = format!("{} {:.0}%", task.status.as_str(), task.progress_percent);
+    for value in [&task.file_size, &task.download_speed, &task.estimated_time] {
+        if !value.is_empty() {
+            progress.push_str(&format!("  {value}"));
+        }
+    }
+
+    // one bar per byte range of a segmented native download
+    let segments = task
+        .segments
+        .iter()
+        .enumerate()
+        .flat_map(|(i, segment)| {
+            let ratio = segment.downloaded as f64 / segment.len().max(1) as f64;
+            let filled = ((ratio * 20.0).round() as usize).min(20);
+            field(
+                if i == 0 { "Segments" } else { "" },
+                format!(
+                    "{}{} {:>3.0}%  {}/{}",
+                    "█".repeat(filled),
+                    "░".repeat(20 - filled),
+                    ratio * 100.0,
+                    format_bytes(segment.downloaded),
+                    format_bytes(segment.len()),
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut lines = [
+        vec![
+            Line::from(Span::styled(
+                task.title.clone(),
+                Style::default().fg(model.theme.primary),
+            )),
+            Line::default(),
+        ],
+        field("Status", progress),
+        segments,
+        field("URL", task.source_url.clone()),
+        field("Site", task.site()),
+        field(
+            "Backend",
+            task.backend
+                .map(|backend| backend.to_string())
+                .unwrap_or_else(|| "auto".to_string()),
+        ),
+        field("Path", task.destination_path.display().to_string()),
+        field(
+            "Type",
+            task.mime_type.clone().unwrap_or_else(|| "-".to_string()),
+        ),
+        field("Tags", task.tags.join(", ")),
+        field("Added", format_timestamp(task.added_at)),
+        field("Started", format_timestamp(task.started_at)),
+        field("Finished", format_timestamp(task.finished_at)),
+        field("ID", task.id().to_string()),
+        field(
+            "Log",
+            task.log_path
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+        ),
+    ]
+    .concat();
+    if !task.command.is_empty() {
+        lines.extend(field("Command", task.command_line()));
+    }
+    let height = lines.len() as u16;
+
+    let layout =
+        Layout::vertical(vec![Constraint::Length(height), Constraint::Min(3)]).split(inner);
+
+    frame.render_widget(Paragraph::new(lines), layout[0]);
+
+    let output = manager
+        .output(id)
+        .map(|output| {
+            output
+                .iter()
+                .map(|line| {
+                    Line::styled(
+                        line.text.clone(),
+                        Style::default().fg(if line.stderr {
+                            model.theme.destructive
+                        } else {
+                            model.theme.forground
+                        }),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let visible = layout[1].height.saturating_sub(2);
+    let max_scroll = (output.len() as u16).saturating_sub(visible);
+    let following = model
+        .details
+        .scroll
+        .is_none_or(|scroll| scroll >= max_scroll);
+    let scroll = if following {
+        max_scroll
+    } else {
+        model.details.scroll.unwrap_or_default()
+    };
+    model.details.max_scroll = max_scroll;
+
+    let output_block = Block::default()
+        .borders(Borders::TOP | Borders::BOTTOM)
+        .border_style(Style::default().fg(model.theme.border))
+        .title(Line::from(vec![
+            Span::from("[ "),
+            Span::styled("OUTPUT", Style::default().fg(model.theme.primary)),
+            Span::from(" ]"),
+        ]))
+        .title(
+            Line::from(Span::styled(
+                if following {
+                    " following "
+                } else {
+                    " scrolled "
+                },
+                Style::default().fg(model.theme.muted),
+            ))
+            .right_aligned(),
+        );
+
+    let paragraph = if output.is_empty() {
+        Paragraph::new(Line::styled(
+            "No output yet",
+            Style::default().fg(model.theme.muted),
+        ))
+    } else {
+        Paragraph::new(output).scroll((scroll, 0))
+    };
+    frame.render_widget(paragraph.block(output_block), layout[1]);
+}
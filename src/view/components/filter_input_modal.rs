@@ -0,0 +1,114 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget, Wrap},
+};
+
+use crate::{
+    model::state::{FocusedInput, Model},
+    view::layout::LayoutAreas,
+};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
+    Clear.render(area, frame.buffer_mut());
+
+    let block = Block::bordered()
+        .border_style(Style::default().fg(model.theme.border_active))
+        .title(
+            Line::from(vec![
+                Span::from("[ "),
+                Span::styled("SAVE A FILTER", Style::default().fg(model.theme.primary)),
+                Span::from(" ]"),
+            ])
+            .centered(),
+        )
+        .style(match model.theme.background {
+            Some(color) => Style::default().bg(color).fg(model.theme.forground),
+            None => Style::default().fg(model.theme.forground),
+        });
+
+    frame.render_widget(block, area);
+
+    let layout = Layout::vertical(vec![
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Length(3),
+        Constraint::Min(1),
+        Constraint::Length(5),
+    ])
+    .split(area);
+
+    let width = layout[1].width.max(3) - 5;
+    let name_scroll = model.input_state.filter_name.visual_scroll(width as usize);
+    let query_scroll = model.input_state.filter_query.visual_scroll(width as usize);
+
+    let name_input = Paragraph::new(model.input_state.filter_name.value())
+        .style(Style::default().fg(match model.input_state.focused {
+            FocusedInput::FilterName => model.theme.forground,
+            _ => model.theme.muted,
+        }))
+        .scroll((0, name_scroll as u16))
+        .block(Block::bordered().title("[ Name ]"));
+
+    frame.render_widget(name_input, layout[1].inner(Margin::new(1, 0)));
+
+    let query_input = Paragraph::new(model.input_state.filter_query.value())
+        .style(Style::default().fg(match model.input_state.focused {
+            FocusedInput::FilterQuery => model.theme.forground,
+            _ => model.theme.muted,
+        }))
+        .scroll((0, query_scroll as u16))
+        .block(Block::bordered().title("[ Filter ]"));
+
+    frame.render_widget(query_input, layout[2].inner(Margin::new(1, 0)));
+
+    let hint = Paragraph::new(
+        "e.g. site:youtube size>1GiB status:failed added<1w\n\
+         keys: status, category, site, tag, size, added",
+    )
+    .style(Style::default().fg(model.theme.muted))
+    .wrap(Wrap { trim: true });
+
+    frame.render_widget(hint, layout[3].inner(Margin::new(2, 0)));
+
+    match model.input_state.focused {
+        FocusedInput::FilterName => {
+            let x = model
+                .input_state
+                .filter_name
+                .visual_cursor()
+                .max(name_scroll)
+                - name_scroll
+                + 2;
+            frame.set_cursor_position((layout[1].x + x as u16, layout[1].y + 1));
+        }
+        FocusedInput::FilterQuery => {
+            let x = model
+                .input_state
+                .filter_query
+                .visual_cursor()
+                .max(query_scroll)
+                - query_scroll
+                + 2;
+            frame.set_cursor_position((layout[2].x + x as u16, layout[2].y + 1));
+        }
+        _ => {}
+    }
+
+    let [cancel_area, submit_area] = LayoutAreas::modal_buttons(area);
+
+    let cancel_button = Paragraph::new("CENCEL 󱊷")
+        .style(Style::default().fg(model.theme.destructive))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::bordered());
+
+    let submit_button = Paragraph::new("SAVE 󰌑")
+        .style(Style::default().fg(model.theme.success))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::bordered());
+
+    frame.render_widget(cancel_button, cancel_area);
+    frame.render_widget(submit_button, submit_area);
+}
@@ -0,0 +1,83 @@
+/// list every key binding active in the panel the help was opened from
+use ratatui::{
+    Frame,
+    layout::{Margin, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::{
+    model::state::{ActiveTab, Model},
+    update::keymap::Context,
+};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, active_tab: &ActiveTab) {
+    Clear.render(area, frame.buffer_mut());
+
+    let context = Context::from(&model.overlay_panel);
+    let active = model.keymap.active(&model.overlay_panel, active_tab);
+
+    let mut lines = Vec::new();
+    for section in [Context::Global, context] {
+        let bindings = active
+            .iter()
+            .filter(|(c, _, _)| *c == section)
+            .collect::<Vec<_>>();
+        if bindings.is_empty() {
+            continue;
+        }
+
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(
+            section.name().replace('_', " ").to_uppercase(),
+            Style::default()
+                .fg(model.theme.muted)
+                .add_modifier(Modifier::BOLD),
+        )));
+
+        for (_, action, keys) in bindings {
+            let keys = keys
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {keys:<20}"),
+                    Style::default().fg(model.theme.primary),
+                ),
+                Span::from(model.keymap.describe(*action)),
+            ]));
+        }
+    }
+
+    let height = area.height.saturating_sub(2);
+    let max_scroll = (lines.len() as u16).saturating_sub(height);
+    model.help_scroll = model.help_scroll.min(max_scroll);
+
+    let block = Block::bordered()
+        .border_style(Style::default().fg(model.theme.border_active))
+        .title(
+            Line::from(vec![
+                Span::from("[ "),
+                Span::styled(
+                    format!("KEYS · {}", context.name().replace('_', " ").to_uppercase()),
+                    Style::default().fg(model.theme.primary),
+                ),
+                Span::from(" ]"),
+            ])
+            .centered(),
+        )
+        .style(match model.theme.background {
+            Some(color) => Style::default().bg(color).fg(model.theme.forground),
+            None => Style::default().fg(model.theme.forground),
+        });
+
+    frame.render_widget(block, area);
+
+    let paragraph = Paragraph::new(lines).scroll((model.help_scroll, 0));
+    frame.render_widget(paragraph, area.inner(Margin::new(2, 1)));
+}
@@ -0,0 +1,66 @@
+/// recent warnings and errors of the app log, newest at the bottom
+use log::Level;
+use ratatui::{
+    Frame,
+    layout::{Margin, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use crate::model::{app_log, downloader::format_timestamp, state::Model};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(Style::default().fg(model.theme.border))
+        .title(Line::from(vec![
+            Span::from("[ "),
+            Span::styled("LOG", Style::default().fg(model.theme.primary)),
+            Span::from(" ]"),
+        ]))
+        .title(
+            Line::from(Span::styled(
+                format!(" level {} ", app_log::level().as_str().to_lowercase()),
+                Style::default().fg(model.theme.muted),
+            ))
+            .right_aligned(),
+        );
+
+    let entries = app_log::recent();
+    let visible = area.inner(Margin::new(1, 1)).height as usize;
+    let lines = entries
+        .iter()
+        .skip(entries.len().saturating_sub(visible))
+        .map(|entry| {
+            let color = match entry.level {
+                Level::Error => model.theme.destructive,
+                _ => model.theme.warning,
+            };
+            // the date is left out, the panel only holds this session's entries
+            let time = format_timestamp(entry.time);
+            Line::from(vec![
+                Span::styled(
+                    format!(" {} ", time.get(11..19).unwrap_or_default()),
+                    Style::default().fg(model.theme.muted),
+                ),
+                Span::styled(
+                    format!("{:<5} ", entry.level.as_str()),
+                    Style::default().fg(color),
+                ),
+                Span::from(entry.message.replace('\n', " ")),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let paragraph = if lines.is_empty() {
+        Paragraph::new(Line::styled(
+            " No warnings or errors",
+            Style::default().fg(model.theme.muted),
+        ))
+    } else {
+        Paragraph::new(lines)
+    };
+    frame.render_widget(paragraph.block(block), area);
+}
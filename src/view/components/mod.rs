@@ -1,8 +1,14 @@
 pub mod action_button;
 pub mod destination_input_modal;
+pub mod details;
+pub mod filter_input_modal;
+pub mod help_modal;
+pub mod log_panel;
 pub mod logo;
 pub mod menu;
+pub mod palette_modal;
 pub mod progress_bar;
+pub mod prompt_modal;
 pub mod scrollbar;
 pub mod source_input_modal;
 pub mod static_modal;
@@ -0,0 +1,93 @@
+/// command palette, an input with the matching commands below it
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Widget},
+};
+
+use crate::{
+    model::state::Model,
+    update::palette::{self, Entry},
+};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
+    Clear.render(area, frame.buffer_mut());
+
+    let block = Block::bordered()
+        .border_style(Style::default().fg(model.theme.border_active))
+        .title(
+            Line::from(vec![
+                Span::from("[ "),
+                Span::styled("COMMANDS", Style::default().fg(model.theme.primary)),
+                Span::from(" ]"),
+            ])
+            .centered(),
+        )
+        .style(match model.theme.background {
+            Some(color) => Style::default().bg(color).fg(model.theme.forground),
+            None => Style::default().fg(model.theme.forground),
+        });
+
+    frame.render_widget(block, area);
+
+    let layout = Layout::vertical(vec![
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Min(1),
+    ])
+    .split(area.inner(Margin::new(1, 0)));
+
+    let width = layout[1].width.max(5) - 5;
+    let scroll = model.palette.input.visual_scroll(width as usize);
+    let input = Paragraph::new(format!(":{}", model.palette.input.value()))
+        .scroll((0, scroll as u16))
+        .block(Block::bordered().border_style(Style::default().fg(model.theme.border)));
+
+    frame.render_widget(input, layout[1]);
+
+    let x = model.palette.input.visual_cursor().max(scroll) - scroll + 2;
+    frame.set_cursor_position((layout[1].x + x as u16, layout[1].y + 1));
+
+    let items = palette::entries(
+        model.palette.input.value(),
+        &model.palette.history,
+        model.plugins.commands(),
+    )
+    .into_iter()
+    .map(|entry| match entry {
+        Entry::History(line) => ListItem::new(Line::from(vec![
+            Span::styled(" ", Style::default().fg(model.theme.muted)),
+            Span::from(line.to_string()),
+        ])),
+        Entry::Command(command) => ListItem::new(Line::from(vec![
+            Span::from(format!(" {:<12}", command.name)),
+            Span::styled(
+                format!("{:<12}", command.args),
+                Style::default().fg(model.theme.warning),
+            ),
+            Span::styled(command.description, Style::default().fg(model.theme.muted)),
+        ])),
+        Entry::Plugin(command) => ListItem::new(Line::from(vec![
+            Span::from(format!(" {:<12}", command.name)),
+            Span::styled(
+                format!("{:<12}", command.args),
+                Style::default().fg(model.theme.warning),
+            ),
+            Span::styled(
+                command.description.clone(),
+                Style::default().fg(model.theme.muted),
+            ),
+        ])),
+    })
+    .collect::<Vec<_>>();
+
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .fg(model.theme.primary)
+            .add_modifier(Modifier::BOLD),
+    );
+    let mut state = ListState::default().with_selected(Some(model.palette.selected));
+    frame.render_stateful_widget(list, layout[2].inner(Margin::new(1, 0)), &mut state);
+}
@@ -6,9 +6,34 @@ use ratatui::{
     widgets::{Block, BorderType, Borders, Gauge},
 };
 
-use crate::model::state::Model;
+use crate::model::state::{ActivePanel, ActiveTab, Model};
+
+pub fn render(
+    model: &mut Model,
+    frame: &mut Frame,
+    area: Rect,
+    active_panel: &ActivePanel,
+    active_tab: &ActiveTab,
+) {
+    // most relevant keys of the focused panel, e.g. "a add a task · p pause"
+    let mut hints = vec![Span::from(" ")];
+    for (i, (keys, description)) in model
+        .keymap
+        .hints(active_panel, active_tab)
+        .into_iter()
+        .enumerate()
+    {
+        if i > 0 {
+            hints.push(Span::styled(" · ", Style::default().fg(model.theme.border)));
+        }
+        hints.push(Span::styled(keys, Style::default().fg(model.theme.primary)));
+        hints.push(Span::styled(
+            format!(" {description}"),
+            Style::default().fg(model.theme.muted),
+        ));
+    }
+    hints.push(Span::from(" "));
 
-pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
     let progress_bar = Gauge::default()
         .block(
             Block::default()
@@ -19,6 +44,24 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
                     Span::styled("PROGRESS", Style::default().fg(model.theme.primary)),
                     Span::from(" ]"),
                 ]))
+                .title(
+                    Line::from(vec![
+                        Span::styled(
+                            model.pending_keys.clone(),
+                            Style::default().fg(model.theme.warning),
+                        ),
+                        Span::styled(
+                            match model.unseen_log {
+                                0 => String::new(),
+                                1 => " 1 new log entry ".to_string(),
+                                n => format!(" {n} new log entries "),
+                            },
+                            Style::default().fg(model.theme.destructive),
+                        ),
+                    ])
+                    .right_aligned(),
+                )
+                .title_bottom(Line::from(hints))
                 .border_style(Style::default().fg(model.theme.border)),
         )
         .gauge_style(
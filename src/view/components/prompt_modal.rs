@@ -0,0 +1,95 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::{
+    model::state::{Model, PromptKind},
+    view::layout::LayoutAreas,
+};
+
+pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, kind: &PromptKind) {
+    Clear.render(area, frame.buffer_mut());
+
+    let (title, label, hint) = match kind {
+        PromptKind::Destination => (
+            "MOVE TASKS",
+            "[ Download Path ]",
+            "Finished files are moved to the new directory",
+        ),
+        PromptKind::Tags => (
+            "TAG TASKS",
+            "[ Tags ]",
+            "Comma separated, prefix a tag with - to remove it",
+        ),
+        PromptKind::Connections => (
+            "CONNECTIONS",
+            "[ Connections ]",
+            "1 to 32 for the next runs of direct downloads, empty for the default",
+        ),
+        PromptKind::Backend => (
+            "BACKEND",
+            "[ Backend ]",
+            "yt-dlp, http or aria2 for the next runs, empty to choose from the URL",
+        ),
+    };
+
+    let block = Block::bordered()
+        .border_style(Style::default().fg(model.theme.border_active))
+        .title(
+            Line::from(vec![
+                Span::from("[ "),
+                Span::styled(title, Style::default().fg(model.theme.primary)),
+                Span::from(" ]"),
+            ])
+            .centered(),
+        )
+        .style(match model.theme.background {
+            Some(color) => Style::default().bg(color).fg(model.theme.forground),
+            None => Style::default().fg(model.theme.forground),
+        });
+
+    frame.render_widget(block, area);
+
+    let layout = Layout::vertical(vec![
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Min(1),
+        Constraint::Length(5),
+    ])
+    .split(area);
+
+    let width = layout[1].width.max(3) - 5;
+    let scroll = model.input_state.prompt.visual_scroll(width as usize);
+
+    let input = Paragraph::new(model.input_state.prompt.value())
+        .style(Style::default().fg(model.theme.forground))
+        .scroll((0, scroll as u16))
+        .block(Block::bordered().title(label));
+
+    frame.render_widget(input, layout[1].inner(Margin::new(1, 0)));
+
+    let hint = Paragraph::new(hint).style(Style::default().fg(model.theme.muted));
+    frame.render_widget(hint, layout[2].inner(Margin::new(2, 0)));
+
+    let x = model.input_state.prompt.visual_cursor().max(scroll) - scroll + 2;
+    frame.set_cursor_position((layout[1].x + x as u16, layout[1].y + 1));
+
+    let [cancel_area, submit_area] = LayoutAreas::modal_buttons(area);
+
+    let cancel_button = Paragraph::new("CENCEL 󱊷")
+        .style(Style::default().fg(model.theme.destructive))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::bordered());
+
+    let submit_button = Paragraph::new("SUBMIT 󰌑")
+        .style(Style::default().fg(model.theme.success))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::bordered());
+
+    frame.render_widget(cancel_button, cancel_area);
+    frame.render_widget(submit_button, submit_area);
+}
@@ -6,7 +6,7 @@ use ratatui::{
     widgets::{Block, Clear, Paragraph, Widget},
 };
 
-use crate::model::state::Model;
+use crate::{model::state::Model, view::layout::LayoutAreas};
 
 pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
     Clear.render(area, frame.buffer_mut());
@@ -49,18 +49,7 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
     let x = model.input_state.source.visual_cursor().max(scroll) - scroll + 2;
     frame.set_cursor_position((layout[1].x + x as u16, layout[1].y + 1));
 
-    // weird trick to center but all I know is constraint!
-    let button_layout = Layout::horizontal(vec![
-        Constraint::Min(1),
-        Constraint::Length(14), //button
-        Constraint::Length(1),  // spacer
-        Constraint::Length(14), //button
-        Constraint::Min(1),
-    ])
-    .split(layout[3].inner(Margin {
-        horizontal: 1,
-        vertical: 1,
-    }));
+    let [cancel_area, submit_area] = LayoutAreas::modal_buttons(area);
 
     let cancel_button = Paragraph::new("CENCEL 󱊷")
         .style(Style::default().fg(model.theme.destructive))
@@ -72,6 +61,6 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect) {
         .alignment(ratatui::layout::Alignment::Center)
         .block(Block::bordered());
 
-    frame.render_widget(cancel_button, button_layout[1]);
-    frame.render_widget(submit_button, button_layout[3]);
+    frame.render_widget(cancel_button, cancel_area);
+    frame.render_widget(submit_button, submit_area);
 }
@@ -1,9 +1,9 @@
-/// display info or error modal
+/// display info, error or confirm modal
 use ratatui::{
     Frame,
     layout::{Constraint, Layout, Rect},
     style::Style,
-    widgets::{Block, Clear, Paragraph, Widget},
+    widgets::{Block, Clear, Paragraph, Widget, Wrap},
 };
 
 use crate::model::state::{ModalType, Model};
@@ -15,6 +15,7 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, modal_type: &Mod
         .border_style(Style::default().fg(match modal_type {
             ModalType::Info => model.theme.success,
             ModalType::Error => model.theme.destructive,
+            ModalType::Confirm => model.theme.warning,
             _ => model.theme.muted, // unreachable case
         }))
         .style(match model.theme.background {
@@ -30,8 +31,10 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, modal_type: &Mod
         .style(Style::default().fg(match modal_type {
             ModalType::Info => model.theme.success,
             ModalType::Error => model.theme.destructive,
+            ModalType::Confirm => model.theme.warning,
             _ => model.theme.muted, // unreachable case
         }))
+        .wrap(Wrap { trim: false })
         .block(block);
     frame.render_widget(paragraph, centered_layout[0]);
 }
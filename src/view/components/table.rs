@@ -28,27 +28,28 @@ pub fn render(
         .add_modifier(Modifier::REVERSED)
         .fg(model.theme.primary);
 
-    let header = ["Name", "Speed", "Size", "Progress", "ETA", "Status"]
+    let header = ["", "Name", "Speed", "Size", "Progress", "ETA", "Status"]
         .into_iter()
         .map(|c| Cell::from(Text::from(c.to_ascii_uppercase().to_string())))
         .collect::<Row>()
         .style(header_style)
         .height(1);
 
+    let manager = model.downloader.manager(active_tab);
     let tasks = match active_tab {
-        ActiveTab::Single => model.downloader.single.state.filtered_downloads.clone(),
-        ActiveTab::Batch => model.downloader.batch.state.filtered_downloads.clone(),
-        ActiveTab::Playlist => model.downloader.playlist.state.filtered_downloads.clone(),
+        ActiveTab::Single | ActiveTab::Batch | ActiveTab::Playlist => {
+            manager.state.filtered_downloads.clone()
+        }
         _ => Default::default(),
     };
+    let selection = manager.state.selection.clone();
+    let visual_mode = manager.state.visual_anchor.is_some();
 
-    let rows = tasks.iter().enumerate().map(|(i, data)| {
-        // let color = match i % 2 {
-        //     0 => model.theme.muted,
-        //     _ => model.theme.muted,
-        // };
+    let rows = tasks.iter().map(|data| {
+        let selected = selection.contains(&data.id());
 
         let item = [
+            Text::from(if selected { "●" } else { "" }),
             Text::from(data.title.to_string()),
             Text::from(data.download_speed.clone()),
             Text::from(data.file_size.clone()),
@@ -59,13 +60,42 @@ pub fn render(
         item.into_iter()
             .map(|content| Cell::from(Text::from(format!("\n{content}\n"))))
             .collect::<Row>()
-            .style(Style::new().fg(model.theme.forground))
+            .style(Style::new().fg(if selected {
+                model.theme.primary
+            } else {
+                model.theme.forground
+            }))
             .height(3)
     });
 
+    let mut title = vec![
+        Span::from("[ "),
+        Span::styled(
+            format!("{} TASKS", active_tab.to_string().to_uppercase()),
+            Style::default().fg(model.theme.primary),
+        ),
+        Span::from(" ]"),
+    ];
+    if visual_mode {
+        title.push(Span::styled(
+            " VISUAL ",
+            Style::default()
+                .fg(model.theme.warning_forground)
+                .bg(model.theme.warning)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if !selection.is_empty() {
+        title.push(Span::styled(
+            format!(" {} SELECTED ", selection.len()),
+            Style::default().fg(model.theme.warning),
+        ));
+    }
+
     let t = Table::new(
         rows,
         [
+            Constraint::Length(2),  // selection marker
             Constraint::Min(30),    // name
             Constraint::Length(15), // speed
             Constraint::Length(15), // size
@@ -78,14 +108,7 @@ pub fn render(
         Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Plain)
-            .title(Line::from(vec![
-                Span::from("[ "),
-                Span::styled(
-                    format!("{} TASKS", active_tab.to_string().to_uppercase()),
-                    Style::default().fg(model.theme.primary),
-                ),
-                Span::from(" ]"),
-            ]))
+            .title(Line::from(title))
             .border_style(Style::default().fg(match active_panel {
                 ActivePanel::Content => model.theme.border_active,
                 _ => model.theme.border,
@@ -110,3 +133,13 @@ pub fn render(
         },
     );
 }
+
+/// Index of the task rendered at row `y`, each task takes three rows below the border,
+/// the top padding and the header
+pub fn row_at(area: Rect, offset: usize, y: u16) -> Option<usize> {
+    let top = area.y + 3;
+    if y < top || y >= area.bottom().saturating_sub(1) {
+        return None;
+    }
+    Some(offset + (y - top) as usize / 3)
+}
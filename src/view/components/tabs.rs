@@ -7,8 +7,10 @@ use ratatui::{
 
 use crate::model::state::{ActiveTab, Model};
 
+const TITLES: [&str; 5] = ["SINGLE", "BATCH", "PLAYLIST", "SETTINGS", "ABOUT"];
+
 pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, active_tab: &ActiveTab) {
-    let tabs = Tabs::new(vec!["SINGLE", "BATCH", "PLAYLIST", "SETTINGS", "ABOUT"])
+    let tabs = Tabs::new(TITLES.to_vec())
         .select(*active_tab as usize)
         .block(
             Block::default()
@@ -26,3 +28,17 @@ pub fn render(model: &mut Model, frame: &mut Frame, area: Rect, active_tab: &Act
 
     frame.render_widget(tabs, area);
 }
+
+/// Index of the tab rendered at column `x`, titles are padded by a space on both sides
+/// and separated by a one column divider
+pub fn tab_at(area: Rect, x: u16) -> Option<usize> {
+    let mut start = area.x + 1;
+    for (i, title) in TITLES.iter().enumerate() {
+        let end = start + title.chars().count() as u16 + 2;
+        if (start..end).contains(&x) {
+            return Some(i);
+        }
+        start = end + 1;
+    }
+    None
+}
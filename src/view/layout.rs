@@ -1,4 +1,4 @@
-use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::layout::{Constraint, Layout, Margin, Rect};
 
 pub struct LayoutAreas {
     pub logo: Rect,
@@ -6,12 +6,14 @@ pub struct LayoutAreas {
     pub tabs: Rect,
     pub action_button: Rect,
     pub content: Rect,
+    /// Below the task list when the log panel is shown, empty otherwise
+    pub log: Rect,
     pub progress_bar: Rect,
     pub modal: Rect,
 }
 
 impl LayoutAreas {
-    pub fn compute(area: Rect) -> Self {
+    pub fn compute(area: Rect, show_log: bool) -> Self {
         let main_layout = Layout::vertical(vec![
             Constraint::Min(10),   // main content
             Constraint::Length(3), // progress bar
@@ -36,6 +38,12 @@ impl LayoutAreas {
         ])
         .split(body_layout[1]);
 
+        let table_layout = Layout::vertical(vec![
+            Constraint::Min(10),                               // main table
+            Constraint::Length(if show_log { 10 } else { 0 }), // log panel
+        ])
+        .split(content_layout[1]);
+
         let action_layout = Layout::horizontal(vec![
             Constraint::Min(20),    // tabs
             Constraint::Length(16), // action button
@@ -55,9 +63,31 @@ impl LayoutAreas {
             menu: sidebar_layout[1],
             tabs: action_layout[0],
             action_button: action_layout[1],
-            content: content_layout[1],
+            content: table_layout[0],
+            log: table_layout[1],
             progress_bar: main_layout[1],
             modal: modal_area,
         }
     }
+
+    /// The CANCEL and SUBMIT buttons at the bottom of an input modal
+    pub fn modal_buttons(modal: Rect) -> [Rect; 2] {
+        let bottom =
+            Layout::vertical(vec![Constraint::Min(1), Constraint::Length(5)]).split(modal)[1];
+
+        // weird trick to center but all I know is constraint!
+        let button_layout = Layout::horizontal(vec![
+            Constraint::Min(1),
+            Constraint::Length(14), //button
+            Constraint::Length(1),  // spacer
+            Constraint::Length(14), //button
+            Constraint::Min(1),
+        ])
+        .split(bottom.inner(Margin {
+            horizontal: 1,
+            vertical: 1,
+        }));
+
+        [button_layout[1], button_layout[3]]
+    }
 }
@@ -1,5 +1,5 @@
 pub mod components;
-mod layout;
+pub mod layout;
 
 use ratatui::{
     Frame,
@@ -11,7 +11,8 @@ use crate::{
     model::state::{ActivePanel, ActiveTab, ModalType, Model},
     view::{
         components::{
-            action_button, destination_input_modal, logo, menu, progress_bar, scrollbar,
+            action_button, destination_input_modal, details, filter_input_modal, help_modal,
+            log_panel, logo, menu, palette_modal, progress_bar, prompt_modal, scrollbar,
             source_input_modal, static_modal, table, tabs,
         },
         layout::LayoutAreas,
@@ -37,8 +38,9 @@ pub fn draw(
     };
 
     frame.render_widget(&screen, frame.area());
+    model.screen = frame.area();
 
-    let layout = LayoutAreas::compute(frame.area());
+    let layout = LayoutAreas::compute(frame.area(), model.show_log);
     action_button::render(model, frame, layout.action_button);
     logo::render(model, frame, layout.logo);
     menu::render(model, frame, layout.menu, active_panel);
@@ -75,7 +77,10 @@ pub fn draw(
         _ => {}
     }
 
-    progress_bar::render(model, frame, layout.progress_bar);
+    if model.show_log {
+        log_panel::render(model, frame, layout.log);
+    }
+    progress_bar::render(model, frame, layout.progress_bar, active_panel, active_tab);
 
     if let ActivePanel::Modal(modal_type) = active_panel {
         match modal_type {
@@ -85,11 +90,22 @@ pub fn draw(
             ModalType::DestinationInput => {
                 destination_input_modal::render(model, frame, layout.modal);
             }
-            ModalType::Confirm => {
-                todo!()
-                // Render confirm modal
+            ModalType::FilterInput => {
+                filter_input_modal::render(model, frame, layout.modal);
+            }
+            ModalType::Prompt(kind) => {
+                prompt_modal::render(model, frame, layout.modal, kind);
+            }
+            ModalType::Details => {
+                details::render(model, frame, layout.content);
+            }
+            ModalType::Palette => {
+                palette_modal::render(model, frame, layout.modal);
+            }
+            ModalType::Help => {
+                help_modal::render(model, frame, layout.modal, active_tab);
             }
-            ModalType::Info | ModalType::Error => {
+            ModalType::Info | ModalType::Error | ModalType::Confirm => {
                 static_modal::render(model, frame, layout.modal, modal_type);
             }
         }